@@ -1,6 +1,9 @@
 use kg_tree::opath::NodeSet;
 
-use op_core::outcome::Outcome;
+use op_core::outcome::{HostPlan, HostReachability, Outcome};
+use op_exec::rsync::compare::State;
+use op_exec::rsync::DiffInfo;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub enum DisplayFormat {
@@ -72,6 +75,18 @@ pub fn display_outcome(outcome: &Outcome, format: DisplayFormat) {
         Outcome::NodeSet(ref node_set) => {
             display_nodeset(&*node_set.lock(), format);
         }
+        Outcome::Plan(ref plans) => {
+            display_plan(plans, format);
+        }
+        Outcome::Failed(ref err) => {
+            eprintln!("Failed: {}", err);
+        }
+        Outcome::Reachability(ref hosts) => {
+            display_reachability(hosts, format);
+        }
+        Outcome::FileDiff(ref diffs) => {
+            display_file_diff(diffs, format);
+        }
         _ => unimplemented!(),
     }
 }
@@ -87,18 +102,29 @@ fn display_nodeset(ns: &NodeSet, format: DisplayFormat) {
 }
 
 fn display_nodeset_json(ns: &NodeSet) {
+    println!("{}", format_nodeset_json(ns));
+}
+
+/// Formats nodeset as JSON. Empty nodeset (no matches) is formatted as an empty array,
+/// single node as is and multiple nodes as an array.
+fn format_nodeset_json(ns: &NodeSet) -> String {
     match *ns {
-        NodeSet::Empty => {}
-        NodeSet::One(ref node) => println!("{}", node.to_json_pretty()),
-        NodeSet::Many(ref nodes) => println!("{}", serde_json::to_string_pretty(nodes).unwrap()),
+        NodeSet::Empty => "[]".to_string(),
+        NodeSet::One(ref node) => node.to_json_pretty(),
+        NodeSet::Many(ref nodes) => serde_json::to_string_pretty(nodes).unwrap(),
     }
 }
 
 fn display_nodeset_yaml(ns: &NodeSet) {
+    println!("{}", format_nodeset_yaml(ns));
+}
+
+/// Formats nodeset as YAML, with the same shape rules as [`format_nodeset_json`].
+fn format_nodeset_yaml(ns: &NodeSet) -> String {
     match *ns {
-        NodeSet::Empty => {}
-        NodeSet::One(ref node) => println!("{}", node.to_yaml()),
-        NodeSet::Many(ref nodes) => println!("{}", serde_yaml::to_string(nodes).unwrap()),
+        NodeSet::Empty => "[]".to_string(),
+        NodeSet::One(ref node) => node.to_yaml(),
+        NodeSet::Many(ref nodes) => serde_yaml::to_string(nodes).unwrap(),
     }
 }
 
@@ -118,10 +144,209 @@ fn display_nodeset_text(ns: &NodeSet) {
     }
 }
 
+/// Displays nodeset as an aligned table if it is a list of objects with uniform keys,
+/// otherwise falls back to JSON.
 fn display_nodeset_table(ns: &NodeSet) {
-    match *ns {
-        NodeSet::Empty => {}
-        NodeSet::One(ref node) => println!("{}", node.to_yaml()),
-        NodeSet::Many(ref nodes) => println!("{}", toml::to_string(nodes).unwrap()),
+    let rows = match *ns {
+        NodeSet::Empty => return,
+        NodeSet::One(ref node) => match serde_json::to_value(node).unwrap() {
+            serde_json::Value::Array(elems) => elems,
+            _ => return display_nodeset_json(ns),
+        },
+        NodeSet::Many(ref nodes) => nodes
+            .iter()
+            .map(|n| serde_json::to_value(n).unwrap())
+            .collect(),
+    };
+
+    match format_table(&rows) {
+        Some(table) => print!("{}", table),
+        None => display_nodeset_json(ns),
+    }
+}
+
+/// Formats rows as a table with a header. Returns `None` if rows are not objects
+/// with the same set of keys.
+fn format_table(rows: &[serde_json::Value]) -> Option<String> {
+    use std::fmt::Write;
+
+    let columns: Vec<&String> = rows.first()?.as_object()?.keys().collect();
+    if columns.is_empty() {
+        return None;
+    }
+
+    let mut cells: Vec<Vec<String>> = Vec::with_capacity(rows.len() + 1);
+    cells.push(columns.iter().map(|c| c.to_string()).collect());
+    for row in rows {
+        let obj = row.as_object()?;
+        if obj.len() != columns.len() {
+            return None;
+        }
+        let mut line = Vec::with_capacity(columns.len());
+        for c in columns.iter() {
+            line.push(format_cell(obj.get(*c)?));
+        }
+        cells.push(line);
+    }
+
+    let mut widths = vec![0; columns.len()];
+    for line in cells.iter() {
+        for (w, cell) in widths.iter_mut().zip(line.iter()) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+
+    let mut out = String::new();
+    for (i, line) in cells.iter().enumerate() {
+        let mut l = String::new();
+        for (cell, w) in line.iter().zip(widths.iter()) {
+            write!(l, "{:width$}  ", cell, width = w).unwrap();
+        }
+        writeln!(out, "{}", l.trim_end()).unwrap();
+        if i == 0 {
+            let sep: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+            writeln!(out, "{}", sep.join("  ")).unwrap();
+        }
+    }
+    Some(out)
+}
+
+fn format_cell(value: &serde_json::Value) -> String {
+    match *value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(ref s) => s.clone(),
+        ref v => v.to_string(),
+    }
+}
+
+fn display_plan(plans: &[HostPlan], format: DisplayFormat) {
+    match format {
+        DisplayFormat::Json => println!("{}", serde_json::to_string_pretty(plans).unwrap()),
+        DisplayFormat::Yaml => println!("{}", serde_yaml::to_string(plans).unwrap()),
+        DisplayFormat::Toml | DisplayFormat::Text | DisplayFormat::Table => {
+            display_plan_text(plans)
+        }
+    }
+}
+
+fn display_reachability(hosts: &BTreeMap<String, HostReachability>, format: DisplayFormat) {
+    match format {
+        DisplayFormat::Json => println!("{}", serde_json::to_string_pretty(hosts).unwrap()),
+        DisplayFormat::Yaml => println!("{}", serde_yaml::to_string(hosts).unwrap()),
+        DisplayFormat::Toml => println!("{}", toml::to_string(hosts).unwrap()),
+        DisplayFormat::Text | DisplayFormat::Table => display_reachability_text(hosts),
+    }
+}
+
+fn display_reachability_text(hosts: &BTreeMap<String, HostReachability>) {
+    let mut unreachable = 0;
+    for (host, status) in hosts.iter() {
+        match status {
+            HostReachability::Reachable => println!("{}: reachable", host),
+            HostReachability::Unreachable(err) => {
+                unreachable += 1;
+                println!("{}: unreachable: {}", host, err.trim_end());
+            }
+        }
+    }
+    println!(
+        "{} reachable, {} unreachable",
+        hosts.len() - unreachable,
+        unreachable
+    );
+}
+
+/// Prints file comparison results. Structured formats include every compared file
+/// with its full state, text format lists only changed files.
+fn display_file_diff(diffs: &[DiffInfo], format: DisplayFormat) {
+    match format {
+        DisplayFormat::Json => println!("{}", serde_json::to_string_pretty(diffs).unwrap()),
+        DisplayFormat::Yaml => println!("{}", serde_yaml::to_string(diffs).unwrap()),
+        DisplayFormat::Toml | DisplayFormat::Text | DisplayFormat::Table => {
+            let mut changed = 0;
+            for diff in diffs.iter().filter(|d| *d.state() != State::Identical) {
+                changed += 1;
+                println!("{}", format_file_change(diff));
+            }
+            if changed == 0 {
+                println!("(no changes)");
+            }
+        }
+    }
+}
+
+fn display_plan_text(plans: &[HostPlan]) {
+    for plan in plans {
+        println!("{}:", plan.host());
+        if plan.is_empty() {
+            println!("  (no changes)");
+            continue;
+        }
+        for diff in plan.files() {
+            println!("  {}", format_file_change(diff));
+        }
+        for cmd in plan.commands() {
+            println!("  $ {}", cmd);
+        }
+    }
+}
+
+fn format_file_change(diff: &DiffInfo) -> String {
+    let path = diff.file_path().display();
+    match diff.state() {
+        State::Missing => format!("+ {}", path),
+        State::Extraneous => format!("- {}", path),
+        State::Identical => format!("  {}", path),
+        State::Modified(_) => {
+            let state = diff.state();
+            let mut changes = Vec::new();
+            if state.is_modified_content() {
+                changes.push("content");
+            }
+            if state.is_modified_chmod() {
+                changes.push("chmod");
+            }
+            if state.is_modified_chown() {
+                changes.push("chown");
+            }
+            if changes.is_empty() {
+                format!("~ {}", path)
+            } else {
+                format!("~ {} ({})", path, changes.join(", "))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kg_tree::NodeRef;
+
+    #[test]
+    fn nodeset_json_scalar() {
+        let ns = NodeSet::One(NodeRef::integer(5));
+        assert_eq!("5", format_nodeset_json(&ns));
+        assert!(format_nodeset_yaml(&ns).contains('5'));
+    }
+
+    #[test]
+    fn nodeset_json_array() {
+        let ns = NodeSet::Many(vec![NodeRef::integer(1), NodeRef::string("a")]);
+        let json: serde_json::Value = serde_json::from_str(&format_nodeset_json(&ns)).unwrap();
+        assert_eq!(serde_json::json!([1, "a"]), json);
+
+        let yaml: serde_json::Value = serde_yaml::from_str(&format_nodeset_yaml(&ns)).unwrap();
+        assert_eq!(serde_json::json!([1, "a"]), yaml);
+    }
+
+    #[test]
+    fn nodeset_json_empty() {
+        let ns = NodeSet::Empty;
+        let json: serde_json::Value = serde_json::from_str(&format_nodeset_json(&ns)).unwrap();
+        assert_eq!(serde_json::json!([]), json);
+
+        let yaml: serde_json::Value = serde_yaml::from_str(&format_nodeset_yaml(&ns)).unwrap();
+        assert_eq!(serde_json::json!([]), yaml);
     }
 }
@@ -0,0 +1,132 @@
+use kg_diag::Diag;
+use op_core::config::ConfigErrorDetail;
+use op_core::outcome::Outcome;
+use op_engine::OperationErrorDetail;
+use op_exec::command::ssh::SshErrorDetail;
+use op_exec::command::CommandErrorDetail;
+use op_exec::rsync::compare::State;
+use op_rev::GitErrorDetail;
+
+/// Process exit codes returned by `op`. Values are stable and can be relied on in scripts.
+///
+/// | code | meaning                                                        |
+/// |------|----------------------------------------------------------------|
+/// | 0    | success                                                        |
+/// | 1    | any other error                                                |
+/// | 2    | success, changes were made (or would be made in dry-run)       |
+/// | 3    | check failed, eg. command exited with non-zero status          |
+/// | 4    | host unreachable                                               |
+/// | 78   | configuration error (`EX_CONFIG` from `sysexits.h`)            |
+/// | 124  | operation timed out (same as coreutils `timeout`)              |
+/// | 130  | operation cancelled (same as interrupted by `SIGINT`)          |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    Error = 1,
+    Changed = 2,
+    CheckFailed = 3,
+    Unreachable = 4,
+    ConfigError = 78,
+    Timeout = 124,
+    Cancelled = 130,
+}
+
+impl ExitCode {
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+
+    /// Significance of the code, higher wins when combining outcomes.
+    fn rank(self) -> u8 {
+        match self {
+            ExitCode::Success => 0,
+            ExitCode::Changed => 1,
+            ExitCode::CheckFailed => 2,
+            ExitCode::Error => 3,
+            ExitCode::Unreachable => 4,
+            ExitCode::ConfigError => 5,
+            ExitCode::Timeout => 6,
+            ExitCode::Cancelled => 7,
+        }
+    }
+
+    fn max(self, other: ExitCode) -> ExitCode {
+        if other.rank() > self.rank() {
+            other
+        } else {
+            self
+        }
+    }
+
+    pub fn from_outcome(outcome: &Outcome) -> ExitCode {
+        match *outcome {
+            Outcome::Command(ref out) if !out.success() => ExitCode::CheckFailed,
+            Outcome::FileCopy { status } if status != Some(0) => ExitCode::CheckFailed,
+            Outcome::FileDiff(ref diffs)
+                if diffs.iter().any(|d| *d.state() != State::Identical) =>
+            {
+                ExitCode::Changed
+            }
+            Outcome::Plan(ref plans) if plans.iter().any(|p| !p.is_empty()) => ExitCode::Changed,
+            Outcome::Failed(_) => ExitCode::CheckFailed,
+            Outcome::Reachability(ref hosts) if hosts.values().any(|h| !h.is_reachable()) => {
+                ExitCode::Unreachable
+            }
+            Outcome::Many(ref outcomes) => outcomes
+                .iter()
+                .map(ExitCode::from_outcome)
+                .fold(ExitCode::Success, ExitCode::max),
+            _ => ExitCode::Success,
+        }
+    }
+
+    /// Maps error to exit code, looking through the whole chain of causes.
+    pub fn from_error(err: &dyn Diag) -> ExitCode {
+        let detail = err.detail();
+        let code = if let Some(OperationErrorDetail::Cancelled) = detail.downcast_ref() {
+            Some(ExitCode::Cancelled)
+        } else if let Some(GitErrorDetail::Cancelled) = detail.downcast_ref() {
+            Some(ExitCode::Cancelled)
+        } else if let Some(CommandErrorDetail::Timeout { .. }) = detail.downcast_ref() {
+            Some(ExitCode::Timeout)
+        } else if detail.downcast_ref::<SshErrorDetail>().is_some() {
+            Some(ExitCode::Unreachable)
+        } else if detail.downcast_ref::<ConfigErrorDetail>().is_some() {
+            Some(ExitCode::ConfigError)
+        } else {
+            None
+        };
+
+        match code {
+            Some(code) => code,
+            None => match err.cause() {
+                Some(cause) => ExitCode::from_error(cause),
+                None => ExitCode::Error,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op_exec::rsync::DiffInfo;
+
+    #[test]
+    fn file_diff_identical_is_success() {
+        let outcome = Outcome::FileDiff(vec![
+            DiffInfo::parse(b".f         ", "a", 10).unwrap(),
+            DiffInfo::parse(b".f         ", "b", 10).unwrap(),
+        ]);
+        assert_eq!(ExitCode::Success, ExitCode::from_outcome(&outcome));
+    }
+
+    #[test]
+    fn file_diff_modified_is_changed() {
+        let outcome = Outcome::FileDiff(vec![
+            DiffInfo::parse(b".f         ", "same", 10).unwrap(),
+            DiffInfo::parse(b">f.st......", "changed", 10).unwrap(),
+        ]);
+        assert_eq!(ExitCode::Changed, ExitCode::from_outcome(&outcome));
+    }
+}
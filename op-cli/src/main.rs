@@ -11,6 +11,7 @@ use structopt::StructOpt;
 use url::Url;
 
 use display::DisplayFormat;
+use exit::ExitCode;
 
 use kg_diag::BasicDiag;
 use op_rev::RevPath;
@@ -20,10 +21,14 @@ use op_core::config::ConfigRef;
 use op_core::context::Context as ExecContext;
 use op_core::state::CoreState;
 use op_exec::command::ssh::{SshAuth, SshDest};
+use op_model::HostLimit;
 use op_engine::EngineRef;
+use std::time::Duration;
 
 mod display;
+mod exit;
 mod options;
+mod password;
 
 pub static SHORT_VERSION: &str = env!("OP_SHORT_VERSION");
 pub static LONG_VERSION: &str = env!("OP_LONG_VERSION");
@@ -33,6 +38,9 @@ fn make_path_absolute(path: &Path) -> PathBuf {
     path.canonicalize().unwrap()
 }
 
+/// Time given to operations to finish after cancellation on timeout, before engine is stopped
+const TIMEOUT_CANCEL_GRACE: Duration = Duration::from_secs(5);
+
 /// start engine and execute provided operation. Returns exit code
 fn local_run(
     current_dir: PathBuf,
@@ -40,13 +48,17 @@ fn local_run(
     ctx: ExecContext,
     disp_format: DisplayFormat,
     verbosity: u8,
-) -> Result<u32, BasicDiag> {
+    timeout: Option<Duration>,
+) -> ExitCode {
     op_log::init_tracing(verbosity, config.log());
 
-    let mut rt = EngineRef::<()>::build_runtime();
+    let mut rt = EngineRef::<()>::build_runtime_with(config.runtime());
 
     let out_res = rt.block_on(async {
-        let services = init_services(current_dir, config.clone()).await?;
+        let services = match init_services(current_dir, config.clone()).await {
+            Ok(services) => services,
+            Err(err) => return Some(Err(err)),
+        };
         let state = CoreState::new(config);
 
         let engine = EngineRef::new(services, state);
@@ -62,14 +74,90 @@ fn local_run(
                 println!("{}", o.read().progress())
             }
         });
-        let (_engine_result, res) = futures::future::join(engine.start(), res).await;
-        res.unwrap()
+
+        let run = futures::future::join(engine.start(), res);
+        let timeout = match timeout {
+            Some(timeout) => timeout,
+            None => return Some(run.await.1.unwrap()),
+        };
+        tokio::pin!(run);
+
+        let out_res = tokio::select! {
+            (_engine_result, res) = &mut run => Some(res.unwrap()),
+            _ = tokio::time::sleep(timeout) => None,
+        };
+        if out_res.is_none() {
+            // give cancelled operations a chance to clean up, without waiting for their result
+            engine.cancel_all().await;
+            let _ = tokio::time::timeout(TIMEOUT_CANCEL_GRACE, &mut run).await;
+            engine.stop();
+        }
+        out_res
     });
 
-    let outcome = out_res?;
+    let out_res = match out_res {
+        Some(out_res) => out_res,
+        None => {
+            eprintln!("Operation timed out after {:?}", timeout.unwrap());
+            return ExitCode::Timeout;
+        }
+    };
 
-    display::display_outcome(&outcome, disp_format);
-    Ok(0)
+    match out_res {
+        Ok(outcome) => {
+            display::display_outcome(&outcome, disp_format);
+            ExitCode::from_outcome(&outcome)
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from_error(&err)
+        }
+    }
+}
+
+/// Reads query expression from a file, or from standard input if `path` is `-`.
+fn read_query_file(path: &Path) -> std::io::Result<String> {
+    use std::io::Read;
+
+    let expr = if path == Path::new("-") {
+        let mut expr = String::new();
+        std::io::stdin().read_to_string(&mut expr)?;
+        expr
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(expr.trim().to_string())
+}
+
+/// Warns about uncommitted changes in model working directory. Returns `false` if
+/// the operation should not proceed.
+fn check_working_tree(model_dir: &Path, require_clean: bool) -> bool {
+    let mut rt = EngineRef::<()>::build_runtime();
+    let res: Result<Vec<PathBuf>, BasicDiag> = rt.block_on(async {
+        let repo_path = op_model::Model::resolve_manifest_dir(model_dir)?;
+        let mut repo = op_rev::open_repository(repo_path).await?;
+        repo.changed_paths().await
+    });
+
+    match res {
+        Ok(paths) if paths.is_empty() => true,
+        Ok(paths) => {
+            eprintln!("Model working directory has uncommitted changes:");
+            for p in paths.iter() {
+                eprintln!("    {}", p.display());
+            }
+            if require_clean {
+                eprintln!("Commit or revert changes, or run without --require-clean");
+                false
+            } else {
+                true
+            }
+        }
+        Err(err) => {
+            eprintln!("Cannot check model working directory status: {}", err);
+            !require_clean
+        }
+    }
 }
 
 fn main() {
@@ -82,8 +170,10 @@ fn main() {
 
     let Opts {
         config_file_path,
+        config_overrides,
         model_dir_path,
         command,
+        timeout,
         verbose,
     } = Opts::from_clap(&matches);
 
@@ -91,41 +181,88 @@ fn main() {
         .canonicalize()
         .expect("Cannot find model directory");
 
-    let config = match ConfigRef::read(&config_file_path) {
-        Err(err) => {
-            println!("Cannot read config file {} : {:?}", config_file_path, err);
-            return;
+    let config =
+        match ConfigRef::from_layers(&config_file_path, std::env::vars(), &config_overrides) {
+            Err(err) => {
+                println!("Cannot read config file {} : {:?}", config_file_path, err);
+                std::process::exit(ExitCode::ConfigError.code());
+            }
+            Ok(c) => c,
+        };
+
+    let mut disp_format = DisplayFormat::Json;
+
+    let dirty_check = match command {
+        Command::Update { require_clean, .. } | Command::Check { require_clean, .. } => {
+            Some(require_clean)
         }
-        Ok(c) => c,
+        _ => None,
     };
 
-    let mut disp_format = DisplayFormat::Json;
+    if let Some(require_clean) = dirty_check {
+        if !check_working_tree(&model_dir_path, require_clean) {
+            std::process::exit(ExitCode::Error.code());
+        }
+    }
 
     let cmd: ExecContext = match command {
         //////////////////////////////// CLI client options ////////////////////////////////
-        Command::Config { format } => {
+        Command::Config { format, defaults } => {
             disp_format = format;
 
-            ExecContext::ConfigGet
+            ExecContext::ConfigGet { defaults }
         }
-        Command::Commit { message } => {
+        Command::Commit {
+            message,
+            tag,
+            annotate,
+        } => {
             disp_format = DisplayFormat::Text;
-            ExecContext::ModelCommit(message)
+            ExecContext::ModelCommit {
+                message,
+                tag,
+                annotated: annotate,
+            }
         }
         Command::Query {
             expr,
+            file,
             model,
             format,
         } => {
             disp_format = format;
+            let expr = match (expr, file) {
+                (Some(expr), _) => expr,
+                (None, Some(path)) => match read_query_file(&path) {
+                    Ok(expr) => expr,
+                    Err(err) => {
+                        eprintln!("Cannot read query file '{}': {}", path.display(), err);
+                        std::process::exit(ExitCode::Error.code());
+                    }
+                },
+                (None, None) => unreachable!("OPATH or --file is required"),
+            };
             ExecContext::ModelQuery { model, expr }
         }
         Command::Test { format, model } => {
             disp_format = format;
             ExecContext::ModelTest { model }
         }
+        Command::Lint { format, model } => {
+            disp_format = format;
+            ExecContext::ModelLint { model }
+        }
+        Command::Fingerprint { format, model } => {
+            disp_format = format;
+            ExecContext::ModelFingerprint { model }
+        }
+        Command::Log { format, limit } => {
+            disp_format = format;
+            ExecContext::ModelLog { limit }
+        }
         Command::Diff {
             format,
+            method,
             source,
             target,
         } => {
@@ -135,6 +272,7 @@ fn main() {
             ExecContext::ModelDiff {
                 prev_model: source,
                 next_model: target,
+                method,
             }
         }
         Command::Update {
@@ -142,6 +280,7 @@ fn main() {
             source,
             target,
             dry_run,
+            require_clean: _,
         } => {
             disp_format = format;
             ExecContext::ModelUpdate {
@@ -157,20 +296,51 @@ fn main() {
         Command::Check {
             model,
             filter,
+            group,
             dry_run,
+            require_clean: _,
         } => ExecContext::ModelCheck {
             model,
             filter,
+            group,
             dry_run,
         },
         Command::Probe {
             model,
             url,
+            group,
             password,
+            password_file,
+            ask_password,
             identity_file,
             filter,
             args,
         } => {
+            let password = if let Some(password) = password {
+                eprintln!(
+                    "Warning: --password is deprecated, use --password-file or --ask-password"
+                );
+                Some(password)
+            } else if let Some(path) = password_file {
+                match password::read_password_file(&path) {
+                    Ok(password) => Some(password),
+                    Err(err) => {
+                        eprintln!("Cannot read password file '{}': {}", path.display(), err);
+                        std::process::exit(ExitCode::Error.code());
+                    }
+                }
+            } else if ask_password {
+                match password::prompt_password("SSH password: ") {
+                    Ok(password) => Some(password),
+                    Err(err) => {
+                        eprintln!("Cannot read password: {}", err);
+                        std::process::exit(ExitCode::Error.code());
+                    }
+                }
+            } else {
+                None
+            };
+
             let ssh_auth = if let Some(password) = password {
                 SshAuth::Password { password }
             } else if let Some(identity_file) = identity_file {
@@ -179,10 +349,18 @@ fn main() {
                 SshAuth::Default
             };
 
-            let ssh_dest = SshDest::from_url(&url, ssh_auth);
+            let ssh_dest = match url.map(|url| SshDest::from_url(&url, ssh_auth)) {
+                Some(Ok(dest)) => Some(dest),
+                Some(Err(err)) => {
+                    eprintln!("{}", err);
+                    std::process::exit(ExitCode::Error.code());
+                }
+                None => None,
+            };
 
             ExecContext::ModelProbe {
                 ssh_dest,
+                group,
                 model,
                 filter,
                 args,
@@ -193,27 +371,30 @@ fn main() {
         },
         Command::Remote {
             expr,
+            group,
             command,
             model,
         } => {
             let command = command.join(" ");
             ExecContext::RemoteExec {
-                expr,
+                expr: expr.unwrap_or_else(|| "$$hosts".into()),
+                group,
                 command,
                 model_path: model,
             }
         }
+        Command::Validate {
+            group,
+            limit,
+            model,
+        } => ExecContext::HostsValidate {
+            model,
+            group,
+            limit: HostLimit::new(limit),
+        },
     };
 
-    let res = local_run(model_dir_path, config, cmd, disp_format, verbose);
-
-    let exit_code = match res {
-        Ok(code) => code as i32,
-        Err(err) => {
-            eprintln!("{}", err);
-            -1
-        }
-    };
+    let exit_code = local_run(model_dir_path, config, cmd, disp_format, verbose, timeout);
 
-    std::process::exit(exit_code)
+    std::process::exit(exit_code.code())
 }
@@ -1,7 +1,9 @@
 use super::*;
 
 use self::display::DisplayFormat;
+use op_core::context::DiffMethod;
 use std::path::PathBuf;
+use std::time::Duration;
 use structopt::clap::AppSettings;
 
 fn parse_key_value(s: &str) -> Result<(String, String), String> {
@@ -19,6 +21,48 @@ fn parse_ssh_url(s: &str) -> Result<Url, String> {
     }
 }
 
+/// Parses human readable duration, eg. `30s`, `5m`, `1h30m`, `500ms`. Number without unit is in seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("duration cannot be empty".into());
+    }
+
+    let mut total = Duration::from_secs(0);
+    let mut rest = s;
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or_else(|| rest.len());
+        if num_len == 0 {
+            return Err(format!("invalid duration '{}'", s));
+        }
+        let num: u64 = rest[..num_len]
+            .parse()
+            .map_err(|_| format!("invalid duration '{}'", s))?;
+        rest = &rest[num_len..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or_else(|| rest.len());
+        let d = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(num),
+            "" | "s" => Duration::from_secs(num),
+            "m" => Duration::from_secs(num * 60),
+            "h" => Duration::from_secs(num * 3600),
+            unit => {
+                return Err(format!(
+                    "invalid duration unit '{}', expected one of: ms, s, m, h",
+                    unit
+                ))
+            }
+        };
+        total += d;
+        rest = &rest[unit_len..];
+    }
+    Ok(total)
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "op",
@@ -35,6 +79,11 @@ pub struct Opts {
     )]
     pub config_file_path: String,
 
+    /// Config value override, eg. `--set model.cache_limit=5`. Overrides config files and
+    /// `OP_`-prefixed environment variables (eg. `OP_MODEL__CACHE_LIMIT=5`)
+    #[structopt(long = "set", name = "KEY=VALUE", number_of_values = 1)]
+    pub config_overrides: Vec<String>,
+
     /// Path to model directory
     #[structopt(
         short = "m",
@@ -44,7 +93,13 @@ pub struct Opts {
     )]
     pub model_dir_path: String,
 
-    /// Verbose mode (-v, -vv, -vvv, etc.)
+    /// Maximum time the operation may run, eg. `30s`, `5m`. Operation is cancelled after timeout
+    /// and `op` exits with code 124
+    #[structopt(long = "timeout", name = "DURATION", parse(try_from_str = parse_duration))]
+    pub timeout: Option<Duration>,
+
+    /// Verbose mode (-v, -vv, -vvv, etc.). Each level shows more details in the output:
+    /// -v target names, -vv span timings, -vvv source file and line
     #[structopt(short = "v", long = "verbose", parse(from_occurrences))]
     pub verbose: u8,
 
@@ -69,6 +124,9 @@ pub enum Command {
             default_value = "toml"
         )]
         format: DisplayFormat,
+        /// Annotate every value with its source: default, config file, environment or override
+        #[structopt(long = "defaults")]
+        defaults: bool,
     },
     /// Commit current model
     #[structopt(
@@ -79,6 +137,12 @@ pub enum Command {
         /// Optional path to read model from. By default current directory model is used.
         #[structopt(name = "MESSAGE", default_value = "Model update")]
         message: String,
+        /// Tag created revision with this name
+        #[structopt(short = "t", long = "tag")]
+        tag: Option<String>,
+        /// Create annotated tag with commit message, instead of lightweight one
+        #[structopt(short = "a", long = "annotate", requires = "tag")]
+        annotate: bool,
     },
     /// Query model
     #[structopt(
@@ -99,8 +163,16 @@ pub enum Command {
         #[structopt(short = "m", long = "model", default_value = "@")]
         model: RevPath,
         /// Query expression
-        #[structopt(name = "OPATH")]
-        expr: String,
+        #[structopt(name = "OPATH", required_unless = "file")]
+        expr: Option<String>,
+        /// Read query expression from a file, `-` reads from standard input
+        #[structopt(
+            short = "F",
+            long = "file",
+            conflicts_with = "OPATH",
+            parse(from_os_str)
+        )]
+        file: Option<PathBuf>,
     },
     /// Test model
     #[structopt(
@@ -121,6 +193,63 @@ pub enum Command {
         #[structopt(name = "MODEL", default_value = "@")]
         model: RevPath,
     },
+    /// Check model for structural problems
+    #[structopt(
+        name = "lint",
+        setting = AppSettings::ColoredHelp,
+    )]
+    Lint {
+        /// Output format
+        #[structopt(
+            short = "f",
+            long = "format",
+            possible_values = &["json","yaml","toml","text","table"],
+            case_insensitive = true,
+            default_value = "yaml"
+        )]
+        format: DisplayFormat,
+        /// Model path, defaults to current working directory
+        #[structopt(name = "MODEL", default_value = "@")]
+        model: RevPath,
+    },
+    /// Print model fingerprint, a hash of model tree that changes only when model content changes
+    #[structopt(
+        name = "fingerprint",
+        setting = AppSettings::ColoredHelp,
+    )]
+    Fingerprint {
+        /// Output format
+        #[structopt(
+            short = "f",
+            long = "format",
+            possible_values = &["json","yaml","toml","text","table"],
+            case_insensitive = true,
+            default_value = "text"
+        )]
+        format: DisplayFormat,
+        /// Model path, defaults to current working directory
+        #[structopt(name = "MODEL", default_value = "@")]
+        model: RevPath,
+    },
+    /// List model revisions
+    #[structopt(
+        name = "log",
+        setting = AppSettings::ColoredHelp,
+    )]
+    Log {
+        /// Output format
+        #[structopt(
+            short = "f",
+            long = "format",
+            possible_values = &["json","yaml","toml","text","table"],
+            case_insensitive = true,
+            default_value = "yaml"
+        )]
+        format: DisplayFormat,
+        /// Maximum number of revisions to list
+        #[structopt(short = "n", long = "limit", default_value = "20")]
+        limit: usize,
+    },
     /// Compare two model versions
     #[structopt(
         name = "diff",
@@ -136,6 +265,16 @@ pub enum Command {
             default_value = "yaml"
         )]
         format: DisplayFormat,
+        /// Diff method: `model` - semantic changeset of model trees with old and new values of
+        /// updated nodes, `tree` - raw structural diff of model trees, `files` - list of files
+        /// added, removed, modified or renamed between revisions
+        #[structopt(
+            short = "m",
+            long = "method",
+            possible_values = &["model","tree","files"],
+            default_value = "model"
+        )]
+        method: DiffMethod,
         /// Target model path, defaults to current working directory
         #[structopt(name = "TARGET", default_value = "@")]
         target: RevPath,
@@ -161,6 +300,9 @@ pub enum Command {
         /// When set this flags prevents from actually executing any actions in hosts
         #[structopt(short = "d", long = "dry-run")]
         dry_run: bool,
+        /// Refuse to run when model working directory has uncommitted changes
+        #[structopt(long = "require-clean")]
+        require_clean: bool,
         /// Target model path, defaults to current working directory
         #[structopt(name = "TARGET", default_value = "@")]
         target: RevPath,
@@ -180,9 +322,15 @@ pub enum Command {
         /// Check name filter expression
         #[structopt(short = "n", long = "name")]
         filter: Option<String>,
+        /// Host group name defined in manifest `[groups]` section. Restricts checks to hosts from this group
+        #[structopt(short = "g", long = "group")]
+        group: Option<String>,
         /// When set this flags prevents from actually executing any actions in hosts
         #[structopt(short = "d", long = "dry-run")]
         dry_run: bool,
+        /// Refuse to run when model working directory has uncommitted changes
+        #[structopt(long = "require-clean")]
+        require_clean: bool,
     },
     /// Run probe from a model
     #[structopt(
@@ -191,11 +339,21 @@ pub enum Command {
     )]
     Probe {
         /// SSH connection url to remote host being probed, for example ssh://root@example.org:22
-        #[structopt(name = "URL", parse(try_from_str = parse_ssh_url))]
-        url: Url,
-        /// Password for SSH authentication
+        #[structopt(name = "URL", required_unless = "group", parse(try_from_str = parse_ssh_url))]
+        url: Option<Url>,
+        /// Host group name defined in manifest `[groups]` section. Probes all hosts from this group
+        #[structopt(short = "g", long = "group", conflicts_with = "URL")]
+        group: Option<String>,
+        /// Password for SSH authentication. Deprecated, password will be visible in shell history
+        /// and process list, use `--password-file` or `--ask-password` instead
         #[structopt(short = "P", long = "password", group = "ssh_auth")]
         password: Option<String>,
+        /// Read password for SSH authentication from the first line of a file
+        #[structopt(long = "password-file", group = "ssh_auth", parse(from_os_str))]
+        password_file: Option<PathBuf>,
+        /// Prompt for password for SSH authentication, requires interactive terminal
+        #[structopt(long = "ask-password", group = "ssh_auth")]
+        ask_password: bool,
         /// Path to an identity file for SSH authentication
         #[structopt(short = "i", group = "ssh_auth")]
         identity_file: Option<PathBuf>,
@@ -216,8 +374,11 @@ pub enum Command {
     )]
     Remote {
         /// Query expression. Determines target hosts. Defaults to all hosts from current model
-        #[structopt(name = "OPATH", short = "h", long = "hosts", default_value = "$$hosts")]
-        expr: String,
+        #[structopt(name = "OPATH", short = "h", long = "hosts")]
+        expr: Option<String>,
+        /// Host group name defined in manifest `[groups]` section. Alternative to the query expression
+        #[structopt(short = "g", long = "group", conflicts_with = "OPATH")]
+        group: Option<String>,
         /// Command to execute on remote hosts
         #[structopt(name = "COMMAND", raw(true))]
         command: Vec<String>,
@@ -225,6 +386,22 @@ pub enum Command {
         #[structopt(short = "m", long = "model", default_value = "@")]
         model: RevPath,
     },
+    /// Check that ssh connection can be established to target hosts, without running any command
+    #[structopt(
+        name = "validate",
+        setting = AppSettings::ColoredHelp,
+    )]
+    Validate {
+        /// Host group name defined in manifest `[groups]` section. Defaults to all hosts
+        #[structopt(short = "g", long = "group")]
+        group: Option<String>,
+        /// Restrict check to hosts with hostname matching any of these glob patterns (comma-separated)
+        #[structopt(long = "limit", use_delimiter = true)]
+        limit: Vec<String>,
+        /// Model path, defaults to current model
+        #[structopt(name = "MODEL", default_value = "@")]
+        model: RevPath,
+    },
     /// Execute prepared work package
     #[structopt(
         name = "exec",
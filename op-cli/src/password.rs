@@ -0,0 +1,64 @@
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// Reads password from the first line of a file, without trailing newline.
+pub fn read_password_file(path: &Path) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content.lines().next().unwrap_or_default().to_string())
+}
+
+/// Returns `true` if standard input is attached to a terminal.
+pub fn stdin_is_tty() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+/// Prompts for a password on stderr and reads it from stdin with echo disabled.
+/// Fails if stdin is not a terminal.
+pub fn prompt_password(prompt: &str) -> std::io::Result<String> {
+    if !stdin_is_tty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "cannot prompt for password, standard input is not a terminal",
+        ));
+    }
+
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+
+    let _echo = EchoGuard::disable()?;
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    eprintln!();
+
+    Ok(line.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Disables terminal echo on stdin, restoring previous settings when dropped.
+struct EchoGuard {
+    termios: libc::termios,
+}
+
+impl EchoGuard {
+    fn disable() -> std::io::Result<EchoGuard> {
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut noecho = termios;
+            noecho.c_lflag &= !libc::ECHO;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &noecho) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(EchoGuard { termios })
+        }
+    }
+}
+
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.termios);
+        }
+    }
+}
@@ -6,12 +6,15 @@ use kg_tree::diff::NodeDiffOptions;
 use kg_tree::opath::{RootedResolveStrategy, TreeResolver};
 use kg_tree::serial::{from_tree, to_tree};
 use kg_tree::NodeRef;
+use op_engine::RuntimeConfig;
+use op_log::config::LogConfig;
+use op_rev::Author;
 use regex::{Captures, Regex};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use op_log::config::LogConfig;
 
 pub type ConfigResult<T> = Result<T, BasicDiag>;
 
@@ -32,6 +35,12 @@ pub enum ConfigErrorDetail {
 
     #[display(fmt = "cannot create config")]
     DeserializationErr,
+
+    #[display(fmt = "invalid config override '{value}', expected 'key.path=value'")]
+    InvalidOverride { value: String },
+
+    #[display(fmt = "unknown config key '{key}' in {source}")]
+    UnknownKey { key: String, source: String },
 }
 
 pub fn resolve_env_vars(input: &str) -> Cow<str> {
@@ -60,8 +69,215 @@ pub fn parse_path_list(path_list: &str) -> Vec<PathBuf> {
     paths
 }
 
+/// Prefix of environment variables overriding config values
+pub const ENV_PREFIX: &str = "OP_";
+
+/// Separator of nested keys in environment variable names, eg. `OP_MODEL__CACHE_LIMIT`
+pub const ENV_KEY_SEPARATOR: &str = "__";
+
+/// Parses override value according to the type of `default` value of the same key. Values of
+/// string keys are never coerced, so eg. `1` or `true` stay strings. Keys without a default
+/// (optional values) get booleans and numbers typed and everything else as a string.
+fn parse_value(default: Option<&serde_json::Value>, value: &str) -> serde_json::Value {
+    use serde_json::Value;
+
+    let parse_bool = || value.parse::<bool>().ok().map(Value::Bool);
+    let parse_number = || {
+        if let Ok(i) = value.parse::<i64>() {
+            Some(Value::from(i))
+        } else if let Ok(f) = value.parse::<f64>() {
+            Some(Value::from(f))
+        } else {
+            None
+        }
+    };
+
+    let typed = match default {
+        Some(Value::Bool(_)) => parse_bool(),
+        Some(Value::Number(_)) => parse_number(),
+        Some(Value::Null) | None => parse_bool().or_else(parse_number),
+        Some(_) => None,
+    };
+    typed.unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+/// Types raw string `overrides` against `defaults`, see [`parse_value`].
+fn typed_overrides(
+    defaults: &serde_json::Value,
+    overrides: Vec<(Vec<String>, String)>,
+) -> Vec<(Vec<String>, serde_json::Value)> {
+    overrides
+        .into_iter()
+        .map(|(path, value)| {
+            let default = path.iter().try_fold(defaults, |d, key| d.get(key));
+            let value = parse_value(default, &value);
+            (path, value)
+        })
+        .collect()
+}
+
+fn insert_path(
+    obj: &mut serde_json::Map<String, serde_json::Value>,
+    path: &[String],
+    value: serde_json::Value,
+) {
+    if path.len() == 1 {
+        obj.insert(path[0].clone(), value);
+    } else {
+        let child = obj
+            .entry(path[0].clone())
+            .or_insert_with(|| serde_json::Value::Object(serde_json::Map::new()));
+        if !child.is_object() {
+            *child = serde_json::Value::Object(serde_json::Map::new());
+        }
+        insert_path(child.as_object_mut().unwrap(), &path[1..], value);
+    }
+}
+
+/// Collects config overrides from `OP_`-prefixed environment variables. Variable name is
+/// lowercased and split on `__` into key path, eg. `OP_MODEL__CACHE_LIMIT=5` sets `model.cache_limit`.
+/// Values are returned as they are, they are typed against config keys when layers are merged.
+pub fn env_overrides<I, K, V>(vars: I) -> Vec<(Vec<String>, String)>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut overrides = Vec::new();
+    for (k, v) in vars {
+        if let Some(name) = k.as_ref().strip_prefix(ENV_PREFIX) {
+            let path: Vec<String> = name
+                .split(ENV_KEY_SEPARATOR)
+                .map(|s| s.to_lowercase())
+                .collect();
+            if path.iter().all(|s| !s.is_empty()) {
+                overrides.push((path, v.as_ref().to_string()));
+            }
+        }
+    }
+    // sort for deterministic result, environment iteration order is unspecified
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    overrides
+}
+
+/// Returns path of the first key from `value` not present in `defaults`. Empty objects
+/// (maps) and nulls (optional values) in `defaults` accept any keys.
+fn find_unknown_key(defaults: &serde_json::Value, value: &serde_json::Value) -> Option<String> {
+    match (defaults, value) {
+        (serde_json::Value::Object(d), serde_json::Value::Object(v)) if !d.is_empty() => {
+            for (k, val) in v {
+                match d.get(k) {
+                    Some(def) => {
+                        if let Some(key) = find_unknown_key(def, val) {
+                            return Some(format!("{}.{}", k, key));
+                        }
+                    }
+                    None => return Some(k.clone()),
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+/// Checks that all keys in `value` are known config keys.
+fn validate_keys(value: &serde_json::Value, source: &str) -> ConfigResult<()> {
+    let defaults =
+        serde_json::to_value(&Config::default()).expect("Config should always be serializable");
+    match find_unknown_key(&defaults, value) {
+        Some(key) => Err(ConfigErrorDetail::UnknownKey {
+            key,
+            source: source.to_string(),
+        }
+        .into()),
+        None => Ok(()),
+    }
+}
+
+fn overrides_to_json(overrides: &[(Vec<String>, serde_json::Value)]) -> serde_json::Value {
+    let mut obj = serde_json::Map::new();
+    for (path, value) in overrides {
+        insert_path(&mut obj, path, value.clone());
+    }
+    serde_json::Value::Object(obj)
+}
+
+/// Layer a config value comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    Env,
+    Override,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(ref path) => write!(f, "file '{}'", path.display()),
+            ConfigSource::Env => write!(f, "environment"),
+            ConfigSource::Override => write!(f, "override"),
+        }
+    }
+}
+
+/// Sources of config values set by non-default layers, keyed by dotted key path
+pub type ConfigSources = BTreeMap<String, ConfigSource>;
+
+/// Flattens `value` into dotted key paths of its leaf values.
+pub fn flatten_keys(value: &serde_json::Value) -> Vec<(String, serde_json::Value)> {
+    fn flatten(
+        value: &serde_json::Value,
+        prefix: &str,
+        out: &mut Vec<(String, serde_json::Value)>,
+    ) {
+        match value {
+            serde_json::Value::Object(obj) if !obj.is_empty() => {
+                for (k, v) in obj {
+                    let key = if prefix.is_empty() {
+                        k.clone()
+                    } else {
+                        format!("{}.{}", prefix, k)
+                    };
+                    flatten(v, &key, out);
+                }
+            }
+            _ => out.push((prefix.to_string(), value.clone())),
+        }
+    }
+
+    let mut out = Vec::new();
+    flatten(value, "", &mut out);
+    out
+}
+
+fn record_sources(sources: &mut ConfigSources, value: &serde_json::Value, source: ConfigSource) {
+    for (key, _) in flatten_keys(value) {
+        sources.insert(key, source.clone());
+    }
+}
+
+/// Parses explicit `key.path=value` override, eg. `model.cache_limit=5`. Value is returned
+/// as it is, it is typed against config keys when layers are merged.
+pub fn parse_override(value: &str) -> ConfigResult<(Vec<String>, String)> {
+    let mut parts = value.splitn(2, '=');
+    let key = parts.next().unwrap_or("").trim();
+    match parts.next() {
+        Some(v) if !key.is_empty() && key.split('.').all(|s| !s.is_empty()) => Ok((
+            key.split('.').map(|s| s.to_string()).collect(),
+            v.to_string(),
+        )),
+        _ => Err(ConfigErrorDetail::InvalidOverride {
+            value: value.to_string(),
+        }
+        .into()),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct DaemonConfig {
     socket_path: PathBuf,
     pid_file_path: PathBuf,
@@ -87,7 +303,7 @@ impl Default for DaemonConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct QueueConfig {
     persist_dir: PathBuf,
 }
@@ -107,11 +323,12 @@ impl Default for QueueConfig {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct ModelConfig {
     data_dir: PathBuf,
     cache_limit: usize,
     diff: NodeDiffOptions,
+    author: Option<Author>,
 }
 
 impl ModelConfig {
@@ -126,6 +343,11 @@ impl ModelConfig {
     pub fn diff(&self) -> &NodeDiffOptions {
         &self.diff
     }
+
+    /// Commit author, defaults to the first author from model manifest
+    pub fn author(&self) -> Option<&Author> {
+        self.author.as_ref()
+    }
 }
 
 impl Default for ModelConfig {
@@ -134,12 +356,13 @@ impl Default for ModelConfig {
             data_dir: PathBuf::from("/var/run/opereon/data"),
             cache_limit: 10,
             diff: NodeDiffOptions::new(true, Some(5), Some(0.1)),
+            author: None,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(default)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     run_dir: PathBuf,
     data_dir: PathBuf,
@@ -148,23 +371,92 @@ pub struct Config {
     queue: QueueConfig,
     model: ModelConfig,
     exec: ExecConfig,
+    runtime: RuntimeConfig,
+    #[serde(skip)]
+    sources: ConfigSources,
 }
 
 impl Config {
     fn read(path_list: &str) -> ConfigResult<Config> {
-        let d =
-            to_tree(&Config::default()).expect("Config should always be serializable to NodeRef");
+        let d = Config::default_tree();
+        let mut sources = ConfigSources::new();
+        Config::read_files(&d, path_list, &mut sources)?;
+        let mut conf = Config::resolve(d)?;
+        conf.sources = sources;
+        Ok(conf)
+    }
+
+    fn from_json(json: &str) -> ConfigResult<Config> {
+        let d = Config::default_tree();
+        let c: NodeRef =
+            NodeRef::from_json(&json).map_err_as_cause(|| ConfigErrorDetail::ParseConf)?;
+        if let Ok(value) = serde_json::from_str::<serde_json::Value>(json) {
+            validate_keys(&value, "json config")?;
+        }
+        d.extend(c, None).unwrap(); //FIXME (jc) handle errors
+        Config::resolve(d)
+    }
+
+    /// Merges config layers in order, later layers win per key:
+    /// built-in defaults, config files from `path_list`, `OP_`-prefixed environment variables
+    /// (see [`env_overrides`]) and explicit `key.path=value` overrides (see [`parse_override`]).
+    fn from_layers<I, K, V>(path_list: &str, env: I, overrides: &[String]) -> ConfigResult<Config>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let d = Config::default_tree();
+        let mut sources = ConfigSources::new();
+        Config::read_files(&d, path_list, &mut sources)?;
+
+        // variables not starting with a known top-level key are not meant for us, eg. `OP_SESSION`
+        let defaults =
+            serde_json::to_value(&Config::default()).expect("Config should always be serializable");
+        let env: Vec<_> = env_overrides(env)
+            .into_iter()
+            .filter(|(path, _)| defaults.get(&path[0]).is_some())
+            .collect();
+        let env = typed_overrides(&defaults, env);
+        let env_json = overrides_to_json(&env);
+        validate_keys(&env_json, "environment variables")?;
+        record_sources(&mut sources, &env_json, ConfigSource::Env);
+        Config::extend_with(&d, &env)?;
+
+        let mut flags = Vec::with_capacity(overrides.len());
+        for o in overrides {
+            flags.push(parse_override(o)?);
+        }
+        let flags = typed_overrides(&defaults, flags);
+        let flags_json = overrides_to_json(&flags);
+        validate_keys(&flags_json, "config overrides")?;
+        record_sources(&mut sources, &flags_json, ConfigSource::Override);
+        Config::extend_with(&d, &flags)?;
+
+        let mut conf = Config::resolve(d)?;
+        conf.sources = sources;
+        Ok(conf)
+    }
+
+    fn default_tree() -> NodeRef {
+        to_tree(&Config::default()).expect("Config should always be serializable to NodeRef")
+    }
+
+    fn read_files(d: &NodeRef, path_list: &str, sources: &mut ConfigSources) -> ConfigResult<()> {
         let paths = parse_path_list(path_list);
         let mut read_paths = 0;
         let mut content = String::new();
         for path in paths {
             match fs::read_to_string(&path, &mut content) {
                 Ok(_) => {
-                    let c: NodeRef = NodeRef::from_toml(&content).map_err_as_cause(|| {
-                        ConfigErrorDetail::ParseFile {
-                            file: path.to_string_lossy().to_string(),
-                        }
-                    })?;
+                    let file = path.to_string_lossy().to_string();
+                    let c: NodeRef = NodeRef::from_toml(&content)
+                        .map_err_as_cause(|| ConfigErrorDetail::ParseFile { file: file.clone() })?;
+                    if let Ok(value) = toml::from_str::<toml::Value>(&content) {
+                        let value = serde_json::to_value(value).unwrap();
+                        validate_keys(&value, &format!("file '{}'", file))?;
+                        record_sources(sources, &value, ConfigSource::File(path.clone()));
+                    }
                     d.extend(c, None).unwrap(); //FIXME (jc) handle errors
                     read_paths += 1;
                 }
@@ -182,23 +474,23 @@ impl Config {
             }
             .into());
         }
-
-        let mut r = TreeResolver::with_delims("${", "}");
-        r.resolve_custom(RootedResolveStrategy, &d)
-            .map_err_as_cause(|| ConfigErrorDetail::InterpolationErr)?;
-
-        let conf: Self = from_tree(&d)
-            .into_diag_res()
-            .map_err_as_cause(|| ConfigErrorDetail::DeserializationErr)?;
-        Ok(conf)
+        Ok(())
     }
 
-    fn from_json(json: &str) -> ConfigResult<Config> {
-        let d = to_tree(&Config::default()).unwrap();
-        let c: NodeRef =
-            NodeRef::from_json(&json).map_err_as_cause(|| ConfigErrorDetail::ParseConf)?;
+    fn extend_with(
+        d: &NodeRef,
+        overrides: &[(Vec<String>, serde_json::Value)],
+    ) -> ConfigResult<()> {
+        if overrides.is_empty() {
+            return Ok(());
+        }
+        let c: NodeRef = NodeRef::from_json(&overrides_to_json(overrides).to_string())
+            .map_err_as_cause(|| ConfigErrorDetail::ParseConf)?;
         d.extend(c, None).unwrap(); //FIXME (jc) handle errors
+        Ok(())
+    }
 
+    fn resolve(d: NodeRef) -> ConfigResult<Config> {
         let mut r = TreeResolver::with_delims("${", "}");
         r.resolve_custom(RootedResolveStrategy, &d)
             .map_err_as_cause(|| ConfigErrorDetail::InterpolationErr)?;
@@ -236,6 +528,18 @@ impl Config {
     pub fn log(&self) -> &LogConfig {
         &self.log
     }
+
+    pub fn runtime(&self) -> &RuntimeConfig {
+        &self.runtime
+    }
+
+    /// Returns layer the value at dotted `key` path comes from.
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
 }
 
 impl Default for Config {
@@ -248,6 +552,8 @@ impl Default for Config {
             queue: QueueConfig::default(),
             model: ModelConfig::default(),
             exec: ExecConfig::default(),
+            runtime: RuntimeConfig::default(),
+            sources: ConfigSources::new(),
         }
     }
 }
@@ -271,6 +577,22 @@ impl ConfigRef {
         let config = Config::from_json(json)?;
         Ok(ConfigRef(Arc::new(config)))
     }
+
+    /// Reads config merging layers: defaults < files from `path_list` < `env` vars < `overrides`.
+    /// Usually called with `std::env::vars()` as `env`.
+    pub fn from_layers<I, K, V>(
+        path_list: &str,
+        env: I,
+        overrides: &[String],
+    ) -> ConfigResult<ConfigRef>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let config = Config::from_layers(path_list, env, overrides)?;
+        Ok(ConfigRef(Arc::new(config)))
+    }
 }
 
 impl Default for ConfigRef {
@@ -300,4 +622,132 @@ mod tests {
         assert_eq!(paths.len(), 1);
         assert_eq!(paths[0], Path::new("var1_value/.opereon/config.toml"));
     }
+
+    fn write_config(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("op-config-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    const CONFIG_FILE: &str = "run_dir = \"/tmp/op-run\"\n\n[model]\ncache_limit = 20\n";
+
+    #[test]
+    fn layers_defaults() {
+        let path = write_config("defaults", "");
+        let env: Vec<(String, String)> = vec![];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).unwrap();
+
+        assert_eq!(Path::new("/var/run/opereon"), cfg.run_dir());
+        assert_eq!(10, cfg.model().cache_limit());
+    }
+
+    #[test]
+    fn layers_file_over_defaults() {
+        let path = write_config("file", CONFIG_FILE);
+        let env: Vec<(String, String)> = vec![];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).unwrap();
+
+        assert_eq!(Path::new("/tmp/op-run"), cfg.run_dir());
+        assert_eq!(20, cfg.model().cache_limit());
+        assert_eq!(Path::new("/var/lib/opereon"), cfg.data_dir());
+    }
+
+    #[test]
+    fn layers_env_over_file() {
+        let path = write_config("env", CONFIG_FILE);
+        let env = vec![
+            ("OP_MODEL__CACHE_LIMIT", "30"),
+            ("OP_DATA_DIR", "/tmp/op-data"),
+            ("HOME", "/root"),
+        ];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).unwrap();
+
+        assert_eq!(Path::new("/tmp/op-run"), cfg.run_dir());
+        assert_eq!(30, cfg.model().cache_limit());
+        assert_eq!(Path::new("/tmp/op-data"), cfg.data_dir());
+    }
+
+    #[test]
+    fn layers_overrides_over_env() {
+        let path = write_config("overrides", CONFIG_FILE);
+        let env = vec![("OP_MODEL__CACHE_LIMIT", "30")];
+        let overrides = vec!["model.cache_limit=40".to_string()];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &overrides).unwrap();
+
+        assert_eq!(40, cfg.model().cache_limit());
+    }
+
+    #[test]
+    fn layers_runtime_override() {
+        let path = write_config("runtime", "");
+        let env = vec![("OP_RUNTIME__THREAD_NAME", "op-worker")];
+        let overrides = vec!["runtime.worker_threads=2".to_string()];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &overrides).unwrap();
+
+        assert_eq!(Some(2), cfg.runtime().worker_threads());
+        assert_eq!("op-worker", cfg.runtime().thread_name());
+        assert_eq!(None, cfg.runtime().thread_stack_size());
+    }
+
+    #[test]
+    fn layers_string_key_not_coerced() {
+        let path = write_config("string-key", "");
+        let env = vec![("OP_EXEC__COMMAND__LOCAL__SHELL_CMD", "1")];
+        let overrides = vec!["exec.command.local.runas_cmd=true".to_string()];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &overrides).unwrap();
+
+        assert_eq!("1", cfg.exec().command().local().shell_cmd());
+        assert_eq!("true", cfg.exec().command().local().runas_cmd());
+    }
+
+    #[test]
+    fn layers_sources() {
+        let path = write_config("sources", CONFIG_FILE);
+        let env = vec![("OP_DATA_DIR", "/tmp/op-data")];
+        let overrides = vec!["model.cache_limit=40".to_string()];
+        let cfg = ConfigRef::from_layers(path.to_str().unwrap(), env, &overrides).unwrap();
+
+        assert_eq!(ConfigSource::File(path.clone()), cfg.source("run_dir"));
+        assert_eq!(ConfigSource::Env, cfg.source("data_dir"));
+        assert_eq!(ConfigSource::Override, cfg.source("model.cache_limit"));
+        assert_eq!(ConfigSource::Default, cfg.source("queue.persist_dir"));
+    }
+
+    #[test]
+    fn unknown_key_in_file() {
+        let path = write_config("unknown", "[exec.command.ssh]\nsocket_dr = \"/tmp\"\n");
+        let env: Vec<(String, String)> = vec![];
+        let err = ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).unwrap_err();
+
+        match err.detail().downcast_ref::<ConfigErrorDetail>() {
+            Some(ConfigErrorDetail::UnknownKey { key, .. }) => {
+                assert_eq!("exec.command.ssh.socket_dr", key)
+            }
+            _ => panic!("expected UnknownKey error, got: {}", err),
+        }
+    }
+
+    #[test]
+    fn unknown_key_in_env_and_overrides() {
+        let path = write_config("unknown-env", CONFIG_FILE);
+        let env = vec![("OP_MODEL__CACHE_LIMT", "5")];
+        assert!(ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).is_err());
+
+        // foreign variables are ignored
+        let env = vec![("OP_SESSION_ID", "abc")];
+        assert!(ConfigRef::from_layers(path.to_str().unwrap(), env, &[]).is_ok());
+
+        let env: Vec<(String, String)> = vec![];
+        let overrides = vec!["model.cache_limt=5".to_string()];
+        assert!(ConfigRef::from_layers(path.to_str().unwrap(), env, &overrides).is_err());
+    }
+
+    #[test]
+    fn parse_override_err() {
+        assert!(parse_override("model.cache_limit").is_err());
+        assert!(parse_override("=5").is_err());
+        assert!(parse_override("model..cache_limit=5").is_err());
+    }
 }
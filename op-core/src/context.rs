@@ -1,12 +1,15 @@
 use crate::ops::config::ConfigGetOperation;
+use crate::ops::hosts::HostsValidateOperation;
+pub use crate::ops::model::DiffMethod;
 use crate::ops::model::{
-    ModelCommitOperation, ModelDiffOperation, ModelInitOperation, ModelQueryOperation,
-    ModelTestOperation,
+    ModelCommitOperation, ModelDiffOperation, ModelFingerprintOperation, ModelInitOperation,
+    ModelLintOperation, ModelLogOperation, ModelQueryOperation, ModelTestOperation,
 };
 use crate::outcome::Outcome;
 use op_engine::operation::OperationImplExt;
 use op_engine::OperationRef;
 use op_exec::command::ssh::SshDest;
+use op_model::HostLimit;
 use op_rev::RevPath;
 use std::path::PathBuf;
 
@@ -14,11 +17,17 @@ use std::path::PathBuf;
 #[serde(rename_all = "kebab-case")]
 #[serde(tag = "type", content = "arg")]
 pub enum Context {
-    ConfigGet,
+    ConfigGet {
+        defaults: bool,
+    },
     ModelInit {
         path: PathBuf,
     },
-    ModelCommit(String),
+    ModelCommit {
+        message: String,
+        tag: Option<String>,
+        annotated: bool,
+    },
     ModelQuery {
         model: RevPath,
         expr: String,
@@ -26,9 +35,19 @@ pub enum Context {
     ModelTest {
         model: RevPath,
     },
+    ModelLint {
+        model: RevPath,
+    },
+    ModelFingerprint {
+        model: RevPath,
+    },
+    ModelLog {
+        limit: usize,
+    },
     ModelDiff {
         prev_model: RevPath,
         next_model: RevPath,
+        method: DiffMethod,
     },
     ModelUpdate {
         prev_model: RevPath,
@@ -38,10 +57,12 @@ pub enum Context {
     ModelCheck {
         model: RevPath,
         filter: Option<String>,
+        group: Option<String>,
         dry_run: bool,
     },
     ModelProbe {
-        ssh_dest: SshDest,
+        ssh_dest: Option<SshDest>,
+        group: Option<String>,
         model: RevPath,
         filter: Option<String>,
         args: Vec<(String, String)>,
@@ -68,9 +89,15 @@ pub enum Context {
     },
     RemoteExec {
         expr: String,
+        group: Option<String>,
         command: String,
         model_path: RevPath,
     },
+    HostsValidate {
+        model: RevPath,
+        group: Option<String>,
+        limit: HostLimit,
+    },
     // Sequence(Vec<OperationRef<Outcome>>),
     // Parallel(Vec<OperationRef<Outcome>>),
 }
@@ -78,11 +105,14 @@ pub enum Context {
 impl Context {
     pub fn label(&self) -> &str {
         match *self {
-            Context::ConfigGet => "config-get",
+            Context::ConfigGet { .. } => "config-get",
             Context::ModelInit { .. } => "model-init",
-            Context::ModelCommit(..) => "model-store",
+            Context::ModelCommit { .. } => "model-store",
             Context::ModelQuery { .. } => "model-query",
             Context::ModelTest { .. } => "model-test",
+            Context::ModelLint { .. } => "model-lint",
+            Context::ModelFingerprint { .. } => "model-fingerprint",
+            Context::ModelLog { .. } => "model-log",
             Context::ModelDiff { .. } => "model-diff",
             Context::ModelUpdate { .. } => "model-update",
             Context::ModelCheck { .. } => "model-check",
@@ -92,6 +122,7 @@ impl Context {
             Context::TaskExec { .. } => "task-exec",
             Context::FileCopyExec { .. } => "file-copy-exec",
             Context::RemoteExec { .. } => "remote-exec",
+            Context::HostsValidate { .. } => "hosts-validate",
             // Context::Sequence(..) => "sequence",
             // Context::Parallel(..) => "parallel",
         }
@@ -103,14 +134,22 @@ impl Into<OperationRef<Outcome>> for Context {
         let label = self.label().to_string();
         let op_impl = match self {
             Context::ModelInit { path } => ModelInitOperation::new(path).boxed(),
-            Context::ConfigGet => ConfigGetOperation::new().boxed(),
-            Context::ModelCommit(message) => ModelCommitOperation::new(message).boxed(),
+            Context::ConfigGet { defaults } => ConfigGetOperation::new(defaults).boxed(),
+            Context::ModelCommit {
+                message,
+                tag,
+                annotated,
+            } => ModelCommitOperation::new(message, tag, annotated).boxed(),
             Context::ModelQuery { model, expr } => ModelQueryOperation::new(model, expr).boxed(),
             Context::ModelTest { model } => ModelTestOperation::new(model).boxed(),
+            Context::ModelLint { model } => ModelLintOperation::new(model).boxed(),
+            Context::ModelFingerprint { model } => ModelFingerprintOperation::new(model).boxed(),
+            Context::ModelLog { limit } => ModelLogOperation::new(limit).boxed(),
             Context::ModelDiff {
                 prev_model,
                 next_model,
-            } => ModelDiffOperation::new(prev_model, next_model).boxed(),
+                method,
+            } => ModelDiffOperation::new(prev_model, next_model, method).boxed(),
             Context::ModelUpdate {
                 prev_model: _,
                 next_model: _,
@@ -119,10 +158,12 @@ impl Into<OperationRef<Outcome>> for Context {
             Context::ModelCheck {
                 model: _,
                 filter: _,
+                group: _,
                 dry_run: _,
             } => unimplemented!(),
             Context::ModelProbe {
                 ssh_dest: _,
+                group: _,
                 model: _,
                 filter: _,
                 args: _,
@@ -146,9 +187,15 @@ impl Into<OperationRef<Outcome>> for Context {
             } => unimplemented!(),
             Context::RemoteExec {
                 expr: _,
+                group: _,
                 command: _,
                 model_path: _,
             } => unimplemented!(),
+            Context::HostsValidate {
+                model,
+                group,
+                limit,
+            } => HostsValidateOperation::new(model, group, limit).boxed(),
         };
         OperationRef::new(label, op_impl)
     }
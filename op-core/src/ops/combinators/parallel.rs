@@ -2,6 +2,7 @@ use crate::ops::combinators::handle_cancel;
 use crate::outcome::Outcome;
 use async_trait::*;
 
+use kg_diag::Diag;
 use op_engine::operation::OperationResult;
 use op_engine::{EngineRef, OperationImpl, OperationRef, ProgressUpdate};
 use tokio::task::JoinHandle;
@@ -22,9 +23,27 @@ impl Default for ParallelPolicy {
     }
 }
 
+/// Determines how failure of a child operation affects its siblings (with `ParallelPolicy::All`).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FailurePolicy {
+    /// Cancel remaining operations on first error and return that error
+    FailFast,
+
+    /// Run all operations to completion, failed operations are reported as `Outcome::Failed`
+    CollectAll,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        FailurePolicy::CollectAll
+    }
+}
+
 pub struct ParallelOperation {
     ops: Vec<OperationRef<Outcome>>,
     policy: ParallelPolicy,
+    failure_policy: FailurePolicy,
+    max_parallel: Option<usize>,
     done_handle: Option<JoinHandle<OperationResult<Vec<Outcome>>>>,
 }
 
@@ -37,9 +56,66 @@ impl ParallelOperation {
         ParallelOperation {
             ops,
             policy,
+            failure_policy: FailurePolicy::default(),
+            max_parallel: None,
             done_handle: None,
         }
     }
+
+    pub fn with_failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Limits number of child operations running at once, `0` means no limit.
+    /// Only applies to `ParallelPolicy::All`.
+    pub fn with_max_parallel(mut self, max_parallel: usize) -> Self {
+        self.max_parallel = if max_parallel == 0 {
+            None
+        } else {
+            Some(max_parallel)
+        };
+        self
+    }
+}
+
+/// Runs `ops` with at most `limit` operations in flight, returning outcomes in order of `ops`,
+/// regardless of completion order.
+async fn run_all(
+    engine: EngineRef<Outcome>,
+    operation: OperationRef<Outcome>,
+    ops: Vec<OperationRef<Outcome>>,
+    limit: usize,
+    failure_policy: FailurePolicy,
+) -> OperationResult<Vec<Outcome>> {
+    use futures::StreamExt;
+
+    let total = ops.len();
+    let futs = ops.into_iter().enumerate().map(|(idx, op)| {
+        let engine = engine.clone();
+        async move {
+            let res = engine.enqueue_with_res(op).await;
+            (idx, res)
+        }
+    });
+    let mut results = futures::stream::iter(futs).buffer_unordered(limit.max(1));
+
+    let mut outcomes: Vec<Option<Outcome>> = vec![None; total];
+    while let Some((idx, res)) = results.next().await {
+        match res {
+            Ok(outcome) => outcomes[idx] = Some(outcome),
+            Err(err) => {
+                if failure_policy == FailurePolicy::FailFast {
+                    // cancelling this operation fans out to all child operations
+                    operation.cancel().await;
+                    return Err(err);
+                }
+                outcomes[idx] = Some(Outcome::Failed(err.detail().to_string()));
+            }
+        }
+    }
+
+    Ok(outcomes.into_iter().map(|o| o.unwrap()).collect())
 }
 
 #[async_trait]
@@ -51,22 +127,29 @@ impl OperationImpl<Outcome> for ParallelOperation {
     ) -> OperationResult<()> {
         handle_cancel(self.ops.clone(), operation);
 
-        let mut futs = vec![];
-        use futures::FutureExt;
-        for op in self.ops.iter() {
-            futs.push(engine.enqueue_with_res(op.clone()).boxed())
-        }
-
         let done_handle = match self.policy {
-            ParallelPolicy::All => tokio::spawn(async {
-                let results = futures::future::try_join_all(futs).await;
-                results
-            }),
-            ParallelPolicy::First => tokio::spawn(async {
-                let fut = futures::future::select_all(futs);
-                let (res, _idx, _rest) = fut.await;
-                res.map(|o| vec![o])
-            }),
+            ParallelPolicy::All => {
+                let limit = self.max_parallel.unwrap_or_else(|| self.ops.len());
+                tokio::spawn(run_all(
+                    engine.clone(),
+                    operation.clone(),
+                    self.ops.clone(),
+                    limit,
+                    self.failure_policy,
+                ))
+            }
+            ParallelPolicy::First => {
+                let mut futs = vec![];
+                use futures::FutureExt;
+                for op in self.ops.iter() {
+                    futs.push(engine.enqueue_with_res(op.clone()).boxed())
+                }
+                tokio::spawn(async {
+                    let fut = futures::future::select_all(futs);
+                    let (res, _idx, _rest) = fut.await;
+                    res.map(|o| vec![o])
+                })
+            }
         };
         self.done_handle = Some(done_handle);
         Ok(())
@@ -263,7 +346,7 @@ mod tests {
             TestOp::new_op_fail(1),
         ];
 
-        let op_impl = ParallelOperation::new(ops);
+        let op_impl = ParallelOperation::new(ops).with_failure_policy(FailurePolicy::FailFast);
         let op = OperationRef::new("parallel_operation", op_impl.boxed());
 
         rt.block_on(async move {
@@ -279,4 +362,94 @@ mod tests {
             println!("Engine stopped");
         })
     }
+
+    #[test]
+    fn parallel_operation_collect_all_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+        let mut rt = EngineRef::<()>::build_runtime();
+
+        let ops = vec![
+            TestOp::new_op(1),
+            TestOp::new_op_fail(1),
+            TestOp::new_op(2),
+            TestOp::new_op_fail(1),
+        ];
+
+        let op_impl = ParallelOperation::new(ops);
+        let op = OperationRef::new("parallel_operation", op_impl.boxed());
+
+        rt.block_on(async move {
+            let e = engine.clone();
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                let res = engine.enqueue_with_res(op).await.unwrap();
+                println!("operation completed with {:?}", res);
+                assert!(start.elapsed() >= Duration::from_secs(2));
+                let failed = Outcome::Failed("empty error message".into());
+                assert_eq!(
+                    res,
+                    Outcome::Many(vec![Outcome::Empty, failed.clone(), Outcome::Empty, failed])
+                );
+                engine.stop();
+            });
+
+            e.start().await;
+            println!("Engine stopped");
+        })
+    }
+
+    #[test]
+    fn parallel_operation_fail_fast_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+        let mut rt = EngineRef::<()>::build_runtime();
+
+        let ops = vec![TestOp::new_op(5), TestOp::new_op(5), TestOp::new_op_fail(1)];
+
+        let op_impl = ParallelOperation::new(ops).with_failure_policy(FailurePolicy::FailFast);
+        let op = OperationRef::new("parallel_operation", op_impl.boxed());
+
+        rt.block_on(async move {
+            let e = engine.clone();
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                let res = engine.enqueue_with_res(op).await.unwrap_err();
+                println!("operation completed with {}", res);
+                assert!(start.elapsed() < Duration::from_secs(5));
+                assert!(res.detail().downcast_ref::<TestErr>().is_some());
+                engine.stop();
+            });
+
+            e.start().await;
+            println!("Engine stopped");
+        })
+    }
+
+    #[test]
+    fn parallel_operation_max_parallel_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+        let mut rt = EngineRef::<()>::build_runtime();
+
+        let ops = vec![TestOp::new_op(1), TestOp::new_op(1), TestOp::new_op(1)];
+
+        let op_impl = ParallelOperation::new(ops).with_max_parallel(1);
+        let op = OperationRef::new("parallel_operation", op_impl.boxed());
+
+        rt.block_on(async move {
+            let e = engine.clone();
+            tokio::spawn(async move {
+                let start = std::time::Instant::now();
+                let res = engine.enqueue_with_res(op).await.unwrap();
+                assert!(start.elapsed() >= Duration::from_secs(3));
+                if let Outcome::Many(outs) = res {
+                    assert_eq!(outs.len(), 3);
+                    engine.stop();
+                } else {
+                    panic!();
+                }
+            });
+
+            e.start().await;
+            println!("Engine stopped");
+        })
+    }
 }
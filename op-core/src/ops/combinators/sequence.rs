@@ -1,12 +1,15 @@
 use crate::ops::combinators::handle_cancel;
 use crate::outcome::Outcome;
 use async_trait::*;
+use kg_diag::Diag;
 use op_engine::operation::OperationResult;
 use op_engine::progress::{Progress, Unit};
 use op_engine::{EngineRef, OperationImpl, OperationRef, ProgressUpdate};
 
 pub struct SequenceOperation {
     ops: Vec<OperationRef<Outcome>>,
+    ignore_errors: Vec<bool>,
+    on_failure: Option<OperationRef<Outcome>>,
     current_step: usize,
     outcomes: Vec<Outcome>,
 }
@@ -15,10 +18,35 @@ impl SequenceOperation {
     pub fn new(ops: Vec<OperationRef<Outcome>>) -> Self {
         SequenceOperation {
             outcomes: Vec::with_capacity(ops.len()),
+            ignore_errors: vec![false; ops.len()],
+            on_failure: None,
             ops,
             current_step: 0,
         }
     }
+
+    /// Marks step `step` as non-critical: its failure is recorded as `Outcome::Failed`
+    /// and the sequence continues with the next step.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is not an index of one of the sequence operations.
+    pub fn with_ignore_errors(mut self, step: usize) -> Self {
+        assert!(
+            step < self.ops.len(),
+            "cannot ignore errors of step {}, sequence has {} steps",
+            step,
+            self.ops.len()
+        );
+        self.ignore_errors[step] = true;
+        self
+    }
+
+    /// Sets operation executed when any critical step fails, before the error is returned.
+    pub fn with_on_failure(mut self, on_failure: OperationRef<Outcome>) -> Self {
+        self.on_failure = Some(on_failure);
+        self
+    }
 }
 
 #[async_trait]
@@ -44,7 +72,31 @@ impl OperationImpl<Outcome> for SequenceOperation {
         }
 
         let op = self.ops[self.current_step].clone();
-        let out = engine.enqueue_with_res(op).await?;
+        let out = match engine.enqueue_with_res(op).await {
+            Ok(out) => out,
+            Err(err) if self.ignore_errors[self.current_step] => {
+                warn!(
+                    verb = 0,
+                    "Step {} failed, continuing: {}",
+                    self.current_step,
+                    err.detail()
+                );
+                Outcome::Failed(err.detail().to_string())
+            }
+            Err(err) => {
+                if let Some(on_failure) = self.on_failure.take() {
+                    if let Err(rollback_err) = engine.enqueue_with_res(on_failure).await {
+                        warn!(
+                            verb = 0,
+                            "Failure handler of step {} failed: {}",
+                            self.current_step,
+                            rollback_err
+                        );
+                    }
+                }
+                return Err(err);
+            }
+        };
         self.outcomes.push(out);
         self.current_step += 1;
         let pu = ProgressUpdate::new(self.current_step as f64);
@@ -71,6 +123,8 @@ mod tests {
     use kg_diag::Severity;
     use op_engine::operation::{OperationImplExt, OperationResult};
     use op_engine::{EngineRef, OperationImpl, OperationRef};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
     use tokio::time::Duration;
 
     pub struct TestOp {
@@ -160,4 +214,88 @@ mod tests {
             println!("Engine stopped");
         })
     }
+
+    /// Operation setting a flag when executed
+    pub struct FlagOp(Arc<AtomicBool>);
+
+    #[async_trait]
+    impl OperationImpl<Outcome> for FlagOp {
+        async fn done(
+            &mut self,
+            _engine: &EngineRef<Outcome>,
+            _operation: &OperationRef<Outcome>,
+        ) -> OperationResult<Outcome> {
+            self.0.store(true, Ordering::SeqCst);
+            Ok(Outcome::Empty)
+        }
+    }
+
+    #[test]
+    fn sequence_operation_ignore_errors_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+        let mut rt = EngineRef::<()>::build_runtime();
+
+        let ops = vec![TestOp::new_op(0), TestOp::new_op_fail(0), TestOp::new_op(0)];
+
+        let op_impl = SequenceOperation::new(ops).with_ignore_errors(1);
+        let op = OperationRef::new("sequence_operation", op_impl.boxed());
+
+        rt.block_on(async move {
+            let e = engine.clone();
+            tokio::spawn(async move {
+                let res = engine.enqueue_with_res(op).await.unwrap();
+                if let Outcome::Many(outs) = res {
+                    assert_eq!(outs.len(), 3);
+                    assert_eq!(outs[0], Outcome::Empty);
+                    assert_eq!(outs[1], Outcome::Failed("empty error message".into()));
+                    assert_eq!(outs[2], Outcome::Empty);
+                    engine.stop();
+                } else {
+                    panic!();
+                }
+            });
+
+            e.start().await;
+            println!("Engine stopped");
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot ignore errors of step 3, sequence has 3 steps")]
+    fn sequence_operation_ignore_errors_out_of_range_test() {
+        let ops = vec![TestOp::new_op(0), TestOp::new_op(0), TestOp::new_op(0)];
+        let _ = SequenceOperation::new(ops).with_ignore_errors(3);
+    }
+
+    #[test]
+    fn sequence_operation_on_failure_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+        let mut rt = EngineRef::<()>::build_runtime();
+
+        let rolled_back = Arc::new(AtomicBool::new(false));
+        let executed = Arc::new(AtomicBool::new(false));
+        let ops = vec![
+            TestOp::new_op(0),
+            TestOp::new_op_fail(0),
+            OperationRef::new("flag_op", FlagOp(executed.clone()).boxed()),
+        ];
+        let on_failure = OperationRef::new("rollback", FlagOp(rolled_back.clone()).boxed());
+
+        let op_impl = SequenceOperation::new(ops).with_on_failure(on_failure);
+        let op = OperationRef::new("sequence_operation", op_impl.boxed());
+
+        rt.block_on(async move {
+            let e = engine.clone();
+            tokio::spawn(async move {
+                let res = engine.enqueue_with_res(op).await.unwrap_err();
+                assert!(res.detail().downcast_ref::<TestErr>().is_some());
+                assert!(rolled_back.load(Ordering::SeqCst));
+                assert!(!executed.load(Ordering::SeqCst));
+                engine.stop();
+            });
+
+            e.start().await;
+            println!("Engine stopped");
+        })
+    }
 }
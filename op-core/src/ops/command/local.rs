@@ -93,8 +93,9 @@ impl LocalScriptOperation {
 #[async_trait]
 impl SpawnableCommand for LocalScriptOperation {
     async fn spawn(&self) -> OperationResult<CommandHandle> {
+        let script = self.script.as_ref().fetch().await?;
         spawn_local_script(
-            self.script.as_ref(),
+            script.as_ref(),
             &self.args,
             self.env.as_ref(),
             self.cwd.as_deref(),
@@ -113,8 +114,7 @@ mod tests {
 
     #[test]
     fn local_command_operation_test() {
-        let engine: EngineRef<Outcome> = EngineRef::default();
-        let mut rt = EngineRef::<()>::build_runtime();
+        let engine: EngineRef<Outcome> = EngineRef::new_test(vec![]);
 
         let cfg = LocalConfig::default();
 
@@ -138,17 +138,9 @@ mod tests {
         );
         let op = OperationRef::new("local_command", op_impl.boxed());
 
-        rt.block_on(async move {
-            let e = engine.clone();
-
-            tokio::spawn(async move {
-                let res = engine.enqueue_with_res(op).await.unwrap();
-                println!("operation completed {:?}", res);
-                eprintln!("log = {}", log);
-                engine.stop();
-            });
-            e.start().await;
-        })
+        let res = engine.run_blocking(op).unwrap();
+        println!("operation completed {:?}", res);
+        eprintln!("log = {}", log);
     }
 
     #[test]
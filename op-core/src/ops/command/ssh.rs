@@ -90,11 +90,12 @@ impl SshScriptOperation {
 #[async_trait]
 impl SpawnableCommand for SshScriptOperation {
     async fn spawn(&self) -> OperationResult<CommandHandle> {
+        let script = self.script.as_ref().fetch().await?;
         let sess = self.cache.lock().await.get(&self.dest).await?;
 
         let mut s = sess.lock().await;
         s.spawn_script(
-            self.script.as_ref(),
+            script.as_ref(),
             &self.args,
             self.env.as_ref(),
             self.cwd.as_deref(),
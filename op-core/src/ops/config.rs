@@ -1,3 +1,4 @@
+use crate::config::flatten_keys;
 use crate::outcome::Outcome;
 use crate::state::CoreState;
 use async_trait::*;
@@ -6,14 +7,24 @@ use op_engine::operation::OperationResult;
 use op_engine::{EngineRef, OperationImpl, OperationRef};
 use std::ops::Deref;
 
-pub struct ConfigGetOperation {}
+/// Returns effective config, after all config layers are merged and interpolated.
+/// With `defaults` set, every value is annotated with the layer it comes from.
+pub struct ConfigGetOperation {
+    defaults: bool,
+}
 
 impl ConfigGetOperation {
-    pub fn new() -> Self {
-        ConfigGetOperation {}
+    pub fn new(defaults: bool) -> Self {
+        ConfigGetOperation { defaults }
     }
 }
 
+#[derive(Debug, Serialize)]
+struct AnnotatedValue {
+    value: serde_json::Value,
+    source: String,
+}
+
 #[async_trait]
 impl OperationImpl<Outcome> for ConfigGetOperation {
     async fn done(
@@ -22,7 +33,21 @@ impl OperationImpl<Outcome> for ConfigGetOperation {
         _operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         let state = engine.state::<CoreState>().unwrap();
-        let cfg = to_tree(state.config().deref())?;
-        Ok(Outcome::NodeSet(cfg.into()))
+        let config = state.config();
+        if self.defaults {
+            let value = serde_json::to_value(config.deref()).unwrap();
+            let annotated: std::collections::BTreeMap<String, AnnotatedValue> =
+                flatten_keys(&value)
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let source = config.source(&key).to_string();
+                        (key, AnnotatedValue { value, source })
+                    })
+                    .collect();
+            Ok(Outcome::NodeSet(to_tree(&annotated)?.into()))
+        } else {
+            let cfg = to_tree(config.deref())?;
+            Ok(Outcome::NodeSet(cfg.into()))
+        }
     }
 }
@@ -0,0 +1,107 @@
+use crate::outcome::{HostReachability, Outcome};
+use crate::proto::Host;
+use crate::services::model_manager::ModelManager;
+use async_trait::*;
+use futures::stream::{self, StreamExt};
+use op_engine::operation::OperationResult;
+use op_engine::{EngineRef, OperationImpl, OperationRef};
+use op_exec::command::ssh::{open_session, SshDest, SshSessionCache};
+use op_model::{HostDef, HostLimit};
+use op_rev::RevPath;
+use std::collections::BTreeMap;
+
+/// Maximum number of hosts checked at a time.
+const MAX_CONCURRENT_CHECKS: usize = 16;
+
+/// Checks that ssh master connection can be established to every target host,
+/// without executing any command.
+pub struct HostsValidateOperation {
+    model_path: RevPath,
+    group: Option<String>,
+    limit: HostLimit,
+}
+
+impl HostsValidateOperation {
+    pub fn new(model_path: RevPath, group: Option<String>, limit: HostLimit) -> Self {
+        HostsValidateOperation {
+            model_path,
+            group,
+            limit,
+        }
+    }
+
+    /// Ssh urls and destinations of target hosts
+    async fn resolve_hosts(
+        &self,
+        engine: &EngineRef<Outcome>,
+    ) -> OperationResult<Vec<(String, SshDest)>> {
+        let model = {
+            let mut manager = engine.service::<ModelManager>().await?;
+            manager.resolve(&self.model_path).await?
+        };
+
+        let m = model.lock();
+        let hosts: Vec<&HostDef> = match self.group {
+            Some(ref group) => m.hosts_in_group(group)?,
+            None => m.hosts().iter().collect(),
+        };
+        let mut dests = Vec::with_capacity(hosts.len());
+        for h in self.limit.apply(hosts)? {
+            let host = Host::from_def(&m, h)?;
+            dests.push((host.ssh_dest().to_url(), host.ssh_dest().clone()));
+        }
+        Ok(dests)
+    }
+}
+
+/// Opens connection to `dest`, reusing already cached one if its master connection is alive.
+async fn check_host(engine: &EngineRef<Outcome>, dest: &SshDest) -> OperationResult<()> {
+    let (cached, config) = {
+        let mut cache = engine.service::<SshSessionCache>().await?;
+        (cache.cached(dest), cache.config().clone())
+    };
+    if let Some(session) = cached {
+        if session.lock().await.check().await.unwrap_or(false) {
+            return Ok(());
+        }
+    }
+
+    // cache is not locked while connecting, so that hosts are checked concurrently
+    let session = open_session(dest, &config).await?;
+    engine
+        .service::<SshSessionCache>()
+        .await?
+        .insert(dest, session);
+    Ok(())
+}
+
+#[async_trait]
+impl OperationImpl<Outcome> for HostsValidateOperation {
+    async fn done(
+        &mut self,
+        engine: &EngineRef<Outcome>,
+        _operation: &OperationRef<Outcome>,
+    ) -> OperationResult<Outcome> {
+        info!(verb = 2, "Validating host connectivity");
+        let dests = self.resolve_hosts(engine).await?;
+
+        let checks = dests.into_iter().map(|(url, dest)| async move {
+            let res = check_host(engine, &dest).await;
+            (url, res)
+        });
+
+        let hosts: BTreeMap<String, HostReachability> = stream::iter(checks)
+            .buffer_unordered(MAX_CONCURRENT_CHECKS)
+            .map(|(url, res)| {
+                let status = match res {
+                    Ok(()) => HostReachability::Reachable,
+                    Err(err) => HostReachability::Unreachable(err.to_string()),
+                };
+                (url, status)
+            })
+            .collect()
+            .await;
+
+        Ok(Outcome::Reachability(hosts))
+    }
+}
@@ -38,5 +38,6 @@ mod combinators;
 mod command;
 pub mod config;
 pub mod exec;
+pub mod hosts;
 pub mod model;
 mod rsync;
@@ -7,10 +7,11 @@ use kg_diag::Severity;
 use kg_tree::diff::NodeDiff;
 use kg_tree::opath::Opath;
 use kg_tree::serial::to_tree;
+use kg_tree::NodeRef;
 use op_engine::operation::OperationResult;
 use op_engine::{EngineRef, OperationImpl, OperationRef};
-use op_model::{ModelDef, ScopedModelDef};
-use op_rev::RevPath;
+use op_model::{ModelChangeset, ModelDef, ScopedModelDef};
+use op_rev::{CancelToken, RevPath};
 use std::path::PathBuf;
 
 #[derive(Debug, Detail, Display)]
@@ -19,7 +20,37 @@ pub enum ModelOpErrorDetail {
     QueryOp,
 }
 
+/// Cancels repository operations of model manager when `operation` is cancelled. Every
+/// operation gets its own token, watched until the returned guard is dropped.
+fn handle_cancel(manager: &mut ModelManager, operation: &OperationRef<Outcome>) -> CancelGuard {
+    let token = CancelToken::new();
+    manager.set_cancel_token(token.clone());
+    let mut cancel_rx = operation.write().take_cancel_receiver().unwrap();
+    let t = token.clone();
+    let watcher = tokio::spawn(async move {
+        if cancel_rx.recv().await.is_some() {
+            t.cancel();
+        }
+    });
+    CancelGuard { token, watcher }
+}
+
+/// Stops watching for operation cancellation when dropped
+struct CancelGuard {
+    #[allow(dead_code)]
+    token: CancelToken,
+    watcher: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.watcher.abort();
+    }
+}
+
 #[derive(Debug)]
+/// Evaluates opath expression against a model. Result is always `Outcome::NodeSet`,
+/// which is empty when nothing matches.
 pub struct ModelQueryOperation {
     model_path: RevPath,
     expr: String,
@@ -35,7 +66,7 @@ impl ModelQueryOperation {
 impl OperationImpl<Outcome> for ModelQueryOperation {
     #[instrument(
     name = "ModelQueryOperation",
-    skip(self, engine, _operation),
+    skip(self, engine, operation),
     fields(
         model_path = % _self.model_path,
         expr = % _self.expr)
@@ -43,10 +74,11 @@ impl OperationImpl<Outcome> for ModelQueryOperation {
     async fn done(
         &mut self,
         engine: &EngineRef<Outcome>,
-        _operation: &OperationRef<Outcome>,
+        operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         info!(verb=2, "Querying model");
-        let mut manager = engine.service::<ModelManager>().await.unwrap();
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
         let model = manager.resolve(&self.model_path).await?;
         let expr = Opath::parse(&self.expr).map_err_as_cause(|| ModelOpErrorDetail::QueryOp)?;
 
@@ -63,11 +95,17 @@ impl OperationImpl<Outcome> for ModelQueryOperation {
 
 pub struct ModelCommitOperation {
     message: String,
+    tag: Option<String>,
+    annotated: bool,
 }
 
 impl ModelCommitOperation {
-    pub fn new(message: String) -> Self {
-        ModelCommitOperation { message }
+    pub fn new(message: String, tag: Option<String>, annotated: bool) -> Self {
+        ModelCommitOperation {
+            message,
+            tag,
+            annotated,
+        }
     }
 }
 
@@ -75,18 +113,28 @@ impl ModelCommitOperation {
 impl OperationImpl<Outcome> for ModelCommitOperation {
     #[instrument(
     name = "ModelCommitOperation",
-    skip(self, engine, _operation),
+    skip(self, engine, operation),
     fields(
     message = % _self.message)
     )]
     async fn done(
         &mut self,
         engine: &EngineRef<Outcome>,
-        _operation: &OperationRef<Outcome>,
+        operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         info!(verb=2, "Committing model");
-        let mut manager = engine.service::<ModelManager>().await.unwrap();
-        let _m = manager.commit(&self.message).await?;
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
+        let oid = manager.commit(&self.message).await?;
+        if let Some(ref tag) = self.tag {
+            info!(verb = 2, "Tagging model as '{}'", tag);
+            let message = if self.annotated {
+                Some(self.message.as_str())
+            } else {
+                None
+            };
+            manager.create_tag(tag, oid, message).await?;
+        }
         Ok(Outcome::Empty)
     }
 }
@@ -105,31 +153,175 @@ impl ModelTestOperation {
 impl OperationImpl<Outcome> for ModelTestOperation {
     #[instrument(
     name = "ModelTestOperation",
-    skip(self, engine, _operation),
+    skip(self, engine, operation),
     fields(
         model_path = % _self.model_path)
     )]
     async fn done(
         &mut self,
         engine: &EngineRef<Outcome>,
-        _operation: &OperationRef<Outcome>,
+        operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         info!(verb=2, "Testing model");
-        let mut manager = engine.service::<ModelManager>().await.unwrap();
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
         let model = manager.resolve(&self.model_path).await?;
         let res = to_tree(&*model.lock()).unwrap();
         Ok(Outcome::NodeSet(res.into()))
     }
 }
 
+pub struct ModelLintOperation {
+    model_path: RevPath,
+}
+
+impl ModelLintOperation {
+    pub fn new(model_path: RevPath) -> Self {
+        ModelLintOperation { model_path }
+    }
+}
+
+#[async_trait]
+impl OperationImpl<Outcome> for ModelLintOperation {
+    #[instrument(
+    name = "ModelLintOperation",
+    skip(self, engine, operation),
+    fields(
+        model_path = % _self.model_path)
+    )]
+    async fn done(
+        &mut self,
+        engine: &EngineRef<Outcome>,
+        operation: &OperationRef<Outcome>,
+    ) -> OperationResult<Outcome> {
+        info!(verb = 2, "Linting model");
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
+        let model = manager.resolve(&self.model_path).await?;
+        let findings = model.lock().lint();
+        Ok(Outcome::NodeSet(to_tree(&findings)?.into()))
+    }
+}
+
+/// Computes fingerprint of model, see `Model::fingerprint()`. External tooling can compare
+/// fingerprints to skip deployments of logically unchanged models.
+pub struct ModelFingerprintOperation {
+    model_path: RevPath,
+}
+
+impl ModelFingerprintOperation {
+    pub fn new(model_path: RevPath) -> Self {
+        ModelFingerprintOperation { model_path }
+    }
+}
+
+#[async_trait]
+impl OperationImpl<Outcome> for ModelFingerprintOperation {
+    #[instrument(
+    name = "ModelFingerprintOperation",
+    skip(self, engine, operation),
+    fields(
+        model_path = % _self.model_path)
+    )]
+    async fn done(
+        &mut self,
+        engine: &EngineRef<Outcome>,
+        operation: &OperationRef<Outcome>,
+    ) -> OperationResult<Outcome> {
+        info!(verb = 2, "Computing model fingerprint");
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
+        let model = manager.resolve(&self.model_path).await?;
+        let fingerprint = model.lock().fingerprint();
+        Ok(Outcome::NodeSet(NodeRef::string(fingerprint).into()))
+    }
+}
+
+pub struct ModelLogOperation {
+    limit: usize,
+}
+
+impl ModelLogOperation {
+    pub fn new(limit: usize) -> Self {
+        ModelLogOperation { limit }
+    }
+}
+
+#[async_trait]
+impl OperationImpl<Outcome> for ModelLogOperation {
+    #[instrument(
+    name = "ModelLogOperation",
+    skip(self, engine, operation),
+    fields(
+        limit = % _self.limit)
+    )]
+    async fn done(
+        &mut self,
+        engine: &EngineRef<Outcome>,
+        operation: &OperationRef<Outcome>,
+    ) -> OperationResult<Outcome> {
+        info!(verb = 2, "Listing model revisions");
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
+        let revs = manager.list_revisions(self.limit).await?;
+        Ok(Outcome::NodeSet(to_tree(&revs)?.into()))
+    }
+}
+
+/// Method used to compare two model versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiffMethod {
+    /// Semantic changeset of model trees, with old and new values of updated nodes
+    Model,
+    /// Raw structural diff of model trees, as produced by `kg_tree`
+    Tree,
+    /// List of files added, removed, modified or renamed between model revisions
+    Files,
+}
+
+impl Default for DiffMethod {
+    fn default() -> Self {
+        DiffMethod::Model
+    }
+}
+
+impl std::str::FromStr for DiffMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "model" => Ok(DiffMethod::Model),
+            "tree" => Ok(DiffMethod::Tree),
+            "files" => Ok(DiffMethod::Files),
+            _ => Err(format!("unknown diff method '{}'", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for DiffMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DiffMethod::Model => write!(f, "model"),
+            DiffMethod::Tree => write!(f, "tree"),
+            DiffMethod::Files => write!(f, "files"),
+        }
+    }
+}
+
 pub struct ModelDiffOperation {
     source: RevPath,
     target: RevPath,
+    method: DiffMethod,
 }
 
 impl ModelDiffOperation {
-    pub fn new(source: RevPath, target: RevPath) -> Self {
-        ModelDiffOperation { source, target }
+    pub fn new(source: RevPath, target: RevPath, method: DiffMethod) -> Self {
+        ModelDiffOperation {
+            source,
+            target,
+            method,
+        }
     }
 }
 
@@ -137,30 +329,37 @@ impl ModelDiffOperation {
 impl OperationImpl<Outcome> for ModelDiffOperation {
     #[instrument(
     name = "ModelDiffOperation",
-    skip(self, engine, _operation),
+    skip(self, engine, operation),
     fields(
         source = % _self.source,
-        target = % _self.target)
+        target = % _self.target,
+        method = % _self.method)
     )]
     async fn done(
         &mut self,
         engine: &EngineRef<Outcome>,
-        _operation: &OperationRef<Outcome>,
+        operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         info!(verb=2, "Getting diffs");
-        let mut manager = engine.service::<ModelManager>().await.unwrap();
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
+        if self.method == DiffMethod::Files {
+            let diff = manager.get_file_diff(&self.source, &self.target).await?;
+            return Ok(Outcome::NodeSet(to_tree(&diff)?.into()));
+        }
+
         let m1 = manager.resolve(&self.source).await?;
         let m2 = manager.resolve(&self.target).await?;
         let state = engine.state::<CoreState>().unwrap();
-        let diff = {
-            NodeDiff::diff(
-                m1.lock().root(),
-                m2.lock().root(),
-                state.config().model().diff(),
-            )
-        };
+        let opts = state.config().model().diff();
+        if self.method == DiffMethod::Tree {
+            let diff = NodeDiff::diff(m1.lock().root(), m2.lock().root(), opts);
+            return Ok(Outcome::Diff(diff));
+        }
 
-        Ok(Outcome::NodeSet(to_tree(&diff).unwrap().into()))
+        let changeset = { ModelChangeset::from_models(&m1.lock(), &m2.lock(), opts)? };
+
+        Ok(Outcome::NodeSet(to_tree(&changeset)?.into()))
     }
 }
 
@@ -178,18 +377,54 @@ impl ModelInitOperation {
 impl OperationImpl<Outcome> for ModelInitOperation {
     #[instrument(
     name = "ModelInitOperation",
-    skip(self, engine, _operation),
+    skip(self, engine, operation),
     fields(
         path = ? _self.path)
     )]
     async fn done(
         &mut self,
         engine: &EngineRef<Outcome>,
-        _operation: &OperationRef<Outcome>,
+        operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
         info!(verb=2, "Initializing model");
-        let mut manager = engine.service::<ModelManager>().await.unwrap();
+        let mut manager = engine.service::<ModelManager>().await?;
+        let _cancel = handle_cancel(&mut manager, operation);
         manager.create_model(self.path.clone()).await?;
         Ok(Outcome::Empty)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ModelConfig;
+    use op_engine::operation::OperationImplExt;
+
+    fn query_op() -> OperationRef<Outcome> {
+        let op = ModelQueryOperation::new(RevPath::Current, "$".into());
+        OperationRef::new("query", op.boxed())
+    }
+
+    #[test]
+    fn cancel_token_per_operation() {
+        let rt = EngineRef::<()>::build_runtime();
+        let mut manager = ModelManager::new(PathBuf::from("."), ModelConfig::default());
+
+        rt.block_on(async {
+            let (op1, op2) = (query_op(), query_op());
+            let guard1 = handle_cancel(&mut manager, &op1);
+            let guard2 = handle_cancel(&mut manager, &op2);
+
+            op1.cancel().await;
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            assert!(guard1.token.is_cancelled());
+            assert!(!guard2.token.is_cancelled());
+
+            let token2 = guard2.token.clone();
+            drop(guard2);
+            op2.cancel().await;
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            assert!(!token2.is_cancelled());
+        });
+    }
+}
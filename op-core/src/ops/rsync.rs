@@ -1,6 +1,6 @@
 use async_trait::async_trait;
 
-use crate::outcome::Outcome;
+use crate::outcome::{HostPlan, Outcome};
 
 use op_exec::rsync::compare::State;
 use op_exec::rsync::copy::ProgressInfo;
@@ -45,7 +45,7 @@ impl OperationImpl<Outcome> for FileCompareOperation {
         _engine: &EngineRef<Outcome>,
         operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
-        let cmp = RsyncCompare::spawn(&self.config, &self.params, self.checksum, &self.log)?;
+        let cmp = RsyncCompare::spawn(&self.config, &self.params, self.checksum, &self.log).await?;
 
         let cancel_rx = operation.write().take_cancel_receiver().unwrap();
         handle_cancel(cancel_rx, cmp.child().clone());
@@ -68,7 +68,9 @@ struct FileCopyOperation {
     config: RsyncConfig,
     params: RsyncParams,
     checksum: bool,
+    dry_run: bool,
     log: OutputLog,
+    diffs: Option<Vec<DiffInfo>>,
     progress_receiver: Option<mpsc::UnboundedReceiver<ProgressInfo>>,
     done_receiver: Option<oneshot::Receiver<RsyncResult<()>>>,
 }
@@ -84,11 +86,20 @@ impl FileCopyOperation {
             config: config.clone(),
             params: params.clone(),
             checksum,
+            dry_run: false,
             log: log.clone(),
+            diffs: None,
             progress_receiver: None,
             done_receiver: None,
         }
     }
+
+    /// In dry-run mode files are only compared, and the operation outcome
+    /// is `Outcome::Plan` for destination host, listing files that would be changed.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
 }
 
 fn build_progress(diffs: &[DiffInfo]) -> Progress {
@@ -125,6 +136,11 @@ impl OperationImpl<Outcome> for FileCopyOperation {
             unreachable!()
         };
 
+        if self.dry_run {
+            self.diffs = Some(diffs);
+            return Ok(());
+        }
+
         *operation.write().progress_mut() = build_progress(&diffs);
 
         let (progress_tx, progress_rx) = mpsc::unbounded_channel();
@@ -139,7 +155,7 @@ impl OperationImpl<Outcome> for FileCopyOperation {
         let cancel_rx = operation.write().take_cancel_receiver().unwrap();
 
         tokio::spawn(async move {
-            match RsyncCopy::spawn(&config, &params, progress_tx, &log) {
+            match RsyncCopy::spawn(&config, &params, progress_tx, &log).await {
                 Ok(copy) => {
                     handle_cancel(cancel_rx, copy.child().clone());
                     let res = copy.wait().await;
@@ -159,6 +175,9 @@ impl OperationImpl<Outcome> for FileCopyOperation {
         _engine: &EngineRef<Outcome>,
         _operation: &OperationRef<Outcome>,
     ) -> OperationResult<ProgressUpdate> {
+        if self.dry_run {
+            return Ok(ProgressUpdate::done());
+        }
         let res = self
             .progress_receiver
             .as_mut()
@@ -182,6 +201,11 @@ impl OperationImpl<Outcome> for FileCopyOperation {
         _engine: &EngineRef<Outcome>,
         _operation: &OperationRef<Outcome>,
     ) -> OperationResult<Outcome> {
+        if let Some(diffs) = self.diffs.take() {
+            let mut plan = HostPlan::new(self.params.dst_host().unwrap_or("localhost"));
+            plan.add_files(diffs);
+            return Ok(Outcome::Plan(vec![plan]));
+        }
         let rx = self.done_receiver.take().expect("done_receiver not set!");
         rx.await.expect("Sender dropped before completion")?;
         Ok(Outcome::Empty)
@@ -259,6 +283,41 @@ mod tests {
         })
     }
 
+    #[test]
+    fn dry_run_file_copy_operation_test() {
+        let engine: EngineRef<Outcome> = EngineRef::default();
+
+        let mut rt = EngineRef::<()>::build_runtime();
+        let cfg = RsyncConfig::default();
+        let params = RsyncParams::new("./", "./src", "./../target/debug3");
+        let log = OutputLog::new();
+
+        let op_impl = FileCopyOperation::new(&cfg, &params, false, &log).with_dry_run(true);
+        let op = OperationRef::new("copy_operation", op_impl.boxed());
+
+        let res = rt.block_on(async move {
+            let e = engine.clone();
+            let handle = tokio::spawn(async move {
+                let res = engine.enqueue_with_res(op).await;
+                engine.stop();
+                res
+            });
+
+            e.start().await;
+            handle.await.unwrap()
+        });
+
+        match res.unwrap() {
+            Outcome::Plan(plans) => {
+                assert_eq!(1, plans.len());
+                assert_eq!("localhost", plans[0].host());
+                assert!(!plans[0].files().is_empty());
+            }
+            res => panic!("unexpected outcome {:?}", res),
+        }
+        assert!(!std::path::Path::new("./../target/debug3").exists());
+    }
+
     #[test]
     fn compare_operation_test() {
         let engine: EngineRef<Outcome> = EngineRef::default();
@@ -1,10 +1,12 @@
 use kg_tree::diff::NodeDiff;
 use kg_tree::opath::NodeSet;
 use kg_tree::NodeRef;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use op_exec::command::CommandOutput;
+use op_exec::rsync::compare::State;
 use op_exec::rsync::DiffInfo;
 use serde::{de, ser};
 use std::ops::Deref;
@@ -16,10 +18,95 @@ pub enum Outcome {
     NodeSet(NodeSetRef),
     Diff(NodeDiff),
     FileDiff(Vec<DiffInfo>),
-    FileCopy { status: Option<i32> },
+    FileCopy {
+        status: Option<i32>,
+    },
     Command(CommandOutput),
     File(PathBuf),
     Many(Vec<Outcome>),
+    Plan(Vec<HostPlan>),
+    /// Failure of an operation whose errors are ignored, with error message
+    Failed(String),
+    /// Result of connecting to multiple hosts, keyed by ssh url, which is distinct
+    /// for hosts sharing a hostname with different username or port
+    Reachability(BTreeMap<String, HostReachability>),
+}
+
+/// Whether ssh connection to a host could be established
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "status", content = "error")]
+pub enum HostReachability {
+    Reachable,
+    Unreachable(String),
+}
+
+impl HostReachability {
+    pub fn is_reachable(&self) -> bool {
+        *self == HostReachability::Reachable
+    }
+}
+
+/// Changes planned for a single host in dry-run mode: files that would be modified
+/// and commands that would be executed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostPlan {
+    host: String,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    files: Vec<DiffInfo>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    commands: Vec<String>,
+}
+
+impl HostPlan {
+    pub fn new<S: Into<String>>(host: S) -> HostPlan {
+        HostPlan {
+            host: host.into(),
+            files: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Adds file comparison results, skipping files identical in source and destination.
+    pub fn add_files<I: IntoIterator<Item = DiffInfo>>(&mut self, diffs: I) {
+        self.files
+            .extend(diffs.into_iter().filter(|d| *d.state() != State::Identical));
+    }
+
+    pub fn add_command<S: Into<String>>(&mut self, command: S) {
+        self.commands.push(command.into());
+    }
+
+    /// Collects planned changes from a (possibly nested) outcome. Plans produced by
+    /// nested operations are merged into this plan.
+    pub fn add_outcome(&mut self, outcome: &Outcome) {
+        match *outcome {
+            Outcome::FileDiff(ref diffs) => self.add_files(diffs.iter().cloned()),
+            Outcome::Plan(ref plans) => {
+                for plan in plans.iter() {
+                    self.files.extend(plan.files.iter().cloned());
+                    self.commands.extend(plan.commands.iter().cloned());
+                }
+            }
+            Outcome::Many(ref outcomes) => outcomes.iter().for_each(|o| self.add_outcome(o)),
+            _ => {}
+        }
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn files(&self) -> &[DiffInfo] {
+        &self.files
+    }
+
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty() && self.commands.is_empty()
+    }
 }
 
 //FIXME (jc) implement
@@ -106,3 +193,70 @@ impl<'de> de::Deserialize<'de> for NodeSetRef {
 unsafe impl Send for NodeSetRef {}
 
 unsafe impl Sync for NodeSetRef {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn host_plan_skips_identical_files() {
+        let diffs = vec![
+            DiffInfo::parse(b".f         ", "same", 10).unwrap(),
+            DiffInfo::parse(b">f+++++++++", "new", 10).unwrap(),
+            DiffInfo::parse(b">f.st......", "changed", 10).unwrap(),
+        ];
+
+        let mut plan = HostPlan::new("host1");
+        assert!(plan.is_empty());
+
+        plan.add_outcome(&Outcome::Many(vec![
+            Outcome::FileDiff(diffs),
+            Outcome::Empty,
+        ]));
+        plan.add_command("systemctl restart sshd");
+
+        assert!(!plan.is_empty());
+        assert_eq!(2, plan.files().len());
+        assert_eq!(Path::new("new"), plan.files()[0].file_path());
+        assert_eq!(Path::new("changed"), plan.files()[1].file_path());
+        assert_eq!(&["systemctl restart sshd".to_string()], plan.commands());
+    }
+
+    #[test]
+    fn host_plan_merges_nested_plans() {
+        let mut copy_plan = HostPlan::new("host1");
+        copy_plan.add_files(vec![DiffInfo::parse(b">f+++++++++", "new", 10).unwrap()]);
+        let mut exec_plan = HostPlan::new("host1");
+        exec_plan.add_command("systemctl restart sshd");
+
+        let mut plan = HostPlan::new("host1");
+        plan.add_outcome(&Outcome::Many(vec![
+            Outcome::Plan(vec![copy_plan]),
+            Outcome::Plan(vec![exec_plan]),
+        ]));
+
+        assert_eq!(1, plan.files().len());
+        assert_eq!(Path::new("new"), plan.files()[0].file_path());
+        assert_eq!(&["systemctl restart sshd".to_string()], plan.commands());
+    }
+
+    #[test]
+    fn host_reachability_serialize() {
+        let mut hosts = BTreeMap::new();
+        hosts.insert("a".to_string(), HostReachability::Reachable);
+        hosts.insert(
+            "b".to_string(),
+            HostReachability::Unreachable("timeout".into()),
+        );
+
+        let json = serde_json::to_value(&hosts).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "a": {"status": "reachable"},
+                "b": {"status": "unreachable", "error": "timeout"},
+            }),
+            json
+        );
+    }
+}
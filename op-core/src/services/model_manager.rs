@@ -2,7 +2,7 @@ use crate::config::ModelConfig;
 use kg_diag::BasicDiag;
 use kg_utils::collections::LruCache;
 use op_model::{Model, ModelRef};
-use op_rev::{FileDiff, FileVersionManager, Oid, RevInfo, RevPath};
+use op_rev::{Author, CancelToken, FileDiff, FileVersionManager, Oid, RevInfo, RevPath};
 use std::ops::DerefMut;
 use std::path::PathBuf;
 
@@ -14,6 +14,7 @@ pub struct ModelManager {
     model_cache: LruCache<Oid, ModelRef>,
     repo_path: PathBuf,
     repo_manager: Option<Box<dyn FileVersionManager + Send>>,
+    cancel: CancelToken,
 }
 
 impl ModelManager {
@@ -25,16 +26,44 @@ impl ModelManager {
             model_cache,
             repo_path,
             repo_manager: None,
+            cancel: CancelToken::new(),
         }
     }
 
+    /// Sets token cancelling repository operations started from now on. Operations already
+    /// running keep the token they were started with.
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        if let Some(ref mut repo_manager) = self.repo_manager {
+            repo_manager.set_cancel_token(token.clone());
+        }
+        self.cancel = token;
+    }
+
     /// Commit current model
     pub async fn commit(&mut self, message: &str) -> ModelManagerResult<Oid> {
         self.init_model().await?;
         let oid = self.repo_manager_mut().commit(message).await?;
+        self.invalidate_working_tree();
         Ok(oid)
     }
 
+    /// List most recent model revisions
+    pub async fn list_revisions(&mut self, limit: usize) -> ModelManagerResult<Vec<RevInfo>> {
+        self.init_model().await?;
+        self.repo_manager_mut().list_revisions(limit).await
+    }
+
+    /// Tag model revision, annotated tag is created when `message` is provided
+    pub async fn create_tag(
+        &mut self,
+        name: &str,
+        id: Oid,
+        message: Option<&str>,
+    ) -> ModelManagerResult<()> {
+        self.init_model().await?;
+        self.repo_manager_mut().create_tag(name, id, message).await
+    }
+
     pub async fn get(&mut self, id: Oid) -> ModelManagerResult<ModelRef> {
         self.init_model().await?;
 
@@ -43,7 +72,7 @@ impl ModelManager {
         }
 
         let rev_info = self.repo_manager_mut().checkout(id).await?;
-        let model = ModelRef::read(rev_info)?;
+        let model = ModelRef::read_async(rev_info).await?;
         self.cache_model(model.clone());
         Ok(model)
     }
@@ -75,7 +104,9 @@ impl ModelManager {
 
     #[instrument(skip(self))]
     pub async fn create_model(&mut self, repo_path: PathBuf) -> ModelManagerResult<ModelRef> {
-        let repo_manager = op_rev::create_repository(&repo_path).await?;
+        let mut repo_manager = op_rev::create_repository(&repo_path).await?;
+        repo_manager.set_author(self.config.author().cloned());
+        repo_manager.set_cancel_token(self.cancel.clone());
 
         self.repo_path = repo_path;
         self.repo_manager = Some(repo_manager);
@@ -96,18 +127,80 @@ impl ModelManager {
         let repo_path = Model::resolve_manifest_dir(&self.repo_path)?;
 
         self.repo_path = repo_path;
-        self.repo_manager = Some(op_rev::open_repository(&self.repo_path).await?);
+        let mut repo_manager = op_rev::open_repository(&self.repo_path).await?;
+        repo_manager.set_author(self.resolve_author());
+        repo_manager.set_cancel_token(self.cancel.clone());
+        self.repo_manager = Some(repo_manager);
         info!(verb=2, repo_path=?self.repo_path, "Repository opened");
 
         Ok(())
     }
 
+    /// Author from config, or first parsable author from model manifest `[info].authors`
+    fn resolve_author(&self) -> Option<Author> {
+        if let Some(author) = self.config.author() {
+            return Some(author.clone());
+        }
+        let manifest = Model::load_manifest(&self.repo_path).ok()?;
+        manifest
+            .info()
+            .authors()
+            .iter()
+            .find_map(|a| a.parse().ok())
+    }
+
     fn cache_model(&mut self, m: ModelRef) {
         let id = m.lock().rev_info().id();
         self.model_cache.insert(id, m);
     }
 
+    /// Drops cached working tree model (cached under nil id), so that it is reloaded from
+    /// disk on next access. Committed revisions are immutable and stay cached.
+    fn invalidate_working_tree(&mut self) {
+        self.model_cache.remove(&Oid::nil());
+    }
+
     fn repo_manager_mut(&mut self) -> &mut dyn FileVersionManager {
         self.repo_manager.as_mut().unwrap().deref_mut()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use op_engine::EngineRef;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("op-manager-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn commit_invalidates_working_tree() {
+        let dir = test_dir("commit");
+        let config: ModelConfig = serde_json::from_value(serde_json::json!({
+            "author": {"name": "test", "email": "test@example.org"},
+        }))
+        .unwrap();
+        let mut manager = ModelManager::new(dir.clone(), config);
+        let rt = EngineRef::<()>::build_runtime();
+
+        rt.block_on(async {
+            let created = manager.create_model(dir.clone()).await.unwrap();
+            let current = manager.current().await.unwrap();
+            assert!(created == current);
+            assert!(manager.model_cache.get_mut(&Oid::nil()).is_some());
+
+            manager.commit("initial").await.unwrap();
+            assert!(manager.model_cache.get_mut(&Oid::nil()).is_none());
+
+            let reloaded = manager.current().await.unwrap();
+            assert!(reloaded != current);
+            assert!(manager.current().await.unwrap() == reloaded);
+        });
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
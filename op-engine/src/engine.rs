@@ -3,9 +3,9 @@ use kg_utils::collections::LinkedHashMap;
 use kg_utils::sync::{SyncRef, SyncRefMapReadGuard, SyncRefReadGuard};
 use std::collections::{HashMap, VecDeque};
 
-use crate::operation::OperationResult;
-use futures::lock::{Mutex, MutexGuard};
+use crate::operation::{OperationErrorDetail, OperationResult};
 use kg_diag::Detail;
+use tokio::sync::{Mutex, OwnedMutexGuard};
 //use serde::export::{PhantomData, Formatter};
 use std::any::{Any, TypeId};
 use std::future::Future;
@@ -82,6 +82,56 @@ impl<T: Clone + 'static> Core<T> {
     }
 }
 
+/// Settings of tokio runtime built with [`EngineRef::build_runtime_with`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuntimeConfig {
+    worker_threads: Option<usize>,
+    thread_name: String,
+    thread_stack_size: Option<usize>,
+}
+
+impl RuntimeConfig {
+    /// Number of worker threads, defaults to the number of cores. `0` is treated as default.
+    pub fn worker_threads(&self) -> Option<usize> {
+        self.worker_threads.filter(|&n| n > 0)
+    }
+
+    pub fn thread_name(&self) -> &str {
+        &self.thread_name
+    }
+
+    /// Stack size of worker threads in bytes, defaults to tokio default.
+    pub fn thread_stack_size(&self) -> Option<usize> {
+        self.thread_stack_size
+    }
+
+    pub fn with_worker_threads(mut self, worker_threads: usize) -> RuntimeConfig {
+        self.worker_threads = Some(worker_threads);
+        self
+    }
+
+    pub fn with_thread_name<S: Into<String>>(mut self, thread_name: S) -> RuntimeConfig {
+        self.thread_name = thread_name.into();
+        self
+    }
+
+    pub fn with_thread_stack_size(mut self, thread_stack_size: usize) -> RuntimeConfig {
+        self.thread_stack_size = Some(thread_stack_size);
+        self
+    }
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            worker_threads: None,
+            thread_name: "engine".into(),
+            thread_stack_size: None,
+        }
+    }
+}
+
 pub type Service = Box<dyn Any + Send + 'static>;
 pub type State = Box<dyn Any + Send + Sync + 'static>;
 
@@ -89,7 +139,7 @@ pub type State = Box<dyn Any + Send + Sync + 'static>;
 pub struct EngineRef<T: Clone + 'static> {
     operations: SyncRef<Operations<T>>,
     core: SyncRef<Core<T>>,
-    services: Arc<HashMap<TypeId, Arc<Mutex<Service>>>>,
+    services: SyncRef<HashMap<TypeId, Arc<Mutex<Service>>>>,
     state: Arc<State>,
 }
 
@@ -114,18 +164,43 @@ impl<T: Clone + 'static> EngineRef<T> {
         EngineRef {
             operations: SyncRef::new(Operations::new()),
             core: SyncRef::new(Core::new()),
-            services: Arc::new(services),
+            services: SyncRef::new(services),
             state: Arc::new(Box::new(state)),
         }
     }
 
-    pub fn build_runtime() -> Runtime {
-        let runtime = tokio::runtime::Builder::new_multi_thread()
+    /// Creates engine for testing operations in isolation, with given stub `services`
+    /// and no state. More services can be added later with `register_service`.
+    pub fn new_test(services: Vec<Service>) -> EngineRef<T> {
+        EngineRef::new(services, ())
+    }
+
+    /// Runs `operation` to completion on a current-thread runtime, blocking current thread,
+    /// and returns its outcome. Operation is driven directly, without starting the engine loop,
+    /// so operations enqueueing child operations (eg. combinators) must be run with `start`.
+    pub fn run_blocking(&self, mut operation: OperationRef<T>) -> OperationResult<T> {
+        let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
-            .thread_name("engine")
             .build()
             .unwrap();
-        runtime
+        let op_impl = operation.take_op_impl().unwrap();
+        rt.block_on(run_operation(self, &operation, op_impl))
+    }
+
+    pub fn build_runtime() -> Runtime {
+        Self::build_runtime_with(&RuntimeConfig::default())
+    }
+
+    pub fn build_runtime_with(config: &RuntimeConfig) -> Runtime {
+        let mut builder = tokio::runtime::Builder::new_multi_thread();
+        builder.enable_all().thread_name(config.thread_name());
+        if let Some(n) = config.worker_threads() {
+            builder.worker_threads(n);
+        }
+        if let Some(size) = config.thread_stack_size() {
+            builder.thread_stack_size(size);
+        }
+        builder.build().unwrap()
     }
 
     pub async fn start(&self) -> EngineResult {
@@ -146,6 +221,12 @@ impl<T: Clone + 'static> EngineRef<T> {
         SyncRefReadGuard::map(ops, |o| &o.operations)
     }
 
+    /// Send cancel signal to all running operations
+    pub async fn cancel_all(&self) {
+        let ops: Vec<OperationRef<T>> = self.operations().values().cloned().collect();
+        futures::future::join_all(ops.iter().map(|op| op.cancel())).await;
+    }
+
     fn main_task(&self) -> EngineMainTask<T> {
         EngineMainTask {
             engine: self.clone(),
@@ -219,44 +300,78 @@ impl<T: Clone + 'static> EngineRef<T> {
         }
     }
 
-    pub async fn service<S: 'static>(&self) -> Option<EngineServiceGuard<'_, S>> {
-        let s = self.services.get(&TypeId::of::<S>());
+    /// Locks and returns service of type `S`, fails with `OperationErrorDetail::ServiceMissing`
+    /// if no such service is registered.
+    pub async fn service<S: 'static>(&self) -> OperationResult<EngineServiceGuard<S>> {
+        // service map lock must not be held while waiting for the service itself
+        let s = self.services.read().get(&TypeId::of::<S>()).cloned();
         if let Some(service) = s {
-            let guard = service.lock().await;
-            Some(EngineServiceGuard {
+            let guard = service.lock_owned().await;
+            Ok(EngineServiceGuard {
                 phantom: PhantomData::<S>,
                 guard,
             })
         } else {
-            None
+            Err(OperationErrorDetail::ServiceMissing {
+                type_name: std::any::type_name::<S>().to_string(),
+            }
+            .into())
         }
     }
 
+    /// Registers `service`, replacing previously registered service of the same type.
+    /// Operations already holding the replaced service keep it until the guard is dropped.
+    /// Returns `true` if a service was replaced.
+    pub fn register_service<S: Any + Send + 'static>(&self, service: S) -> bool {
+        let service: Service = Box::new(service);
+        self.services
+            .write()
+            .insert(TypeId::of::<S>(), Arc::new(Mutex::new(service)))
+            .is_some()
+    }
+
+    /// Removes service of type `S`. Operations already holding the service keep it
+    /// until the guard is dropped. Returns `true` if a service was removed.
+    pub fn remove_service<S: 'static>(&self) -> bool {
+        self.services.write().remove(&TypeId::of::<S>()).is_some()
+    }
+
     pub fn state<S: 'static>(&self) -> Option<&S> {
         self.state.downcast_ref::<S>()
     }
 }
 
-pub struct EngineServiceGuard<'a, S> {
+pub struct EngineServiceGuard<S> {
     phantom: PhantomData<S>,
-    guard: MutexGuard<'a, Box<dyn Any + Send + 'static>>,
+    guard: OwnedMutexGuard<Box<dyn Any + Send + 'static>>,
 }
 
-impl<S: 'static> Deref for EngineServiceGuard<'_, S> {
+impl<S: 'static> Deref for EngineServiceGuard<S> {
     type Target = S;
 
     fn deref(&self) -> &S {
         // this is safe since only way to create this guard is through engine.service method.
-        self.guard.downcast_ref().expect("Unexpected service type")
+        self.guard
+            .downcast_ref()
+            .unwrap_or_else(|| unexpected_service_type::<S>())
     }
 }
 
-impl<S: 'static> DerefMut for EngineServiceGuard<'_, S> {
+impl<S: 'static> DerefMut for EngineServiceGuard<S> {
     fn deref_mut(&mut self) -> &mut S {
-        self.guard.downcast_mut().expect("Unexpected service type")
+        self.guard
+            .downcast_mut()
+            .unwrap_or_else(|| unexpected_service_type::<S>())
     }
 }
 
+fn unexpected_service_type<S>() -> ! {
+    panic!(
+        "unexpected type of service, expected '{}'",
+        std::any::type_name::<S>()
+    )
+}
+
 pub struct EngineResult {
     code: i32,
 }
@@ -292,23 +407,25 @@ impl<T: Clone + 'static> Future for EngineMainTask<T> {
 async fn get_operation_fut<T: Clone + 'static>(
     engine: EngineRef<T>,
     operation: OperationRef<T>,
-    mut op_impl: Box<dyn OperationImpl<T>>,
+    op_impl: Box<dyn OperationImpl<T>>,
 ) {
-    let o = operation.clone();
-    let e = engine.clone();
-    let inner = async move || {
-        op_impl.init(&engine, &operation).await?;
-
-        while !operation.write().progress().is_done() {
-            let u = op_impl.next_progress(&engine, &operation).await?;
-            operation.write().progress_mut().update(u);
-            engine.notify_progress(&operation);
-        }
-        op_impl.done(&engine, &operation).await
-    };
+    let out = run_operation(&engine, &operation, op_impl).await;
+    engine.finish_operation(&operation, out);
+}
 
-    let out = inner().await;
-    e.finish_operation(&o, out);
+async fn run_operation<T: Clone + 'static>(
+    engine: &EngineRef<T>,
+    operation: &OperationRef<T>,
+    mut op_impl: Box<dyn OperationImpl<T>>,
+) -> OperationResult<T> {
+    op_impl.init(engine, operation).await?;
+
+    while !operation.write().progress().is_done() {
+        let u = op_impl.next_progress(engine, operation).await?;
+        operation.write().progress_mut().update(u);
+        engine.notify_progress(operation);
+    }
+    op_impl.done(engine, operation).await
 }
 
 impl<T: Debug + Clone + 'static> Debug for EngineRef<T> {
@@ -3,6 +3,8 @@
 #[macro_use]
 extern crate serde_derive;
 
+#[macro_use]
+extern crate kg_diag_derive;
 #[macro_use]
 extern crate kg_display_derive;
 
@@ -10,7 +12,7 @@ pub mod engine;
 pub mod operation;
 pub mod progress;
 
-pub use engine::{EngineRef, EngineResult};
+pub use engine::{EngineRef, EngineResult, RuntimeConfig};
 pub use operation::{OperationError, OperationErrorDetail, OperationImpl, OperationRef};
 pub use progress::ProgressUpdate;
 
@@ -97,6 +99,21 @@ mod tests {
         }
     }
 
+    /// Returns current counter of `TestService`.
+    struct CounterOp;
+
+    #[async_trait]
+    impl OperationImpl<OutputType> for CounterOp {
+        async fn done(
+            &mut self,
+            engine: &EngineRef<OutputType>,
+            _operation: &OperationRef<OutputType>,
+        ) -> OperationResult<OutputType> {
+            let service = engine.service::<TestService>().await?;
+            Ok(service.counter().to_string())
+        }
+    }
+
     fn print_progress<T: Clone + 'static>(e: &EngineRef<T>, first: bool) {
         use std::fmt::Write;
 
@@ -147,4 +164,83 @@ mod tests {
             e.start().await;
         });
     }
+
+    #[test]
+    fn service_missing() {
+        use kg_diag::Diag;
+
+        let engine: EngineRef<String> = EngineRef::default();
+        let rt = EngineRef::<()>::build_runtime();
+
+        let err = match rt.block_on(engine.service::<TestService>()) {
+            Ok(_) => panic!("service should be missing"),
+            Err(err) => err,
+        };
+        match err.detail().downcast_ref::<OperationErrorDetail>() {
+            Some(OperationErrorDetail::ServiceMissing { type_name }) => {
+                assert!(type_name.ends_with("TestService"))
+            }
+            _ => panic!("ServiceMissing expected, got {}", err),
+        }
+    }
+
+    #[test]
+    fn register_and_remove_service() {
+        let engine: EngineRef<String> = EngineRef::default();
+        let rt = EngineRef::<()>::build_runtime();
+
+        assert!(!engine.register_service(TestService::new()));
+        rt.block_on(async {
+            let mut service = engine.service::<TestService>().await.unwrap();
+            service.set_counter(5);
+        });
+
+        let mut replacement = TestService::new();
+        replacement.set_counter(10);
+        assert!(engine.register_service(replacement));
+        rt.block_on(async {
+            let service = engine.service::<TestService>().await.unwrap();
+            assert_eq!(10, service.counter());
+        });
+
+        assert!(engine.remove_service::<TestService>());
+        assert!(!engine.remove_service::<TestService>());
+        assert!(rt.block_on(engine.service::<TestService>()).is_err());
+    }
+
+    #[test]
+    fn build_runtime_with_config() {
+        let config = RuntimeConfig::default()
+            .with_worker_threads(2)
+            .with_thread_name("test-engine")
+            .with_thread_stack_size(4 * 1024 * 1024);
+        let rt = EngineRef::<()>::build_runtime_with(&config);
+
+        let name = rt.block_on(async {
+            tokio::spawn(async { std::thread::current().name().map(String::from) })
+                .await
+                .unwrap()
+        });
+        assert_eq!(Some("test-engine"), name.as_deref());
+        assert_eq!(
+            None,
+            RuntimeConfig::default()
+                .with_worker_threads(0)
+                .worker_threads()
+        );
+    }
+
+    #[test]
+    fn run_blocking_with_stub_service() {
+        let mut service = TestService::new();
+        service.set_counter(7);
+        let engine: EngineRef<String> = EngineRef::new_test(vec![Box::new(service)]);
+
+        let res = engine.run_blocking(OperationRef::new("counter", CounterOp.boxed()));
+        assert_eq!("7", res.unwrap());
+
+        let engine: EngineRef<String> = EngineRef::new_test(vec![]);
+        let res = engine.run_blocking(OperationRef::new("counter", CounterOp.boxed()));
+        assert!(res.is_err());
+    }
 }
@@ -15,10 +15,13 @@ use std::fmt::{Debug, Formatter};
 pub type OperationError = BasicDiag;
 pub type OperationResult<T> = Result<T, OperationError>;
 
-#[derive(Debug, Display)]
+#[derive(Debug, Display, Detail)]
 pub enum OperationErrorDetail {
     #[display(fmt = "operation cancelled by user")]
     Cancelled,
+
+    #[display(fmt = "service '{type_name}' is not registered in engine")]
+    ServiceMissing { type_name: String },
 }
 
 #[async_trait]
@@ -9,6 +9,10 @@ use shared_child::SharedChild;
 use std::io::Write;
 use std::sync::Arc;
 
+/// Spawns `cmd` as a local process with `args` passed as argv, without any shell interpretation.
+/// Arguments are never quoted or expanded, so they reach the program exactly as given.
+/// When `run_as` is set, the command is executed through `runas_cmd`, which also
+/// does not involve a shell.
 pub fn spawn_local_command(
     cmd: &str,
     args: &[String],
@@ -18,21 +22,16 @@ pub fn spawn_local_command(
     config: &LocalConfig,
     log: &OutputLog,
 ) -> CommandResult<CommandHandle> {
-    let mut builder = prepare_builder(cmd, env, run_as, config);
+    let mut builder = prepare_builder(cmd, env, run_as, config)?;
 
     builder.args(args.iter().map(String::as_str));
 
-    if let Some(envs) = env {
-        for (k, v) in envs {
-            builder.env(k, v);
-        }
+    if let Some(cwd) = cwd {
+        builder.cwd(cwd);
     }
 
     let mut command = builder.build();
 
-    if let Some(cwd) = cwd {
-        command.current_dir(cwd);
-    }
     let (out_reader, out_writer) = pipe().unwrap();
     let (err_reader, err_writer) = pipe().unwrap();
     command
@@ -59,6 +58,8 @@ pub fn spawn_local_command(
     })
 }
 
+/// Spawns `script` interpreted by configured `shell_cmd`, with `args` available as positional
+/// parameters. Use [`spawn_local_command`] to execute a program without shell interpretation.
 pub fn spawn_local_script(
     script: SourceRef<'_>,
     args: &[String],
@@ -68,36 +69,34 @@ pub fn spawn_local_script(
     config: &LocalConfig,
     log: &OutputLog,
 ) -> CommandResult<CommandHandle> {
-    let mut builder = prepare_builder(config.shell_cmd(), env, run_as, config);
+    let mut builder = prepare_builder(config.shell_cmd(), env, run_as, config)?;
 
-    match script {
+    // scripts other than files are passed through stdin
+    let src = match script {
         SourceRef::Path(path) => {
             builder.arg(path.to_string_lossy());
+            None
         }
-        SourceRef::Source(_) => {
+        _ => {
             builder.arg("/dev/stdin");
+            Some(script.read()?)
         }
-    }
+    };
 
     builder.args(args.iter().map(String::as_str));
 
-    if let Some(envs) = env {
-        for (k, v) in envs {
-            builder.env(k, v);
-        }
+    if let Some(cwd) = cwd {
+        builder.cwd(cwd);
     }
 
     let mut command = builder.build();
 
-    if let Some(cwd) = cwd {
-        command.current_dir(cwd);
-    }
     let (out_reader, out_writer) = pipe().unwrap();
     let (err_reader, err_writer) = pipe().unwrap();
     command.stdout(out_writer).stderr(err_writer);
 
     log.log_in(format!("{:?}", command).as_bytes())?;
-    if let SourceRef::Source(src) = script {
+    if let Some(src) = src {
         let (r_in, mut w_in) = pipe().unwrap();
         command.stdin(Stdio::from(r_in));
         log.log_in(src.as_bytes())?;
@@ -124,29 +123,46 @@ pub fn spawn_local_script(
     })
 }
 
+/// Prepares command builder, wrapped in `runas_cmd` if `run_as` is set.
+///
+/// When running as another user, env vars are set on `runas_cmd` process and passed through
+/// with `--preserve-env=VAR,...`, since `runas_cmd` (eg. `sudo`) resets the environment.
+/// Values are never put in the command line, where they would be visible to other users.
 fn prepare_builder(
     cmd: &str,
-    env: Option<&LinkedHashMap<String, String>>,
+    env: Option<&EnvVars>,
     run_as: Option<&str>,
     config: &LocalConfig,
-) -> CommandBuilder {
-    let builder = if let Some(user) = run_as {
-        // TODO ws is this implementation ok? Will work only for `runas_cmd = sudo`
+) -> CommandResult<CommandBuilder> {
+    if let Some(user) = run_as {
         let mut builder = CommandBuilder::new(config.runas_cmd());
-        builder.arg("-u").arg(user);
 
         if let Some(env) = env {
-            let envs = env.keys().map(|s| &**s).collect::<Vec<_>>().join(",");
-            builder.arg(format!("--preserve-env={}", envs));
+            if !env.is_empty() {
+                let mut names = Vec::with_capacity(env.len());
+                for (k, v) in env {
+                    if !is_env_name(k) {
+                        return Err(CommandErrorDetail::InvalidEnvName { name: k.clone() }.into());
+                    }
+                    builder.env(k.as_str(), v.as_str());
+                    names.push(k.as_str());
+                }
+                builder.arg(format!("--preserve-env={}", names.join(",")));
+            }
         }
 
+        builder.arg("-u").arg(user);
         builder.arg(cmd);
-        builder
+        Ok(builder)
     } else {
-        let cmd = CommandBuilder::new(cmd);
-        cmd
-    };
-    builder
+        let mut builder = CommandBuilder::new(cmd);
+        if let Some(env) = env {
+            for (k, v) in env {
+                builder.env(k, v);
+            }
+        }
+        Ok(builder)
+    }
 }
 
 #[cfg(test)]
@@ -194,11 +210,219 @@ mod tests {
             let res = lc.wait().await.unwrap();
             eprintln!("status = {:?}", res);
             assert_eq!(res.code, None);
+            assert_eq!(res.signal(), Some(libc::SIGKILL));
 
             // eprintln!("log = {}", log);
         });
     }
 
+    #[test]
+    fn command_timeout_test() {
+        let cfg = LocalConfig::default();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        rt.block_on(async move {
+            let log = OutputLog::new();
+
+            let lc =
+                spawn_local_command("sleep", &["30".into()], None, None, None, &cfg, &log).unwrap();
+
+            let start = std::time::Instant::now();
+            let res = lc.wait_timeout(Duration::from_millis(200)).await;
+
+            assert!(res.is_err());
+            assert!(start.elapsed() < TERMINATE_GRACE_PERIOD);
+        });
+    }
+
+    #[test]
+    fn command_timeout_grandchild_test() {
+        let cfg = LocalConfig::default();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        rt.block_on(async move {
+            let log = OutputLog::new();
+
+            // background `sleep` inherits stdout/stderr and outlives the shell
+            let lc = spawn_local_command(
+                "sh",
+                &["-c".into(), "sleep 600 & exit 0".into()],
+                None,
+                None,
+                None,
+                &cfg,
+                &log,
+            )
+            .unwrap();
+
+            let start = std::time::Instant::now();
+            let res = lc.wait_timeout(Duration::from_millis(200)).await;
+
+            assert!(res.is_err());
+            assert!(start.elapsed() < TERMINATE_GRACE_PERIOD);
+        });
+    }
+
+    #[test]
+    fn command_timeout_terminated_grandchild_test() {
+        let cfg = LocalConfig::default();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        rt.block_on(async move {
+            let log = OutputLog::new();
+
+            let lc = spawn_local_command(
+                "sh",
+                &["-c".into(), "sleep 600 & sleep 30".into()],
+                None,
+                None,
+                None,
+                &cfg,
+                &log,
+            )
+            .unwrap();
+
+            let start = std::time::Instant::now();
+            let res = lc.wait_timeout(Duration::from_millis(200)).await;
+
+            assert!(res.is_err());
+            assert!(start.elapsed() < TERMINATE_GRACE_PERIOD * 3);
+        });
+    }
+
+    #[test]
+    fn command_within_timeout_test() {
+        let cfg = LocalConfig::default();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        rt.block_on(async move {
+            let log = OutputLog::new();
+
+            let lc = spawn_local_command("echo", &["done".into()], None, None, None, &cfg, &log)
+                .unwrap();
+
+            let out = lc.wait_timeout(Duration::from_secs(10)).await.unwrap();
+
+            assert_eq!(Some(0), out.code());
+            assert_eq!("done\n", out.stdout());
+        });
+    }
+
+    #[test]
+    fn command_non_utf8_output_test() {
+        let cfg = LocalConfig::default();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        rt.block_on(async move {
+            let log = OutputLog::new();
+
+            let lc = spawn_local_command(
+                "printf",
+                &["ok\\n\\377\\376bin\\n".into()],
+                None,
+                None,
+                None,
+                &cfg,
+                &log,
+            )
+            .unwrap();
+
+            let out = lc.wait().await.unwrap();
+
+            assert_eq!(Some(0), out.code());
+            assert_eq!(&b"ok\n\xff\xfebin\n"[..], out.stdout_bytes());
+            assert_eq!("ok\n\u{FFFD}\u{FFFD}bin\n", out.stdout());
+            let err = out.parse_stdout(None).unwrap_err();
+            match err.detail().downcast_ref::<CommandErrorDetail>() {
+                Some(CommandErrorDetail::NonUtf8Output) => {}
+                _ => panic!("expected NonUtf8Output, got {}", err),
+            }
+        });
+    }
+
+    #[test]
+    fn run_as_env_test() {
+        // fake `runas_cmd` printing its args to stderr, then dropping `--preserve-env=...`
+        // and `-u <user>` args and executing the rest
+        let runas_path = std::env::temp_dir().join(format!("op-runas-{}", std::process::id()));
+        std::fs::write(
+            &runas_path,
+            "#!/bin/sh\necho \"$@\" >&2\nshift 3\nexec \"$@\"\n",
+        )
+        .unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&runas_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let cfg: LocalConfig = serde_json::from_value(serde_json::json!({
+            "runas_cmd": runas_path.to_str().unwrap()
+        }))
+        .unwrap();
+
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+
+        let mut env = EnvVars::new();
+        env.insert("OP_RUNAS_TEST_VAR".into(), "some value".into());
+
+        let out = rt.block_on(async move {
+            let log = OutputLog::new();
+
+            let lc = spawn_local_command(
+                "printenv",
+                &["OP_RUNAS_TEST_VAR".into()],
+                Some(&env),
+                None,
+                Some("nobody"),
+                &cfg,
+                &log,
+            )
+            .unwrap();
+
+            lc.wait().await.unwrap()
+        });
+
+        std::fs::remove_file(&runas_path).unwrap();
+
+        assert_eq!(Some(0), out.code());
+        assert_eq!("some value\n", out.stdout());
+        assert_eq!(
+            "--preserve-env=OP_RUNAS_TEST_VAR -u nobody printenv OP_RUNAS_TEST_VAR\n",
+            out.stderr()
+        );
+    }
+
+    #[test]
+    fn run_as_invalid_env_name_test() {
+        let cfg = LocalConfig::default();
+
+        let mut env = EnvVars::new();
+        env.insert("A=1 B".into(), "some value".into());
+
+        let res = spawn_local_command(
+            "printenv",
+            &[],
+            Some(&env),
+            None,
+            Some("nobody"),
+            &cfg,
+            &OutputLog::new(),
+        );
+
+        match res {
+            Err(err) => match err.detail().downcast_ref::<CommandErrorDetail>() {
+                Some(CommandErrorDetail::InvalidEnvName { name }) => assert_eq!("A=1 B", name),
+                _ => panic!("expected InvalidEnvName, got {}", err),
+            },
+            Ok(_) => panic!("expected InvalidEnvName error"),
+        }
+    }
+
     #[test]
     fn run_command_test() {
         let cfg = LocalConfig::default();
@@ -1,6 +1,7 @@
 use super::*;
 
 use kg_diag::io::ResultExt;
+use kg_tree::{FileFormat, NodeRef};
 
 use crate::utils::spawn_blocking;
 use shared_child::SharedChild;
@@ -23,8 +24,35 @@ pub enum CommandErrorDetail {
     #[display(fmt = "cannot spawn command")]
     CommandSpawn,
 
-    #[display(fmt = "malformed command output")]
-    MalformedOutput,
+    #[display(fmt = "malformed command output, expected {format}: '{snippet}'")]
+    MalformedOutput { format: String, snippet: String },
+
+    #[display(fmt = "command timed out after {timeout:?}")]
+    Timeout { timeout: std::time::Duration },
+
+    #[display(fmt = "command output is not valid UTF-8, cannot parse it as structured data")]
+    NonUtf8Output,
+
+    #[display(
+        fmt = "command failed with {status}: '{stderr}'",
+        status = "exit_status_display(code, signal)"
+    )]
+    CommandFailed {
+        code: Option<i32>,
+        signal: Option<i32>,
+        stderr: String,
+    },
+
+    #[display(fmt = "invalid environment variable name '{name}'")]
+    InvalidEnvName { name: String },
+}
+
+fn exit_status_display(code: &Option<i32>, signal: &Option<i32>) -> String {
+    match (*code, *signal) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => format!("signal {}", signal),
+        (None, None) => "no exit code".to_string(),
+    }
 }
 
 impl CommandErrorDetail {
@@ -37,25 +65,177 @@ impl CommandErrorDetail {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CommandOutput {
     code: Option<i32>,
+    /// Signal that terminated the command, only available on unix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signal: Option<i32>,
     stdout: String,
     stderr: String,
+    /// Raw standard output, kept only when it is not valid UTF-8
+    /// (`stdout` is then converted lossily).
+    #[serde(skip)]
+    stdout_raw: Option<Vec<u8>>,
 }
 
 impl CommandOutput {
     pub fn new(code: Option<i32>, stdout: String, stderr: String) -> Self {
         CommandOutput {
             code,
+            signal: None,
+            stdout,
+            stderr,
+            stdout_raw: None,
+        }
+    }
+
+    /// Creates output from raw bytes captured from command. Invalid UTF-8 sequences are
+    /// replaced with `U+FFFD` in `stdout()` and `stderr()`, raw standard output is
+    /// still available with `stdout_bytes()`.
+    pub fn from_bytes(code: Option<i32>, stdout: Vec<u8>, stderr: Vec<u8>) -> Self {
+        let (stdout, stdout_raw) = match String::from_utf8(stdout) {
+            Ok(s) => (s, None),
+            Err(err) => {
+                let raw = err.into_bytes();
+                (String::from_utf8_lossy(&raw).into_owned(), Some(raw))
+            }
+        };
+        let stderr = match String::from_utf8(stderr) {
+            Ok(s) => s,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        };
+        CommandOutput {
+            code,
+            signal: None,
             stdout,
             stderr,
+            stdout_raw,
+        }
+    }
+
+    /// Creates output of finished command from its exit `status` and captured raw output.
+    pub fn from_status(status: ExitStatus, stdout: Vec<u8>, stderr: Vec<u8>) -> Self {
+        let mut out = CommandOutput::from_bytes(status.code(), stdout, stderr);
+        out.signal = exit_signal(&status);
+        out
+    }
+
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// Signal that terminated the command. `code()` is `None` in that case.
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    /// Returns `true` if command exited with code `0`.
+    pub fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+
+    /// Converts unsuccessful output (nonzero exit code or termination by signal)
+    /// into `CommandErrorDetail::CommandFailed` error, with standard error attached.
+    pub fn into_result(self) -> CommandResult<CommandOutput> {
+        if self.success() {
+            Ok(self)
+        } else {
+            Err(CommandErrorDetail::CommandFailed {
+                code: self.code,
+                signal: self.signal,
+                stderr: self.stderr.trim().to_string(),
+            }
+            .into())
+        }
+    }
+
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// Standard output exactly as produced by command.
+    pub fn stdout_bytes(&self) -> &[u8] {
+        match self.stdout_raw {
+            Some(ref raw) => raw,
+            None => self.stdout.as_bytes(),
+        }
+    }
+
+    /// Parses standard output as structured data. Format is detected from the output
+    /// itself if `format` is `None`. Output that is not valid UTF-8 is never parsed,
+    /// since lossy conversion could silently alter values.
+    pub fn parse_stdout(&self, format: Option<FileFormat>) -> CommandResult<NodeRef> {
+        if self.stdout_raw.is_some() {
+            return Err(CommandErrorDetail::NonUtf8Output.into());
+        }
+        parse_output(&self.stdout, format)
+    }
+}
+
+/// Maximum number of characters of malformed output included in an error.
+const OUTPUT_SNIPPET_LEN: usize = 64;
+
+/// Guesses format of structured command output from its leading non-whitespace characters.
+pub fn detect_format(output: &str) -> FileFormat {
+    let first = output
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty() && !l.starts_with('#'));
+
+    match first {
+        Some(l) if l.starts_with('{') => FileFormat::Json,
+        Some(l) if l.starts_with('[') => {
+            // `[section]` header is toml, anything else starting with `[` is a json array
+            let header = l.trim_start_matches('[').trim_end_matches(']').trim();
+            if l.ends_with(']')
+                && header.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && !["true", "false", "null"].contains(&header)
+                && header
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "_-. ".contains(c))
+            {
+                FileFormat::Toml
+            } else {
+                FileFormat::Json
+            }
+        }
+        Some(l) if is_toml_assignment(l) => FileFormat::Toml,
+        _ => FileFormat::Yaml,
+    }
+}
+
+fn is_toml_assignment(line: &str) -> bool {
+    match line.find('=') {
+        Some(pos) => {
+            let key = line[..pos].trim();
+            !key.is_empty()
+                && !key.contains(':')
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || "_-.\"".contains(c))
         }
+        None => false,
     }
 }
 
+/// Parses structured command output, detecting its format if `format` is `None`.
+pub fn parse_output(output: &str, format: Option<FileFormat>) -> CommandResult<NodeRef> {
+    let format = format.unwrap_or_else(|| detect_format(output));
+    NodeRef::from_str(output.into(), format).map_err_as_cause(|| {
+        CommandErrorDetail::MalformedOutput {
+            format: format!("{:?}", format).to_lowercase(),
+            snippet: output.trim().chars().take(OUTPUT_SNIPPET_LEN).collect(),
+        }
+    })
+}
+
 pub struct CommandHandle {
     child: Arc<SharedChild>,
     done_rx: oneshot::Receiver<CommandResult<ExitStatus>>,
-    out_rx: oneshot::Receiver<CommandResult<String>>,
-    err_rx: oneshot::Receiver<CommandResult<String>>,
+    out_rx: oneshot::Receiver<CommandResult<Vec<u8>>>,
+    err_rx: oneshot::Receiver<CommandResult<Vec<u8>>>,
     log: OutputLog,
 }
 
@@ -66,7 +246,48 @@ impl CommandHandle {
 
         self.log.log_status(status.code())?;
 
-        Ok(CommandOutput::new(status.code(), out, err))
+        Ok(CommandOutput::from_status(status, out, err))
+    }
+
+    /// Waits for command to finish, but no longer than `timeout`. After timeout the command
+    /// is sent `SIGTERM`, and killed if it is still running after `TERMINATE_GRACE_PERIOD`.
+    /// Output collected so far is logged, and `CommandErrorDetail::Timeout` is returned.
+    ///
+    /// Descendants of the command can keep its output pipes open after it exits, so output
+    /// is collected within the same deadline; a command whose pipes are still open when
+    /// the deadline passes is treated as timed out.
+    pub async fn wait_timeout(
+        mut self,
+        timeout: std::time::Duration,
+    ) -> CommandResult<CommandOutput> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        if let Ok(status) = tokio::time::timeout_at(deadline, &mut self.done_rx).await {
+            let status = status.unwrap()?;
+            let output = futures::future::join(&mut self.out_rx, &mut self.err_rx);
+            if let Ok((out, err)) = tokio::time::timeout_at(deadline, output).await {
+                let (out, err) = (out.unwrap()?, err.unwrap()?);
+                self.log.log_status(status.code())?;
+                return Ok(CommandOutput::from_status(status, out, err));
+            }
+            self.log.log_status(status.code())?;
+            return Err(CommandErrorDetail::Timeout { timeout }.into());
+        }
+
+        terminate(&self.child);
+        let status = match tokio::time::timeout(TERMINATE_GRACE_PERIOD, &mut self.done_rx).await {
+            Ok(status) => status.unwrap()?,
+            Err(_) => {
+                self.child.kill().map_err(CommandErrorDetail::spawn_err)?;
+                (&mut self.done_rx).await.unwrap()?
+            }
+        };
+
+        let output = futures::future::join(self.out_rx, self.err_rx);
+        let _ = tokio::time::timeout(TERMINATE_GRACE_PERIOD, output).await;
+        self.log.log_status(status.code())?;
+
+        Err(CommandErrorDetail::Timeout { timeout }.into())
     }
 
     pub fn child(&self) -> &Arc<SharedChild> {
@@ -74,11 +295,42 @@ impl CommandHandle {
     }
 }
 
+/// Time given to a command to exit after `SIGTERM`, before it is killed.
+pub const TERMINATE_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(5);
+
+#[cfg(unix)]
+fn terminate(child: &SharedChild) {
+    use shared_child::unix::SharedChildExt;
+    // failure means the process has already exited
+    let _ = child.send_signal(libc::SIGTERM);
+}
+
+#[cfg(not(unix))]
+fn terminate(child: &SharedChild) {
+    let _ = child.kill();
+}
+
+#[cfg(unix)]
+fn exit_signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn exit_signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
 pub type EnvVars = LinkedHashMap<String, String>;
 
 pub enum SourceRef<'a> {
     Path(&'a Path),
     Source(&'a str),
+    /// Standard input of the current process, read once and cached
+    Stdin,
+    /// Script fetched over http(s), must be resolved with [`SourceRef::fetch`] before reading,
+    /// see `URL_FETCH_TIMEOUT` and `URL_FETCH_MAX_SIZE`
+    Url(&'a url::Url),
 }
 
 impl<'a> SourceRef<'a> {
@@ -90,6 +342,8 @@ impl<'a> SourceRef<'a> {
                 Ok(s)
             }
             SourceRef::Source(src) => Ok(src.into()),
+            SourceRef::Stdin => Ok(read_stdin()?),
+            SourceRef::Url(url) => Err(unfetched_url(url).into()),
         }
     }
 
@@ -103,6 +357,18 @@ impl<'a> SourceRef<'a> {
                 buf.push_str(src);
                 Ok(())
             }
+            SourceRef::Stdin | SourceRef::Url(_) => {
+                buf.push_str(&self.read()?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Fetches `SourceRef::Url` script, other sources are returned as they are.
+    pub async fn fetch(&self) -> Result<Source, kg_diag::IoErrorDetail> {
+        match *self {
+            SourceRef::Url(url) => Ok(Source::Source(fetch_url(url, URL_FETCH_MAX_SIZE).await?)),
+            _ => Ok(self.to_owned()),
         }
     }
 
@@ -110,6 +376,8 @@ impl<'a> SourceRef<'a> {
         match self {
             SourceRef::Path(p) => Source::Path(p.to_path_buf()),
             SourceRef::Source(src) => Source::Source(src.to_string()),
+            SourceRef::Stdin => Source::Stdin,
+            SourceRef::Url(url) => Source::Url((*url).clone()),
         }
     }
 }
@@ -117,6 +385,8 @@ impl<'a> SourceRef<'a> {
 pub enum Source {
     Path(PathBuf),
     Source(String),
+    Stdin,
+    Url(url::Url),
 }
 
 impl Source {
@@ -124,15 +394,103 @@ impl Source {
         match self {
             Source::Path(p) => SourceRef::Path(p.as_path()),
             Source::Source(src) => SourceRef::Source(src.as_str()),
+            Source::Stdin => SourceRef::Stdin,
+            Source::Url(url) => SourceRef::Url(url),
+        }
+    }
+}
+
+/// Reads whole standard input of the current process. Input is read only once,
+/// subsequent calls return the cached content.
+fn read_stdin() -> std::io::Result<String> {
+    lazy_static! {
+        static ref STDIN: parking_lot::Mutex<Option<String>> = parking_lot::Mutex::new(None);
+    }
+
+    let mut stdin = STDIN.lock();
+    if let Some(ref s) = *stdin {
+        return Ok(s.clone());
+    }
+    let mut s = String::new();
+    std::io::stdin().read_to_string(&mut s)?;
+    *stdin = Some(s.clone());
+    Ok(s)
+}
+
+/// Maximum time allowed for fetching script from url.
+pub const URL_FETCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Maximum size in bytes of script fetched from url.
+pub const URL_FETCH_MAX_SIZE: u64 = 1024 * 1024;
+
+fn unfetched_url(url: &url::Url) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("script from '{}' must be fetched before reading", url),
+    )
+}
+
+async fn fetch_url(url: &url::Url, max_size: u64) -> std::io::Result<String> {
+    use std::io::{Error, ErrorKind};
+
+    fn fetch_err(url: &url::Url, err: reqwest::Error) -> Error {
+        let kind = if err.is_timeout() {
+            ErrorKind::TimedOut
+        } else {
+            ErrorKind::Other
+        };
+        Error::new(kind, format!("cannot fetch script from '{}': {}", url, err))
+    }
+
+    let too_large = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("script fetched from '{}' exceeds {} bytes", url, max_size),
+        )
+    };
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("unsupported url scheme '{}'", url.scheme()),
+        ));
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(URL_FETCH_TIMEOUT)
+        .build()
+        .map_err(|err| fetch_err(url, err))?;
+    let mut resp = client
+        .get(url.clone())
+        .send()
+        .await
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| fetch_err(url, err))?;
+
+    if resp.content_length().map_or(false, |len| len > max_size) {
+        return Err(too_large());
+    }
+
+    let mut buf = Vec::new();
+    while let Some(chunk) = resp.chunk().await.map_err(|err| fetch_err(url, err))? {
+        if (buf.len() + chunk.len()) as u64 > max_size {
+            return Err(too_large());
         }
+        buf.extend_from_slice(&chunk);
     }
+
+    String::from_utf8(buf).map_err(|err| Error::new(ErrorKind::InvalidData, err))
 }
 
+/// Directory used for staging scripts when configured script directory is not usable.
+pub const SCRIPT_FALLBACK_DIR: &str = "/tmp";
+
 fn prepare_script<W: std::io::Write>(
     script: SourceRef,
     args: &[String],
     env: Option<&EnvVars>,
     cwd: Option<&Path>,
+    config: &ssh::SshConfig,
     mut out: W,
 ) -> Result<(), IoErrorDetail> {
     use rand::Rng;
@@ -143,18 +501,50 @@ fn prepare_script<W: std::io::Write>(
     writeln!(out, "#!/usr/bin/env bash")?;
 
     if let Some(cwd) = cwd {
-        writeln!(out, "cd \"{}\"", cwd.display())?;
+        writeln!(out, "cd {}", shell_quote(&cwd.to_string_lossy()))?;
     }
     if let Some(env) = env {
         for (k, v) in env {
-            writeln!(out, "export {}='{}'", k, v)?;
+            if !is_env_name(k) {
+                return Err(IoErrorDetail::Io {
+                    kind: std::io::ErrorKind::InvalidInput,
+                    message: format!("invalid environment variable name '{}'", k),
+                });
+            }
+            writeln!(out, "export {}={}", k, shell_quote(v))?;
         }
     }
 
-    // Create temp script file in ramdisk
-    let tmp_path = format!("/dev/shm/op_{:0x}", rng.gen::<u64>());
+    // Pick first existing and writable staging directory (ramdisk by default)
+    let script_dir = config.script_dir().to_string_lossy();
+    writeln!(out, "OP_TMP_DIR={}", shell_quote(SCRIPT_FALLBACK_DIR))?;
+    writeln!(
+        out,
+        "for d in {} {}; do",
+        shell_quote(&script_dir),
+        shell_quote(SCRIPT_FALLBACK_DIR)
+    )?;
+    writeln!(
+        out,
+        "  if [ -d \"$d\" ] && [ -w \"$d\" ]; then OP_TMP_DIR=\"$d\"; break; fi"
+    )?;
+    writeln!(out, "done")?;
+
+    // Create temp script file, random suffix avoids collisions between concurrent runs
+    writeln!(out, "OP_TMP=\"$OP_TMP_DIR/op_{:0x}\"", rng.gen::<u64>())?;
+    let tmp_path = "\"$OP_TMP\"";
+
+    // Remove temp script on any exit path, including termination by signal.
+    // Trap does not change the exit status of the shell.
+    writeln!(out, "trap 'rm -f {}' EXIT", tmp_path)?;
+
     writeln!(out, "cat > {} <<-'%%EOF%%'", tmp_path)?;
-    writeln!(out, "{}", script.trim())?;
+    write_script_body(
+        &mut out,
+        script.trim(),
+        config.strict_scripts(),
+        config.trace_scripts(),
+    )?;
     writeln!(out, "%%EOF%%")?;
 
     // Make temp script executable
@@ -166,7 +556,7 @@ fn prepare_script<W: std::io::Write>(
     } else {
         write!(out, "({}", tmp_path)?;
         for arg in args {
-            write!(out, " \'{}\'", arg)?;
+            write!(out, " {}", shell_quote(arg))?;
         }
         writeln!(out, ")")?;
     }
@@ -174,21 +564,88 @@ fn prepare_script<W: std::io::Write>(
     // Capture script status
     write!(out, "STATUS=$?\n")?;
 
-    // Remove temp script
-    write!(out, "rm -f {}\n", tmp_path)?;
-
     // Exit with tmp script status code
     write!(out, "exit $STATUS\n")?;
 
     Ok(())
 }
 
+/// Writes script, with shell options injected after shebang line. Options are only injected
+/// into bash scripts (without shebang or with bash shebang), since `pipefail` is not POSIX.
+fn write_script_body<W: std::io::Write>(
+    mut out: W,
+    script: &str,
+    strict: bool,
+    trace: bool,
+) -> std::io::Result<()> {
+    let (shebang, body) = if script.starts_with("#!") {
+        match script.find('\n') {
+            Some(pos) => (Some(&script[..pos]), &script[pos + 1..]),
+            None => (Some(script), ""),
+        }
+    } else {
+        (None, script)
+    };
+
+    let is_bash = shebang.map_or(true, |s| s.contains("bash"));
+    if let Some(shebang) = shebang {
+        writeln!(out, "{}", shebang)?;
+    }
+    if is_bash && strict {
+        writeln!(out, "set -euo pipefail")?;
+    }
+    if is_bash && trace {
+        writeln!(out, "set -x")?;
+    }
+    writeln!(out, "{}", body)
+}
+
+/// Quotes `s` for POSIX shell. Strings containing only safe characters are returned as is,
+/// others are wrapped in single quotes, with embedded single quotes written as `'\''`.
+pub fn shell_quote(s: &str) -> std::borrow::Cow<str> {
+    fn is_safe(c: char) -> bool {
+        c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)
+    }
+
+    if !s.is_empty() && s.chars().all(is_safe) {
+        std::borrow::Cow::Borrowed(s)
+    } else {
+        std::borrow::Cow::Owned(format!("'{}'", s.replace('\'', "'\\''")))
+    }
+}
+
+/// Returns `true` if `name` can be used as shell variable name, ie. consists of ASCII
+/// alphanumerics and underscores and does not start with a digit.
+pub(crate) fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+const SHELL_CMD: &str = "/bin/sh";
+
+/// Detaches child process from controlling terminal, called in the child before exec.
+#[cfg(unix)]
+fn setsid() -> std::io::Result<()> {
+    if unsafe { libc::setsid() } == -1 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct CommandBuilder {
     cmd: String,
     args: Vec<String>,
     envs: LinkedHashMap<String, String>,
+    cwd: Option<PathBuf>,
     setsid: bool,
+    shell: bool,
 }
 
 impl CommandBuilder {
@@ -197,7 +654,9 @@ impl CommandBuilder {
             cmd: cmd.into(),
             args: Vec::new(),
             envs: LinkedHashMap::new(),
+            cwd: None,
             setsid: false,
+            shell: false,
         }
     }
 
@@ -227,93 +686,97 @@ impl CommandBuilder {
         self
     }
 
+    /// Sets working directory of the command.
+    pub fn cwd<P: Into<PathBuf>>(&mut self, path: P) -> &mut CommandBuilder {
+        self.cwd = Some(path.into());
+        self
+    }
+
+    /// If enabled, command is run wrapped in `sh -c`, otherwise it is executed directly (default).
+    pub fn shell(&mut self, enable: bool) -> &mut CommandBuilder {
+        self.shell = enable;
+        self
+    }
+
+    /// Returns program and arguments to execute, taking `shell` flag into account.
+    fn program_args(&self) -> (&str, Vec<String>) {
+        if self.shell {
+            (SHELL_CMD, vec!["-c".to_string(), self.to_string()])
+        } else {
+            (self.cmd.as_str(), self.args.clone())
+        }
+    }
+
     #[cfg(unix)]
     fn handle_setsid(&self, c: &mut Command) {
-        use std::os::unix::process::CommandExt;
-
         if self.setsid {
             unsafe {
-                c.pre_exec(|| {
-                    if libc::setsid() == -1 {
-                        Err(std::io::Error::last_os_error())
-                    } else {
-                        Ok(())
-                    }
-                });
+                c.pre_exec(setsid);
             }
         }
     }
+
     #[cfg(unix)]
     fn handle_setsid_sync(&self, c: &mut std::process::Command) {
         use std::os::unix::process::CommandExt;
 
         if self.setsid {
             unsafe {
-                c.pre_exec(|| {
-                    if libc::setsid() == -1 {
-                        Err(std::io::Error::last_os_error())
-                    } else {
-                        Ok(())
-                    }
-                });
+                c.pre_exec(setsid);
             }
         }
     }
+
+    /// Sessions are unix specific, `setsid` flag is ignored on other platforms.
     #[cfg(not(unix))]
-    fn handle_setsid(&self, c: &mut Command) {
-        if self.setsid {
-            unsupported!()
-        }
-    }
+    fn handle_setsid(&self, _c: &mut Command) {}
+
+    #[cfg(not(unix))]
+    fn handle_setsid_sync(&self, _c: &mut std::process::Command) {}
 
     pub fn build(&self) -> Command {
-        let mut c = Command::new(&self.cmd);
-        for a in self.args.iter() {
+        let (cmd, args) = self.program_args();
+        let mut c = Command::new(cmd);
+        for a in args.iter() {
             c.arg(a);
         }
         for (k, v) in self.envs.iter() {
             c.env(k, v);
         }
+        if let Some(ref cwd) = self.cwd {
+            c.current_dir(cwd);
+        }
         self.handle_setsid(&mut c);
         c
     }
     // sync version of this method is necessary because we cannot call async code in SshSession destructor
     pub fn build_sync(&self) -> std::process::Command {
-        let mut c = std::process::Command::new(&self.cmd);
-        for a in self.args.iter() {
+        let (cmd, args) = self.program_args();
+        let mut c = std::process::Command::new(cmd);
+        for a in args.iter() {
             c.arg(a);
         }
         for (k, v) in self.envs.iter() {
             c.env(k, v);
         }
+        if let Some(ref cwd) = self.cwd {
+            c.current_dir(cwd);
+        }
         self.handle_setsid_sync(&mut c);
         c
     }
 
     /// Returns command string representation with env vars at the beginning
-    /// eg. `ENV1='some value' printenv`
+    /// eg. `ENV1='some value' printenv`. Args and env values are quoted for POSIX shell.
     pub fn to_string_with_env(&self) -> String {
         use std::fmt::Write;
         let mut out = String::new();
 
-        let envs = self
-            .envs
-            .iter()
-            .map(|(k, v)| format!("{}='{}'", k, v))
-            .collect::<Vec<String>>()
-            .join(" ");
-
-        write!(out, "{} ", envs).unwrap();
-
-        write!(out, "{}", self.cmd).unwrap();
-
-        for a in self.args.iter() {
-            if a.contains(' ') {
-                write!(out, " \"{}\"", a).unwrap();
-            } else {
-                write!(out, " {}", a).unwrap();
-            }
+        for (k, v) in self.envs.iter() {
+            write!(out, "{}={} ", k, shell_quote(v)).unwrap();
         }
+
+        write!(out, "{}", self).unwrap();
         out
     }
 }
@@ -322,30 +785,29 @@ impl std::fmt::Display for CommandBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{}", self.cmd)?;
         for a in self.args.iter() {
-            if a.contains(' ') {
-                write!(f, " \"{}\"", a)?;
-            } else {
-                write!(f, " {}", a)?;
-            }
+            write!(f, " {}", shell_quote(a))?;
         }
         Ok(())
     }
 }
 
-fn collect_out<R: Read, F: FnMut(&str) -> CommandResult<()>>(
+/// Collects output from `reader` as raw bytes, calling `line_cb` for every line.
+/// Output is not required to be valid UTF-8.
+fn collect_out<R: Read, F: FnMut(&[u8]) -> CommandResult<()>>(
     reader: R,
     mut line_cb: F,
-) -> CommandResult<String> {
-    let mut out = String::new();
+) -> CommandResult<Vec<u8>> {
+    let mut out = Vec::new();
     let r = BufReader::new(reader);
-    let lines = r.lines();
-
-    use std::fmt::Write;
 
-    for res in lines {
-        let line = res.map_err_to_diag()?;
+    for res in r.split(b'\n') {
+        let mut line = res.map_err_to_diag()?;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
         line_cb(&line)?;
-        writeln!(&mut out, "{}", &line).unwrap();
+        out.extend_from_slice(&line);
+        out.push(b'\n');
     }
 
     Ok(out)
@@ -356,13 +818,13 @@ fn handle_std<O: Read + Send + 'static, E: Read + Send + 'static>(
     out_reader: O,
     err_reader: E,
 ) -> (
-    oneshot::Receiver<CommandResult<String>>,
-    oneshot::Receiver<CommandResult<String>>,
+    oneshot::Receiver<CommandResult<Vec<u8>>>,
+    oneshot::Receiver<CommandResult<Vec<u8>>>,
 ) {
     let l = log.clone();
     let out_rx = spawn_blocking(move || {
         collect_out(out_reader, |line| {
-            l.log_out(line.as_bytes())?;
+            l.log_out(line)?;
             Ok(())
         })
     });
@@ -370,7 +832,7 @@ fn handle_std<O: Read + Send + 'static, E: Read + Send + 'static>(
     let l = log.clone();
     let err_rx = spawn_blocking(move || {
         collect_out(err_reader, |line| {
-            l.log_err(line.as_bytes())?;
+            l.log_err(line)?;
             Ok(())
         })
     });
@@ -476,3 +938,428 @@ mod tests {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves single http response with `body` on a random local port, returns url.
+    fn serve_once(body: &'static str) -> url::Url {
+        use std::io::Write;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        });
+        url::Url::parse(&format!("http://127.0.0.1:{}/script.sh", port)).unwrap()
+    }
+
+    #[test]
+    fn detect_output_format() {
+        assert_eq!(FileFormat::Json, detect_format("  {\"a\": 1}"));
+        assert_eq!(FileFormat::Json, detect_format("[1, 2, 3]"));
+        assert_eq!(FileFormat::Json, detect_format("[\n  {\"a\": 1}\n]"));
+        assert_eq!(FileFormat::Json, detect_format("[\"a\"]"));
+        assert_eq!(FileFormat::Json, detect_format("[true]"));
+        assert_eq!(FileFormat::Toml, detect_format("[server]\nport = 80"));
+        assert_eq!(FileFormat::Toml, detect_format("# comment\nport = 80"));
+        assert_eq!(FileFormat::Yaml, detect_format("port: 80"));
+        assert_eq!(FileFormat::Yaml, detect_format("url: http://host/?a=b"));
+        assert_eq!(FileFormat::Yaml, detect_format("- a\n- b"));
+        assert_eq!(FileFormat::Yaml, detect_format(""));
+    }
+
+    #[test]
+    fn parse_output_auto() {
+        let out = CommandOutput::new(Some(0), "{\"a\": 1}".into(), String::new());
+        let node = out.parse_stdout(None).unwrap();
+        assert_eq!("1", node.get_child_key("a").unwrap().data().as_string());
+    }
+
+    #[test]
+    fn output_from_bytes_lossy() {
+        let out = CommandOutput::from_bytes(Some(0), b"a: 1\n".to_vec(), b"\xc3(\n".to_vec());
+        assert_eq!("a: 1\n", out.stdout());
+        assert_eq!(&b"a: 1\n"[..], out.stdout_bytes());
+        assert_eq!("\u{FFFD}(\n", out.stderr());
+        assert!(out.parse_stdout(None).is_ok());
+    }
+
+    #[test]
+    fn output_into_result_success() {
+        let out = CommandOutput::new(Some(0), "ok\n".into(), String::new());
+        assert!(out.success());
+        assert_eq!("ok\n", out.into_result().unwrap().stdout());
+    }
+
+    #[test]
+    fn output_into_result_failed() {
+        let out = CommandOutput::new(Some(2), String::new(), "no such file\n".into());
+        assert!(!out.success());
+
+        let err = out.into_result().unwrap_err();
+        match err.detail().downcast_ref::<CommandErrorDetail>() {
+            Some(CommandErrorDetail::CommandFailed { code, stderr, .. }) => {
+                assert_eq!(Some(2), *code);
+                assert_eq!("no such file", stderr);
+            }
+            _ => panic!("expected CommandFailed, got {}", err),
+        }
+    }
+
+    #[test]
+    fn output_into_result_signal() {
+        let mut out = CommandOutput::new(None, String::new(), String::new());
+        out.signal = Some(libc::SIGTERM);
+        assert!(!out.success());
+
+        let err = out.into_result().unwrap_err();
+        match err.detail().downcast_ref::<CommandErrorDetail>() {
+            Some(CommandErrorDetail::CommandFailed { code, signal, .. }) => {
+                assert_eq!(None, *code);
+                assert_eq!(Some(libc::SIGTERM), *signal);
+            }
+            _ => panic!("expected CommandFailed, got {}", err),
+        }
+    }
+
+    #[test]
+    fn output_from_status_signal() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let out =
+            CommandOutput::from_status(ExitStatus::from_raw(libc::SIGTERM), Vec::new(), Vec::new());
+        assert_eq!(None, out.code());
+        assert_eq!(Some(libc::SIGTERM), out.signal());
+
+        let out = CommandOutput::from_status(ExitStatus::from_raw(1 << 8), Vec::new(), Vec::new());
+        assert_eq!(Some(1), out.code());
+        assert_eq!(None, out.signal());
+    }
+
+    #[test]
+    fn collect_out_non_utf8() {
+        let mut lines = Vec::new();
+        let out = collect_out(&b"ok\r\n\xff bin\nend"[..], |line| {
+            lines.push(line.to_vec());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            vec![b"ok".to_vec(), b"\xff bin".to_vec(), b"end".to_vec()],
+            lines
+        );
+        assert_eq!(&b"ok\n\xff bin\nend\n"[..], &out[..]);
+    }
+
+    #[test]
+    fn parse_output_malformed() {
+        let err = parse_output("{\"a\": ", Some(FileFormat::Json)).unwrap_err();
+        match err.detail().downcast_ref::<CommandErrorDetail>() {
+            Some(CommandErrorDetail::MalformedOutput { format, snippet }) => {
+                assert_eq!("json", format);
+                assert_eq!("{\"a\":", snippet);
+            }
+            _ => panic!("expected MalformedOutput, got {}", err),
+        }
+        assert!(err.cause().is_some());
+    }
+
+    #[test]
+    fn source_url_fetch() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let url = serve_once("echo hello\n");
+        let src = rt.block_on(SourceRef::Url(&url).fetch()).unwrap();
+        assert_eq!("echo hello\n", src.as_ref().read().unwrap());
+    }
+
+    #[test]
+    fn source_url_size_cap() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let url = serve_once("echo hello\n");
+        let err = rt.block_on(fetch_url(&url, 4)).unwrap_err();
+        assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn source_url_unsupported_scheme() {
+        let rt = tokio::runtime::Runtime::new().expect("runtime");
+        let url = url::Url::parse("file:///etc/passwd").unwrap();
+        let res = rt.block_on(SourceRef::Url(&url).fetch());
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn source_url_read_unfetched() {
+        let url = url::Url::parse("http://127.0.0.1/script.sh").unwrap();
+        assert!(SourceRef::Url(&url).read().is_err());
+    }
+
+    fn ssh_config(config: serde_json::Value) -> ssh::SshConfig {
+        serde_json::from_value(config).unwrap()
+    }
+
+    fn prepared_script(script: &str, config: &ssh::SshConfig) -> String {
+        let mut buf = Vec::new();
+        prepare_script(SourceRef::Source(script), &[], None, None, config, &mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    fn run_prepared_script(script_dir: &Path) -> std::process::Output {
+        use std::io::Write;
+
+        let mut buf = Vec::new();
+        let script = SourceRef::Source("echo \"$0\"; exit 3");
+        let config = ssh_config(serde_json::json!({ "script_dir": script_dir }));
+        prepare_script(script, &[], None, None, &config, &mut buf).unwrap();
+
+        let mut child = std::process::Command::new("bash")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(&buf).unwrap();
+        child.wait_with_output().unwrap()
+    }
+
+    #[test]
+    fn prepare_script_fallback_dir() {
+        let out = run_prepared_script(Path::new("/nonexistent/op/dir"));
+        let stdout = String::from_utf8_lossy(&out.stdout);
+
+        assert_eq!(Some(3), out.status.code());
+        assert!(stdout.starts_with("/tmp/op_"));
+        assert!(!Path::new(stdout.trim()).exists());
+    }
+
+    #[test]
+    fn prepare_script_killed_status() {
+        use std::io::Write;
+
+        let mut buf = Vec::new();
+        let script = SourceRef::Source("echo \"$0\"; kill -TERM $$");
+        let config = ssh_config(serde_json::json!({ "script_dir": "/tmp" }));
+        prepare_script(script, &[], None, None, &config, &mut buf).unwrap();
+
+        let mut child = std::process::Command::new("bash")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(&buf).unwrap();
+        let out = child.wait_with_output().unwrap();
+        let stdout = String::from_utf8_lossy(&out.stdout);
+
+        assert_eq!(Some(143), out.status.code());
+        assert!(stdout.starts_with("/tmp/op_"));
+        assert!(!Path::new(stdout.trim()).exists());
+    }
+
+    #[test]
+    fn prepare_script_quotes_values() {
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let cwd = tmp.path().join("it's here");
+        std::fs::create_dir(&cwd).unwrap();
+        let cwd = cwd.canonicalize().unwrap();
+        let mut env = EnvVars::new();
+        env.insert("VAR".into(), "it's $(whoami)".into());
+        let args = vec!["a'b".to_string(), "$HOME".to_string()];
+
+        let mut buf = Vec::new();
+        let script = SourceRef::Source("echo \"$VAR\"; echo \"$1\"; echo \"$2\"; pwd");
+        let config = ssh_config(serde_json::json!({ "script_dir": "/tmp" }));
+        prepare_script(script, &args, Some(&env), Some(&cwd), &config, &mut buf).unwrap();
+
+        let mut child = std::process::Command::new("bash")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(&buf).unwrap();
+        let out = child.wait_with_output().unwrap();
+
+        assert_eq!(Some(0), out.status.code());
+        assert_eq!(
+            format!("it's $(whoami)\na'b\n$HOME\n{}\n", cwd.display()),
+            String::from_utf8_lossy(&out.stdout)
+        );
+    }
+
+    #[test]
+    fn prepare_script_invalid_env_name() {
+        let mut env = EnvVars::new();
+        env.insert("A=1; rm -rf /tmp/x #".into(), "v".into());
+
+        let mut buf = Vec::new();
+        let script = SourceRef::Source("true");
+        let config = ssh::SshConfig::default();
+        let res = prepare_script(script, &[], Some(&env), None, &config, &mut buf);
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn env_name() {
+        assert!(is_env_name("PATH"));
+        assert!(is_env_name("_var1"));
+        assert!(!is_env_name(""));
+        assert!(!is_env_name("1VAR"));
+        assert!(!is_env_name("A-B"));
+        assert!(!is_env_name("A=B"));
+    }
+
+    #[test]
+    fn prepare_script_default_mode() {
+        let script = prepared_script("false\necho done", &ssh::SshConfig::default());
+
+        assert!(!script.contains("set -euo pipefail"));
+        assert!(!script.contains("set -x"));
+        assert!(script.contains("<<-'%%EOF%%'\nfalse\necho done\n%%EOF%%\n"));
+    }
+
+    #[test]
+    fn prepare_script_strict_mode() {
+        let config = ssh_config(serde_json::json!({
+            "strict_scripts": true,
+            "trace_scripts": true,
+        }));
+
+        let script = prepared_script("false\necho done", &config);
+        assert!(
+            script.contains("<<-'%%EOF%%'\nset -euo pipefail\nset -x\nfalse\necho done\n%%EOF%%\n")
+        );
+
+        let script = prepared_script("#!/bin/bash\nfalse", &config);
+        assert!(script
+            .contains("<<-'%%EOF%%'\n#!/bin/bash\nset -euo pipefail\nset -x\nfalse\n%%EOF%%\n"));
+
+        let script = prepared_script("#!/usr/bin/env python3\nprint()", &config);
+        assert!(script.contains("<<-'%%EOF%%'\n#!/usr/bin/env python3\nprint()\n%%EOF%%\n"));
+    }
+
+    #[test]
+    fn shell_quote_safe() {
+        assert_eq!("abc", shell_quote("abc"));
+        assert_eq!("/usr/bin/env", shell_quote("/usr/bin/env"));
+        assert_eq!("--opt=a,b", shell_quote("--opt=a,b"));
+        assert_eq!("''", shell_quote(""));
+    }
+
+    #[test]
+    fn shell_quote_nasty() {
+        assert_eq!(r#"'a'\''b'"#, shell_quote("a'b"));
+        assert_eq!("'$(whoami)'", shell_quote("$(whoami)"));
+        assert_eq!("'`id`'", shell_quote("`id`"));
+        assert_eq!("'some value'", shell_quote("some value"));
+        assert_eq!("'a\"b'", shell_quote("a\"b"));
+        assert_eq!("'line1\nline2'", shell_quote("line1\nline2"));
+    }
+
+    #[test]
+    fn display_quoted_args() {
+        let mut cmd = CommandBuilder::new("echo");
+        cmd.arg("a'b")
+            .arg("$(whoami)")
+            .arg("with space")
+            .arg("plain");
+
+        assert_eq!(
+            r#"echo 'a'\''b' '$(whoami)' 'with space' plain"#,
+            cmd.to_string()
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let args = ["a'b", "$(whoami)", "with space", "`id`", "x\ny"];
+        let mut cmd = CommandBuilder::new("printf");
+        cmd.arg("%s|");
+        cmd.args(args.iter().cloned());
+
+        let out = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(cmd.to_string())
+            .output()
+            .unwrap();
+
+        let expected = args.iter().map(|a| format!("{}|", a)).collect::<String>();
+        assert_eq!(expected, String::from_utf8(out.stdout).unwrap());
+    }
+
+    #[test]
+    fn build_with_cwd_and_shell() {
+        let dir = std::env::temp_dir();
+        let mut cmd = CommandBuilder::new("echo");
+        cmd.arg("$(pwd)").cwd(&dir).shell(true);
+
+        let out = cmd.build_sync().output().unwrap();
+
+        // argument is quoted, so it is not expanded by the shell
+        assert_eq!("$(pwd)\n", String::from_utf8(out.stdout).unwrap());
+
+        let out = CommandBuilder::new("pwd")
+            .cwd(&dir)
+            .build_sync()
+            .output()
+            .unwrap();
+        assert_eq!(
+            dir.canonicalize().unwrap(),
+            PathBuf::from(String::from_utf8(out.stdout).unwrap().trim_end())
+                .canonicalize()
+                .unwrap()
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn build_sync_setsid() {
+        let mut cmd = CommandBuilder::new("sh");
+        cmd.arg("-c").arg("ps -o sid= -p $$").setsid(true);
+
+        let out = cmd.build_sync().output().unwrap();
+        let sid: i32 = String::from_utf8(out.stdout)
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+        let parent_sid = unsafe { libc::getsid(0) };
+
+        assert_ne!(parent_sid, sid);
+    }
+
+    #[test]
+    fn to_string_with_env_empty() {
+        let mut cmd = CommandBuilder::new("printenv");
+        assert_eq!("printenv", cmd.to_string_with_env());
+
+        cmd.arg("HOME");
+        assert_eq!("printenv HOME", cmd.to_string_with_env());
+    }
+
+    #[test]
+    fn to_string_with_env_single() {
+        let mut cmd = CommandBuilder::new("printenv");
+        cmd.env("ENV1", "value");
+        assert_eq!("ENV1=value printenv", cmd.to_string_with_env());
+    }
+
+    #[test]
+    fn env_values_quoted() {
+        let mut cmd = CommandBuilder::new("printenv");
+        cmd.env("ENV1", "it's $HOME");
+
+        assert_eq!(r#"ENV1='it'\''s $HOME' printenv"#, cmd.to_string_with_env());
+    }
+}
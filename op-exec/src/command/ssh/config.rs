@@ -8,6 +8,9 @@ pub struct SshConfig {
     runas_cmd: String,
     shell_cmd: String,
     cache_limit: usize,
+    script_dir: PathBuf,
+    strict_scripts: bool,
+    trace_scripts: bool,
 }
 
 impl SshConfig {
@@ -31,6 +34,23 @@ impl SshConfig {
         self.cache_limit
     }
 
+    /// Directory on remote host where scripts are staged before execution. If it does not exist
+    /// or is not writable at runtime, `SCRIPT_FALLBACK_DIR` is used.
+    pub fn script_dir(&self) -> &Path {
+        &self.script_dir
+    }
+
+    /// If enabled, bash scripts are run with `set -euo pipefail`, so that any failing command
+    /// fails the whole script. Disabled by default for compatibility, but recommended.
+    pub fn strict_scripts(&self) -> bool {
+        self.strict_scripts
+    }
+
+    /// If enabled, bash scripts are run with `set -x`, tracing executed commands to stderr.
+    pub fn trace_scripts(&self) -> bool {
+        self.trace_scripts
+    }
+
     pub fn set_socket_dir(&mut self, socket_dir: &Path) {
         self.socket_dir = socket_dir.to_path_buf();
     }
@@ -44,6 +64,9 @@ impl Default for SshConfig {
             runas_cmd: "/bin/sudo".into(),
             shell_cmd: "/bin/bash".into(),
             cache_limit: 10,
+            script_dir: PathBuf::from("/dev/shm"),
+            strict_scripts: false,
+            trace_scripts: false,
         }
     }
 }
@@ -1,8 +1,9 @@
+use std::borrow::Cow;
 use url::Url;
 
 use super::*;
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", tag = "method")]
 pub enum SshAuth {
     Default,
@@ -36,12 +37,54 @@ impl SshAuth {
     }
 }
 
+/// Password is never printed, so that it does not leak into logs.
+impl SshAuth {
+    /// Stable discriminant of auth method, never contains the password. Identity file path
+    /// is hashed to keep it usable in file names.
+    fn to_id_string(&self) -> String {
+        match *self {
+            SshAuth::Default => "default".into(),
+            SshAuth::PublicKey { ref identity_file } => format!(
+                "key{}",
+                short_hash(identity_file.to_string_lossy().as_bytes())
+            ),
+            SshAuth::Password { .. } => "password".into(),
+        }
+    }
+}
+
+impl std::fmt::Debug for SshAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            SshAuth::Default => write!(f, "Default"),
+            SshAuth::PublicKey { ref identity_file } => f
+                .debug_struct("PublicKey")
+                .field("identity_file", identity_file)
+                .finish(),
+            SshAuth::Password { .. } => f
+                .debug_struct("Password")
+                .field("password", &"***")
+                .finish(),
+        }
+    }
+}
+
 impl Default for SshAuth {
     fn default() -> Self {
         Self::Default
     }
 }
 
+/// Returns `hostname` enclosed in brackets if it is an IPv6 literal, as required in urls
+/// and rsync `host:path` arguments. Ssh target argument takes IPv6 literals as they are.
+pub fn bracket_ipv6(hostname: &str) -> Cow<str> {
+    if hostname.contains(':') && !hostname.starts_with('[') {
+        Cow::Owned(format!("[{}]", hostname))
+    } else {
+        Cow::Borrowed(hostname)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(default)]
 pub struct SshDest {
@@ -65,24 +108,43 @@ impl SshDest {
         }
     }
 
-    pub fn from_url(url: &Url, auth: SshAuth) -> SshDest {
-        let hostname = url.host().unwrap().to_string();
+    /// Creates destination from `ssh://[username@]hostname[:port]` url. Username defaults
+    /// to the current user and port to 22.
+    pub fn from_url(url: &Url, auth: SshAuth) -> SshResult<SshDest> {
+        let invalid = |reason: &str| -> SshError {
+            SshErrorDetail::InvalidUrl {
+                url: url.to_string(),
+                reason: reason.to_string(),
+            }
+            .into()
+        };
+
+        if url.scheme() != "ssh" {
+            return Err(invalid("scheme must be 'ssh'"));
+        }
+        let hostname = match url.host() {
+            Some(url::Host::Ipv6(addr)) => addr.to_string(),
+            Some(h) if !h.to_string().is_empty() => h.to_string(),
+            _ => return Err(invalid("missing hostname")),
+        };
+        let port = match url.port() {
+            Some(0) => return Err(invalid("port must be in range 1-65535")),
+            Some(port) => port,
+            None => 22,
+        };
         let username = match url.username() {
             "" => users::get_current_username()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
+                .and_then(|u| u.to_str().map(|u| u.to_string()))
+                .ok_or_else(|| invalid("missing username, cannot determine current user"))?,
             u => u.to_string(),
         };
-        let port = url.port().unwrap_or(22);
 
-        SshDest {
+        Ok(SshDest {
             hostname,
             port,
             username,
             auth,
-        }
+        })
     }
 
     pub fn to_url(&self) -> String {
@@ -90,13 +152,13 @@ impl SshDest {
             format!(
                 "ssh://{username}@{hostname}",
                 username = self.username,
-                hostname = self.hostname
+                hostname = bracket_ipv6(&self.hostname)
             )
         } else {
             format!(
                 "ssh://{username}@{hostname}:{port}",
                 username = self.username,
-                hostname = self.hostname,
+                hostname = bracket_ipv6(&self.hostname),
                 port = self.port
             )
         }
@@ -117,12 +179,15 @@ impl SshDest {
         self.auth.set_auth(cmd);
     }
 
+    /// Identifier of destination used as session cache key and socket file name.
+    /// Includes auth method, so that sessions are not shared between different auth.
     pub(crate) fn to_id_string(&self) -> String {
         format!(
-            "{username}-{hostname}-{port}",
+            "{username}-{hostname}-{port}-{auth}",
             username = self.username,
             hostname = self.hostname,
-            port = self.port
+            port = self.port,
+            auth = self.auth.to_id_string()
         )
     }
 
@@ -186,6 +251,54 @@ impl Default for SshDest {
 mod tests {
     use super::*;
 
+    mod dest_url {
+        use super::*;
+
+        fn dest(url: &str) -> SshResult<SshDest> {
+            SshDest::from_url(&Url::parse(url).unwrap(), SshAuth::Default)
+        }
+
+        fn invalid_reason(url: &str) -> String {
+            let err = dest(url).unwrap_err();
+            match err.detail().downcast_ref::<SshErrorDetail>() {
+                Some(SshErrorDetail::InvalidUrl { reason, .. }) => reason.clone(),
+                _ => panic!("InvalidUrl expected, got {}", err),
+            }
+        }
+
+        #[test]
+        fn from_url() {
+            let d = dest("ssh://root@example.org:2222").unwrap();
+            assert_eq!("example.org", d.hostname());
+            assert_eq!(2222, d.port());
+            assert_eq!("root", d.username());
+
+            let d = dest("ssh://root@example.org").unwrap();
+            assert_eq!(22, d.port());
+        }
+
+        #[test]
+        fn ipv6_host() {
+            let d = dest("ssh://root@[fe80::1]:2222").unwrap();
+            assert_eq!("fe80::1", d.hostname());
+            assert_eq!("ssh://root@[fe80::1]:2222", d.to_url());
+
+            let mut cmd = CommandBuilder::new("ssh");
+            d.set_dest(true, &mut cmd);
+            assert_eq!("ssh root@fe80::1 -p 2222", cmd.to_string());
+        }
+
+        #[test]
+        fn from_url_invalid() {
+            assert_eq!("scheme must be 'ssh'", invalid_reason("http://example.org"));
+            assert_eq!("missing hostname", invalid_reason("ssh:///path"));
+            assert_eq!(
+                "port must be in range 1-65535",
+                invalid_reason("ssh://example.org:0")
+            );
+        }
+    }
+
     mod auth {
         use super::*;
 
@@ -211,5 +324,49 @@ mod tests {
 
             assert_eq!(r#"{"method":"password","password":"passw0rd"}"#, &s);
         }
+
+        #[test]
+        fn id_string_differs_by_auth() {
+            let dest = |auth| SshDest::new("example.org", 22, "root", auth);
+            let default = dest(SshAuth::Default);
+            let key = dest(SshAuth::PublicKey {
+                identity_file: PathBuf::from("~/.ssh/id_rsa"),
+            });
+            let other_key = dest(SshAuth::PublicKey {
+                identity_file: PathBuf::from("~/.ssh/id_ed25519"),
+            });
+            let password = dest(SshAuth::Password {
+                password: "passw0rd".into(),
+            });
+
+            let ids = [
+                default.to_id_string(),
+                key.to_id_string(),
+                other_key.to_id_string(),
+                password.to_id_string(),
+            ];
+            for (i, a) in ids.iter().enumerate() {
+                for b in ids[i + 1..].iter() {
+                    assert_ne!(a, b);
+                }
+            }
+            assert!(!password.to_id_string().contains("passw0rd"));
+            assert_eq!(key.to_id_string(), key.clone().to_id_string());
+            assert_eq!(
+                "root-example.org-22-keyc84a706284235e56",
+                key.to_id_string()
+            );
+        }
+
+        #[test]
+        fn debug_hides_password() {
+            let a = SshAuth::Password {
+                password: "passw0rd".into(),
+            };
+            let s = format!("{:?}", a);
+
+            assert!(!s.contains("passw0rd"));
+            assert_eq!(r#"Password { password: "***" }"#, &s);
+        }
     }
 }
@@ -10,7 +10,7 @@ use super::*;
 use std::io::{Seek, SeekFrom, Write};
 
 pub use self::config::SshConfig;
-pub use self::dest::{SshAuth, SshDest};
+pub use self::dest::{bracket_ipv6, SshAuth, SshDest};
 use crate::utils::spawn_blocking;
 use futures::lock::{Mutex, MutexGuard};
 use kg_diag::io::fs::create_dir_all;
@@ -36,6 +36,9 @@ pub enum SshErrorDetail {
 
     #[display(fmt = "cannot create master socket directory")]
     SocketDir,
+
+    #[display(fmt = "invalid ssh url '{url}': {reason}")]
+    InvalidUrl { url: String, reason: String },
 }
 
 impl SshErrorDetail {
@@ -91,6 +94,28 @@ impl SshSessionCache {
         self.cache.insert(key, s_ref.clone());
         Ok(s_ref)
     }
+
+    pub fn config(&self) -> &SshConfig {
+        &self.config
+    }
+
+    /// Returns already opened session to `dest`, if cached.
+    pub fn cached(&mut self, dest: &SshDest) -> Option<SshSessionRef> {
+        self.cache.get_mut(&dest.to_id_string()).cloned()
+    }
+
+    /// Stores session to `dest` opened with `open_session`.
+    pub fn insert(&mut self, dest: &SshDest, session: SshSessionRef) {
+        self.cache.insert(dest.to_id_string(), session);
+    }
+}
+
+/// Opens ssh master connection to `dest` without going through the cache, so that
+/// connections to many hosts can be opened concurrently.
+pub async fn open_session(dest: &SshDest, config: &SshConfig) -> SshResult<SshSessionRef> {
+    let mut s = SshSession::new(dest.clone(), config.clone());
+    s.open().await?;
+    Ok(SshSessionRef::new(s))
 }
 
 #[derive(Debug, Clone)]
@@ -111,6 +136,47 @@ impl SshSessionCacheRef {
     }
 }
 
+/// Returns first 16 hex digits of sha256 digest of `data`. Unlike `std` hashers, the result
+/// is stable across Rust releases, so it can be used in session ids and socket file names.
+fn short_hash(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut hex = format!("{:x}", Sha256::digest(data));
+    hex.truncate(16);
+    hex
+}
+
+/// Maximum length of unix socket path, the lowest among supported platforms.
+const SOCKET_PATH_MAX: usize = 104;
+
+/// Length of random suffix ssh appends to master socket path while creating it.
+const SOCKET_TMP_SUFFIX_LEN: usize = 17;
+
+/// Returns master socket path for session `id`. If the path would exceed unix socket path
+/// limit, `id` is truncated and suffixed with its hash, so that it stays unique and readable.
+fn socket_path(socket_dir: &Path, id: &str) -> PathBuf {
+    const EXT: &str = ".sock";
+
+    let dir_len = socket_dir.as_os_str().len() + 1;
+    let max_id_len = SOCKET_PATH_MAX.saturating_sub(dir_len + EXT.len() + SOCKET_TMP_SUFFIX_LEN);
+    if id.len() <= max_id_len {
+        return socket_dir.join(id.to_string() + EXT);
+    }
+
+    let hash = short_hash(id.as_bytes());
+
+    let mut prefix_len = max_id_len.saturating_sub(hash.len() + 1);
+    while !id.is_char_boundary(prefix_len) {
+        prefix_len -= 1;
+    }
+    let name = if prefix_len == 0 {
+        hash
+    } else {
+        format!("{}-{}", &id[..prefix_len], hash)
+    };
+    socket_dir.join(name + EXT)
+}
+
 #[derive(Debug)]
 pub struct SshSession {
     opened: Cell<bool>,
@@ -123,7 +189,7 @@ pub struct SshSession {
 impl SshSession {
     pub fn new(dest: SshDest, config: SshConfig) -> SshSession {
         let id = dest.to_id_string();
-        let socket_path = config.socket_dir().join(id.clone() + ".sock");
+        let socket_path = socket_path(config.socket_dir(), &id);
 
         SshSession {
             opened: Cell::new(false),
@@ -138,6 +204,10 @@ impl SshSession {
         &self.config
     }
 
+    pub fn dest(&self) -> &SshDest {
+        &self.dest
+    }
+
     /// Returns CommandBuilder with default args.
     /// # Arguments
     /// * `include_target` - if `false` target `username@hostname` will not be set.
@@ -204,8 +274,9 @@ impl SshSession {
         }
     }
 
-    #[allow(dead_code)]
-    async fn check(&self) -> SshResult<bool> {
+    /// Checks whether master connection of opened session is still alive. Session with
+    /// dead master is marked closed, so that dropping it does not close a newly opened one.
+    pub async fn check(&self) -> SshResult<bool> {
         if !self.opened.get() {
             return SshErrorDetail::closed();
         }
@@ -223,6 +294,9 @@ impl SshSession {
         let done_rx = spawn_blocking(move || cmd.status().map_err(SshErrorDetail::spawn_err));
 
         let s = done_rx.await.unwrap()?;
+        if !s.success() {
+            self.opened.set(false);
+        }
         Ok(s.success())
     }
 
@@ -231,12 +305,14 @@ impl SshSession {
             return Ok(());
         }
 
+        // called from Drop, must never inherit controlling terminal
         let mut cmd = self
             .ssh_cmd(true)
             .arg("-O")
             .arg("exit")
             .arg("-o")
             .arg("ConnectTimeout=2")
+            .setsid(true)
             .build_sync();
 
         cmd.stdout(Stdio::null()).stderr(Stdio::piped());
@@ -274,12 +350,9 @@ impl SshSession {
             .args(args.iter().map(String::as_str))
             .to_string_with_env();
 
-        let mut ssh_cmd = self
-            .ssh_cmd(true)
-            .arg("-o")
-            .arg("BatchMode=yes")
-            .arg(usr_cmd)
-            .build();
+        let mut ssh_builder = self.ssh_cmd(true);
+        ssh_builder.arg("-o").arg("BatchMode=yes").arg(usr_cmd);
+        let mut ssh_cmd = ssh_builder.build();
 
         let (out_reader, out_writer) = pipe().unwrap();
         let (err_reader, err_writer) = pipe().unwrap();
@@ -289,7 +362,8 @@ impl SshSession {
             .stdout(out_writer)
             .stderr(err_writer);
 
-        log.log_in(format!("{:?}", ssh_cmd).as_bytes())?;
+        // log command without env vars, which may contain password
+        log.log_in(ssh_builder.to_string().as_bytes())?;
 
         let child = SharedChild::spawn(&mut ssh_cmd).map_err(SshErrorDetail::spawn_err)?;
         drop(ssh_cmd);
@@ -341,22 +415,20 @@ impl SshSession {
 
         let _r = in_reader.try_clone().unwrap();
 
-        let mut ssh_cmd = self
-            .ssh_cmd(true)
-            .arg("-o")
-            .arg("BatchMode=yes")
-            .arg(usr_cmd)
-            .build();
+        let mut ssh_builder = self.ssh_cmd(true);
+        ssh_builder.arg("-o").arg("BatchMode=yes").arg(usr_cmd);
+        let mut ssh_cmd = ssh_builder.build();
 
         ssh_cmd
             .stdout(out_writer)
             .stderr(err_writer)
             .stdin(in_reader);
 
-        log.log_in(format!("{:?}", ssh_cmd).as_bytes())?;
+        // log command without env vars, which may contain password
+        log.log_in(ssh_builder.to_string().as_bytes())?;
 
         let mut buf = Cursor::new(Vec::new());
-        prepare_script(script, args, env, cwd, &mut buf)?;
+        prepare_script(script, args, env, cwd, self.config(), &mut buf)?;
         buf.seek(SeekFrom::Start(0)).map_err_to_diag()?;
 
         log.log_in(buf.get_ref().as_slice())?;
@@ -410,6 +482,48 @@ mod tests {
     use op_test_helpers::UnwrapDisplay;
     use tokio::time::Duration;
 
+    #[test]
+    fn socket_path_short() {
+        let dest = SshDest::new("example.org", 22, "root", SshAuth::Default);
+        let session = SshSession::new(dest, SshConfig::default());
+        assert_eq!(
+            format!("{}.sock", session.id),
+            session.socket_path.file_name().unwrap().to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn socket_path_long() {
+        let dir = Path::new("/var/run/opereon/ssh");
+        let long = |name: &str| {
+            let dest = SshDest::new(
+                format!("{}.some-very-long-subdomain.example.org", name),
+                22,
+                "some-very-long-username-of-service-account",
+                SshAuth::Default,
+            );
+            socket_path(dir, &dest.to_id_string())
+        };
+
+        let path1 = long("host1");
+        let path2 = long("host2");
+        assert!(path1.as_os_str().len() + SOCKET_TMP_SUFFIX_LEN <= SOCKET_PATH_MAX);
+        assert!(path1.starts_with(dir));
+        assert!(path1
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("some-very-long-username"));
+        assert_ne!(path1, path2);
+        assert_eq!(path1, long("host1"));
+        // hash is stable, so socket paths are reproducible between builds
+        assert_eq!(
+            dir.join("some-very-long-username-of-service-account-h-e9456e5bf02d93cb.sock"),
+            path1
+        );
+    }
+
     #[test]
     fn cancel_command_test() {
         let auth = SshAuth::PublicKey {
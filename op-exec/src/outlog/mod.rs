@@ -28,6 +28,28 @@ impl OutputLog {
         OutputLog(None)
     }
 
+    /// Returns total size of logged data in bytes. Always `0` for null log.
+    pub fn len_bytes(&self) -> usize {
+        self.0.as_ref().map_or(0, |o| o.lock().buf.len())
+    }
+
+    /// Returns number of logged entries. Always `0` for null log.
+    pub fn entry_count(&self) -> usize {
+        self.0.as_ref().map_or(0, |o| o.lock().entries.len())
+    }
+
+    /// Moves all logged entries out of this log, leaving it empty.
+    /// Entries are returned in logging order. Always empty for null log.
+    pub fn take(&self) -> Vec<(EntryKind, Instant, Vec<u8>)> {
+        match self.0 {
+            Some(ref o) => {
+                let output = std::mem::replace(&mut *o.lock(), Output::new());
+                output.into_entries()
+            }
+            None => Vec::new(),
+        }
+    }
+
     pub fn log_entry(&self, kind: EntryKind, timestamp: Instant, data: &[u8]) -> IoResult<()> {
         if let Some(ref o) = self.0 {
             let mut o = o.lock();
@@ -86,14 +108,18 @@ impl OutputLog {
         self.consume_input(stderr, EntryKind::Out)
     }
 
+    /// Logs every line read from `reader` as raw bytes, output is not required
+    /// to be valid UTF-8.
     fn consume_input<R: Read>(&self, reader: R, kind: EntryKind) -> IoResult<()> {
         let r = BufReader::new(reader);
-        let lines = r.lines();
 
-        for res in lines {
+        for res in r.split(b'\n') {
             match res {
-                Ok(line) => {
-                    self.log_entry_now(kind, line.as_bytes())?;
+                Ok(mut line) => {
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    self.log_entry_now(kind, &line)?;
                 }
                 Err(err) => {
                     // TODO ws what to do with error?
@@ -188,6 +214,19 @@ impl Output {
         self.entries.push(entry);
         Ok(())
     }
+
+    /// Splits buffer into separate entries, consuming the output.
+    fn into_entries(self) -> Vec<(EntryKind, Instant, Vec<u8>)> {
+        let Output { mut buf, entries } = self;
+        let mut res = Vec::with_capacity(entries.len());
+        // split from the end, so that each entry data is moved out only once
+        for e in entries.into_iter().rev() {
+            let data = buf.split_off(e.pos.offset);
+            res.push((e.kind, e.timestamp, data));
+        }
+        res.reverse();
+        res
+    }
 }
 
 impl std::fmt::Display for Output {
@@ -241,4 +280,43 @@ mod tests {
         log.log_status(None).unwrap();
         println!("{}", log);
     }
+
+    #[test]
+    fn size_outlog() {
+        let log = OutputLog::new();
+        assert_eq!(0, log.len_bytes());
+        assert_eq!(0, log.entry_count());
+
+        log.log_out(b"test").unwrap();
+        log.log_status(Some(10)).unwrap();
+        assert_eq!(6, log.len_bytes());
+        assert_eq!(2, log.entry_count());
+
+        let log = OutputLog::null();
+        log.log_out(b"test").unwrap();
+        assert_eq!(0, log.len_bytes());
+        assert_eq!(0, log.entry_count());
+    }
+
+    #[test]
+    fn take_outlog() {
+        let log = OutputLog::new();
+        log.log_command(b"echo 'test'").unwrap();
+        log.log_out(b"test").unwrap();
+        log.log_status(Some(0)).unwrap();
+
+        let entries = log.take();
+        let entries: Vec<_> = entries.into_iter().map(|(k, _, d)| (k, d)).collect();
+        assert_eq!(
+            vec![
+                (EntryKind::Command, b"echo 'test'".to_vec()),
+                (EntryKind::Out, b"test".to_vec()),
+                (EntryKind::Status, b"0".to_vec()),
+            ],
+            entries
+        );
+        assert_eq!(0, log.entry_count());
+        assert_eq!(0, log.len_bytes());
+        assert!(OutputLog::null().take().is_empty());
+    }
 }
@@ -263,12 +263,14 @@ pub struct RsyncCompare {
 }
 
 impl RsyncCompare {
-    pub fn spawn(
+    pub async fn spawn(
         config: &RsyncConfig,
         params: &RsyncParams,
         checksum: bool,
         log: &OutputLog,
     ) -> RsyncResult<RsyncCompare> {
+        params.check_version(config).await?;
+
         let mut rsync_cmd = build_compare_cmd(config, params, checksum);
         let (mut out_reader, out_writer) = pipe().unwrap();
         let (mut err_reader, err_writer) = pipe().unwrap();
@@ -412,7 +414,9 @@ mod tests {
         let mut rt = tokio::runtime::Runtime::new().expect("runtime");
 
         rt.block_on(async move {
-            let cmp = RsyncCompare::spawn(&cfg, &params, false, &log).unwrap();
+            let cmp = RsyncCompare::spawn(&cfg, &params, false, &log)
+                .await
+                .unwrap();
 
             let diffs = cmp.output().await.expect("error");
             eprintln!("diffs = {:#?}", diffs);
@@ -429,7 +433,9 @@ mod tests {
         let mut rt = tokio::runtime::Runtime::new().expect("runtime");
 
         rt.block_on(async move {
-            let cmp = RsyncCompare::spawn(&cfg, &params, true, &log).unwrap();
+            let cmp = RsyncCompare::spawn(&cfg, &params, true, &log)
+                .await
+                .unwrap();
             let child = cmp.child().clone();
 
             tokio::spawn(async move {
@@ -1,3 +1,8 @@
+use super::*;
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct RsyncConfig {
@@ -8,6 +13,30 @@ impl RsyncConfig {
     pub fn rsync_cmd(&self) -> &str {
         &self.rsync_cmd
     }
+
+    /// Returns version of configured rsync binary. Version is detected with `rsync --version`
+    /// once per binary path and cached for the lifetime of the process.
+    pub async fn version(&self) -> RsyncResult<RsyncVersion> {
+        lazy_static! {
+            static ref VERSIONS: Mutex<HashMap<String, RsyncVersion>> = Mutex::new(HashMap::new());
+        }
+
+        // lock guard must not be held across await point
+        let cached = VERSIONS.lock().get(&self.rsync_cmd).copied();
+        if let Some(version) = cached {
+            return Ok(version);
+        }
+
+        let output = tokio::process::Command::new(&self.rsync_cmd)
+            .arg("--version")
+            .output()
+            .await
+            .map_err(RsyncErrorDetail::spawn_err)?;
+        let version = RsyncVersion::parse(&String::from_utf8_lossy(&output.stdout))?;
+
+        VERSIONS.lock().insert(self.rsync_cmd.clone(), version);
+        Ok(version)
+    }
 }
 
 impl Default for RsyncConfig {
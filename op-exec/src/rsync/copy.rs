@@ -224,12 +224,14 @@ pub struct RsyncCopy {
 }
 
 impl RsyncCopy {
-    pub fn spawn(
+    pub async fn spawn(
         config: &RsyncConfig,
         params: &RsyncParams,
         progress_sender: mpsc::UnboundedSender<ProgressInfo>,
         log: &OutputLog,
     ) -> RsyncResult<RsyncCopy> {
+        params.check_version(config).await?;
+
         let (out_reader, out_writer) = pipe().unwrap();
         let (err_reader, err_writer) = pipe().unwrap();
 
@@ -323,7 +325,9 @@ mod tests {
                 }
             });
 
-            let copy = RsyncCopy::spawn(&cfg, &params, tx, &log).expect("error");
+            let copy = RsyncCopy::spawn(&cfg, &params, tx, &log)
+                .await
+                .expect("error");
 
             let _res = copy.wait().await.expect("Error");
             println!("{}", log)
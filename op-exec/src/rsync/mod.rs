@@ -8,6 +8,7 @@ use super::*;
 pub use self::config::RsyncConfig;
 pub use self::rsync::compare::{DiffInfo, RsyncCompare};
 pub use self::rsync::copy::RsyncCopy;
+use crate::command::ssh::{bracket_ipv6, SshSession};
 use std::process::ExitStatus;
 
 pub mod compare;
@@ -32,6 +33,17 @@ pub enum RsyncErrorDetail {
 
     #[display(fmt = "rsync process terminated")]
     RsyncTerminated,
+
+    #[display(fmt = "cannot detect rsync version from '{output}'")]
+    VersionDetect { output: String },
+
+    #[display(
+        fmt = "rsync {version} does not support --chown, version {required} or above is required"
+    )]
+    ChownUnsupported {
+        version: RsyncVersion,
+        required: RsyncVersion,
+    },
 }
 
 impl RsyncErrorDetail {
@@ -74,6 +86,69 @@ impl RsyncParseErrorDetail {
     }
 }
 
+/// Version of rsync binary, as reported by `rsync --version`.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[display(fmt = "{major}.{minor}.{patch}")]
+pub struct RsyncVersion {
+    major: u32,
+    minor: u32,
+    patch: u32,
+}
+
+impl RsyncVersion {
+    /// First version supporting `--chown` option.
+    pub const CHOWN_MIN: RsyncVersion = RsyncVersion::new(3, 1, 0);
+
+    pub const fn new(major: u32, minor: u32, patch: u32) -> RsyncVersion {
+        RsyncVersion {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses version from `rsync --version` output, eg. `rsync  version 3.1.3  protocol version 31`.
+    pub fn parse(output: &str) -> RsyncResult<RsyncVersion> {
+        let err = || -> RsyncError {
+            RsyncErrorDetail::VersionDetect {
+                output: output.lines().next().unwrap_or_default().to_string(),
+            }
+            .into()
+        };
+
+        let line = output.lines().next().ok_or_else(err)?;
+        let mut words = line.split_whitespace();
+        if words.next() != Some("rsync") || words.next() != Some("version") {
+            return Err(err());
+        }
+        let version = words.next().ok_or_else(err)?;
+        let version = version.trim_start_matches('v');
+
+        let mut parts = version.split('.').map(|p| {
+            // skip suffixes like `3.2.4dev`
+            let digits = p.find(|c: char| !c.is_ascii_digit()).unwrap_or(p.len());
+            p[..digits].parse::<u32>().ok()
+        });
+        let major = parts.next().flatten().ok_or_else(err)?;
+        let minor = parts.next().flatten().ok_or_else(err)?;
+        let patch = parts.next().flatten().unwrap_or(0);
+
+        Ok(RsyncVersion::new(major, minor, patch))
+    }
+
+    pub fn major(&self) -> u32 {
+        self.major
+    }
+
+    pub fn minor(&self) -> u32 {
+        self.minor
+    }
+
+    pub fn patch(&self) -> u32 {
+        self.patch
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RsyncParams {
     current_dir: PathBuf,
@@ -134,6 +209,11 @@ impl RsyncParams {
         self
     }
 
+    /// Destination hostname, `None` for local destination.
+    pub fn dst_host(&self) -> Option<&str> {
+        self.dst_hostname.as_ref().map(|h| h.as_str())
+    }
+
     pub fn chmod<S: Into<String>>(&mut self, chmod: S) -> &mut RsyncParams {
         self.chmod = Some(chmod.into());
         self
@@ -151,6 +231,31 @@ impl RsyncParams {
         self
     }
 
+    /// Sets destination to the host of ssh `session`. Remote shell reuses session master
+    /// connection and passes destination port explicitly.
+    pub fn dst_ssh(&mut self, session: &SshSession) -> &mut RsyncParams {
+        let dest = session.dest();
+        self.dst_username(dest.username())
+            .dst_hostname(dest.hostname())
+            .remote_shell(session.remote_shell_cmd())
+    }
+
+    /// Checks that configured rsync binary supports all requested options.
+    /// Rsync version is only detected when `--chown` is used.
+    async fn check_version(&self, config: &RsyncConfig) -> RsyncResult<()> {
+        if self.chown.is_some() {
+            let version = config.version().await?;
+            if version < RsyncVersion::CHOWN_MIN {
+                return Err(RsyncErrorDetail::ChownUnsupported {
+                    version,
+                    required: RsyncVersion::CHOWN_MIN,
+                }
+                .into());
+            }
+        }
+        Ok(())
+    }
+
     fn to_cmd(&self, config: &RsyncConfig) -> Command {
         fn print_host(hostname: Option<&String>, username: Option<&String>, out: &mut String) {
             use std::fmt::Write;
@@ -160,10 +265,12 @@ impl RsyncParams {
                     out,
                     "{username}@{hostname}:",
                     username = username,
-                    hostname = hostname
+                    hostname = bracket_ipv6(hostname)
                 )
                 .unwrap(),
-                (Some(hostname), None) => write!(out, "{hostname}:", hostname = hostname).unwrap(),
+                (Some(hostname), None) => {
+                    write!(out, "{hostname}:", hostname = bracket_ipv6(hostname)).unwrap()
+                }
                 _ => {}
             }
         }
@@ -212,3 +319,67 @@ impl RsyncParams {
         cmd
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_parse() {
+        let out = "rsync  version 3.1.3  protocol version 31\nCopyright (C) 1996-2018 by Andrew Tridgell\n";
+        assert_eq!(
+            RsyncVersion::new(3, 1, 3),
+            RsyncVersion::parse(out).unwrap()
+        );
+
+        let out = "rsync  version v3.2.4dev  protocol version 31";
+        assert_eq!(
+            RsyncVersion::new(3, 2, 4),
+            RsyncVersion::parse(out).unwrap()
+        );
+
+        let out = "rsync  version 3.0  protocol version 30";
+        let version = RsyncVersion::parse(out).unwrap();
+        assert_eq!(RsyncVersion::new(3, 0, 0), version);
+        assert!(version < RsyncVersion::CHOWN_MIN);
+        assert_eq!("3.0.0", version.to_string());
+    }
+
+    #[test]
+    fn version_parse_err() {
+        let res = RsyncVersion::parse("openrsync: protocol version 29");
+        let err = res.unwrap_err();
+        match err.detail().downcast_ref::<RsyncErrorDetail>() {
+            Some(RsyncErrorDetail::VersionDetect { output }) => {
+                assert_eq!("openrsync: protocol version 29", output)
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn to_cmd_ipv6_host() {
+        let mut params = RsyncParams::new("./", "src/", "/dst");
+        params.dst_username("root").dst_hostname("fe80::1");
+
+        let cmd = format!("{:?}", params.to_cmd(&RsyncConfig::default()));
+        assert!(cmd.contains("\"root@[fe80::1]:/dst\""), "{}", cmd);
+    }
+
+    #[test]
+    fn to_cmd_ssh_port() {
+        use crate::command::ssh::{SshAuth, SshConfig, SshDest};
+
+        let dest = SshDest::new("zeus", 8820, "root", SshAuth::Default);
+        let session = SshSession::new(dest, SshConfig::default());
+
+        let ssh_cmd = session.remote_shell_cmd();
+        assert!(ssh_cmd.contains(" -p 8820"), "{}", ssh_cmd);
+
+        let mut params = RsyncParams::new("./", "src/", "/dst");
+        params.dst_ssh(&session);
+        let cmd = format!("{:?}", params.to_cmd(&RsyncConfig::default()));
+        assert!(cmd.contains("\"root@zeus:/dst\""), "{}", cmd);
+        assert!(cmd.contains(&format!("\"-e\" {:?}", ssh_cmd)), "{}", cmd);
+    }
+}
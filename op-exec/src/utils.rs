@@ -85,20 +85,14 @@ fn read_line_internal<R: AsyncBufRead + ?Sized>(
     read: &mut usize,
 ) -> Poll<std::io::Result<usize>> {
     let ret = ready!(read_until_internal(reader, cx, bytes, read));
-    if std::str::from_utf8(&bytes).is_err() {
-        Poll::Ready(ret.and_then(|_| {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                "stream did not contain valid UTF-8",
-            ))
-        }))
-    } else {
-        debug_assert!(buf.is_empty());
-        debug_assert_eq!(*read, 0);
-        // Safety: `bytes` is a valid UTF-8 because `str::from_utf8` returned `Ok`.
-        std::mem::swap(unsafe { buf.as_mut_vec() }, bytes);
-        Poll::Ready(ret)
+    debug_assert!(buf.is_empty());
+    debug_assert_eq!(*read, 0);
+    // invalid UTF-8 sequences are replaced, so that binary output does not break reading
+    match String::from_utf8(std::mem::replace(bytes, Vec::new())) {
+        Ok(s) => *buf = s,
+        Err(err) => *buf = String::from_utf8_lossy(err.as_bytes()).into_owned(),
     }
+    Poll::Ready(ret)
 }
 
 fn read_until_internal<R: AsyncBufRead + ?Sized>(
@@ -1,11 +1,73 @@
 use crate::Level;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, suitable for log ingestion
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Text
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyslogFacility {
+    User,
+    Daemon,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+}
+
+impl Default for SyslogFacility {
+    fn default() -> Self {
+        SyslogFacility::Daemon
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyslogConfig {
+    facility: SyslogFacility,
+    /// Remote syslog server address, eg. `udp://10.0.0.1:514`. Local syslog socket is used if not set.
+    remote: Option<String>,
+}
+
+impl SyslogConfig {
+    pub fn facility(&self) -> SyslogFacility {
+        self.facility
+    }
+
+    pub fn remote(&self) -> Option<&str> {
+        self.remote.as_deref()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct LogConfig {
     level: Level,
     log_path: PathBuf,
+    /// Per-target level directives, eg. `ssh = "trace"`
+    targets: BTreeMap<String, Level>,
+    format: LogFormat,
+    syslog: Option<SyslogConfig>,
+    /// Size in bytes after which log file is rotated, no rotation if not set
+    max_file_size: Option<u64>,
+    /// Number of rotated log files to keep
+    max_files: usize,
 }
 
 impl LogConfig {
@@ -16,6 +78,26 @@ impl LogConfig {
     pub fn log_path(&self) -> &Path {
         &self.log_path
     }
+
+    pub fn targets(&self) -> &BTreeMap<String, Level> {
+        &self.targets
+    }
+
+    pub fn format(&self) -> LogFormat {
+        self.format
+    }
+
+    pub fn syslog(&self) -> Option<&SyslogConfig> {
+        self.syslog.as_ref()
+    }
+
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size
+    }
+
+    pub fn max_files(&self) -> usize {
+        self.max_files
+    }
 }
 
 impl Default for LogConfig {
@@ -23,6 +105,11 @@ impl Default for LogConfig {
         LogConfig {
             level: Level::Info,
             log_path: PathBuf::from("/var/log/opereon/opereon.log"),
+            targets: BTreeMap::new(),
+            format: LogFormat::default(),
+            syslog: None,
+            max_file_size: None,
+            max_files: 5,
         }
     }
 }
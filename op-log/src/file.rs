@@ -1,7 +1,10 @@
-use crate::Level;
-use slog::{o, Discard, Drain, Never, Record, Serializer, KV};
+use crate::config::{LogConfig, LogFormat};
+use crate::filter::TargetFilter;
+use crate::json::JsonDrain;
+use crate::rotate::RotatingFile;
+use crate::CRITICAL_KEY;
+use slog::{o, Discard, Drain, Never, Record, SendSyncRefUnwindSafeDrain, Serializer, KV};
 use std::fmt::Debug;
-use std::fs::OpenOptions;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
@@ -52,6 +55,7 @@ impl KV for FileSpan {
 struct FileEvent {
     kvs: Vec<(&'static str, String)>,
     message: Option<String>,
+    critical: bool,
 }
 
 impl FileEvent {
@@ -59,6 +63,7 @@ impl FileEvent {
         let mut evt = FileEvent {
             kvs: vec![],
             message: None,
+            critical: false,
         };
         event.record(&mut evt);
         evt
@@ -66,6 +71,14 @@ impl FileEvent {
 }
 
 impl Visit for FileEvent {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == CRITICAL_KEY {
+            self.critical = value
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+
     fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
         let val = format!("{:?}", value);
         if field.name() == "message" {
@@ -86,22 +99,35 @@ impl KV for FileEvent {
 }
 
 pub struct FileLayer {
-    level: Level,
+    filter: TargetFilter,
     file_path: PathBuf,
+    format: LogFormat,
+    max_file_size: Option<u64>,
+    max_files: usize,
     root_logger: SlogLogger,
 }
 
 impl FileLayer {
-    pub fn new(level: Level, file_path: &Path) -> Self {
+    pub fn new(filter: TargetFilter, cfg: &LogConfig) -> Self {
         FileLayer {
-            level,
-            file_path: file_path.to_path_buf(),
+            filter,
+            file_path: cfg.log_path().to_path_buf(),
+            format: cfg.format(),
+            max_file_size: cfg.max_file_size(),
+            max_files: cfg.max_files(),
             root_logger: SlogLogger(slog::Logger::root(Discard, o!())),
         }
     }
 
     pub fn init(&mut self) {
-        let file_drain = build_file_drain(self.file_path.clone(), self.level.into());
+        // events are filtered per target in `on_event`, drain must pass the most verbose level
+        let file_drain = build_file_drain(
+            self.file_path.clone(),
+            self.filter.max_level().into(),
+            self.format,
+            self.max_file_size,
+            self.max_files,
+        );
 
         self.root_logger = SlogLogger(slog::Logger::root(file_drain, o!()))
     }
@@ -128,6 +154,11 @@ where
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if !self.filter.enabled(meta.target(), meta.level().into()) {
+            return;
+        }
+
         let current = ctx.current_span();
 
         let l = if let Some(id) = current.id() {
@@ -142,14 +173,30 @@ where
 
         let msg = evt.message.take().unwrap_or("".into());
 
-        let module = event.metadata().module_path().unwrap_or("");
+        let module = meta.module_path().unwrap_or("");
+        let target = meta.target();
 
-        match *event.metadata().level() {
-            tracing::Level::TRACE => slog::trace!(l, "{}", msg; evt, "module"=> module),
-            tracing::Level::DEBUG => slog::debug!(l, "{}", msg; evt, "module"=>module),
-            tracing::Level::INFO => slog::info!(l, "{}", msg; evt, "module"=>module),
-            tracing::Level::WARN => slog::warn!(l, "{}", msg; evt, "module"=>module),
-            tracing::Level::ERROR => slog::error!(l, "{}", msg; evt, "module"=>module),
+        if evt.critical {
+            slog::crit!(l, "{}", msg; evt, "module"=>module, "target"=>target);
+            return;
+        }
+
+        match *meta.level() {
+            tracing::Level::TRACE => {
+                slog::trace!(l, "{}", msg; evt, "module"=>module, "target"=>target)
+            }
+            tracing::Level::DEBUG => {
+                slog::debug!(l, "{}", msg; evt, "module"=>module, "target"=>target)
+            }
+            tracing::Level::INFO => {
+                slog::info!(l, "{}", msg; evt, "module"=>module, "target"=>target)
+            }
+            tracing::Level::WARN => {
+                slog::warn!(l, "{}", msg; evt, "module"=>module, "target"=>target)
+            }
+            tracing::Level::ERROR => {
+                slog::error!(l, "{}", msg; evt, "module"=>module, "target"=>target)
+            }
         }
     }
 }
@@ -157,21 +204,29 @@ where
 pub fn build_file_drain<P: AsRef<Path>>(
     log_path: P,
     level: slog::Level,
-) -> impl Drain<Ok = (), Err = Never> {
+    format: LogFormat,
+    max_file_size: Option<u64>,
+    max_files: usize,
+) -> Box<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = Never>> {
     if let Some(log_dir) = log_path.as_ref().parent() {
         std::fs::create_dir_all(log_dir).expect("Cannot create log dir");
     }
 
-    let mut open_opts = OpenOptions::new();
-
-    open_opts.create(true).append(true);
-
-    let log_file = open_opts.open(log_path).expect("Cannot open log file");
+    let log_file = RotatingFile::open(log_path.as_ref(), max_file_size, max_files)
+        .expect("Cannot open log file");
 
-    let drain = slog_bunyan::default(log_file);
+    match format {
+        LogFormat::Text => {
+            let drain = slog_bunyan::default(log_file);
 
-    //    let decorator = slog_term::PlainSyncDecorator::new(log_file.try_clone().unwrap());
-    //    let drain = slog_term::FullFormat::new(decorator).build();
-    let drain = slog::LevelFilter::new(Mutex::new(drain), level);
-    drain.fuse()
+            //    let decorator = slog_term::PlainSyncDecorator::new(log_file.try_clone().unwrap());
+            //    let drain = slog_term::FullFormat::new(decorator).build();
+            let drain = slog::LevelFilter::new(Mutex::new(drain), level);
+            Box::new(drain.fuse())
+        }
+        LogFormat::Json => {
+            let drain = slog::LevelFilter::new(JsonDrain::new(log_file), level);
+            Box::new(drain.fuse())
+        }
+    }
 }
@@ -0,0 +1,84 @@
+use crate::Level;
+use std::collections::BTreeMap;
+
+/// Per-target level filter. Directive matches event target if it equals any contiguous
+/// sequence of target path segments, eg. `ssh` matches `op_exec::command::ssh::config`.
+/// The longest matching directive wins, targets without matching directive use default level.
+#[derive(Debug, Clone)]
+pub struct TargetFilter {
+    level: Level,
+    directives: Vec<(Vec<String>, Level)>,
+}
+
+impl TargetFilter {
+    pub fn new(level: Level, targets: &BTreeMap<String, Level>) -> TargetFilter {
+        let mut directives: Vec<(Vec<String>, Level)> = targets
+            .iter()
+            .map(|(t, l)| (t.split("::").map(|s| s.to_string()).collect(), *l))
+            .collect();
+        // most specific directives first
+        directives.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        TargetFilter { level, directives }
+    }
+
+    /// Returns level threshold for `target`.
+    pub fn level_for(&self, target: &str) -> Level {
+        let segments: Vec<&str> = target.split("::").collect();
+        for (d, level) in self.directives.iter() {
+            if d.len() <= segments.len()
+                && segments
+                    .windows(d.len())
+                    .any(|w| w.iter().zip(d.iter()).all(|(a, b)| *a == b))
+            {
+                return *level;
+            }
+        }
+        self.level
+    }
+
+    pub fn enabled(&self, target: &str, level: Level) -> bool {
+        level as usize >= self.level_for(target) as usize
+    }
+
+    /// Returns most verbose level enabled for any target.
+    pub fn max_level(&self) -> Level {
+        self.directives
+            .iter()
+            .map(|(_, l)| *l)
+            .fold(
+                self.level,
+                |a, b| if (b as usize) < (a as usize) { b } else { a },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> TargetFilter {
+        let mut targets = BTreeMap::new();
+        targets.insert("ssh".to_string(), Level::Trace);
+        targets.insert("op_exec".to_string(), Level::Warn);
+        TargetFilter::new(Level::Info, &targets)
+    }
+
+    #[test]
+    fn segment_match() {
+        let f = filter();
+        assert_eq!(Level::Trace, f.level_for("op_exec::command::ssh"));
+        assert_eq!(Level::Trace, f.level_for("op_exec::command::ssh::config"));
+        assert_eq!(Level::Warn, f.level_for("op_exec::command::local"));
+        assert_eq!(Level::Info, f.level_for("op_core::ops::model"));
+        assert_eq!(Level::Info, f.level_for("op_core::sshd"));
+    }
+
+    #[test]
+    fn enabled() {
+        let f = filter();
+        assert!(f.enabled("op_exec::command::ssh", Level::Trace));
+        assert!(!f.enabled("op_core::ops::model", Level::Debug));
+        assert!(f.enabled("op_core::ops::model", Level::Info));
+        assert_eq!(Level::Trace, f.max_level());
+    }
+}
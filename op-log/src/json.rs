@@ -0,0 +1,109 @@
+use crate::Level;
+use serde_json::{Map, Value};
+use slog::{Drain, Key, OwnedKVList, Record, Serializer, KV};
+use std::fmt::Arguments;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// Keys emitted by `FileLayer` for every event, written as top-level JSON properties.
+const TOP_LEVEL_KEYS: &[&str] = &["target", "module"];
+
+#[derive(Default)]
+struct JsonSerializer {
+    map: Map<String, Value>,
+}
+
+impl Serializer for JsonSerializer {
+    fn emit_arguments(&mut self, key: Key, val: &Arguments<'_>) -> slog::Result {
+        self.map
+            .insert(key.to_string(), Value::String(val.to_string()));
+        Ok(())
+    }
+}
+
+/// Drain writing each record as a single line JSON object with `timestamp`, `level`, `target`,
+/// `module`, `message`, span fields under `span` and event fields under `fields`.
+pub struct JsonDrain<W: Write> {
+    out: Mutex<W>,
+}
+
+impl<W: Write> JsonDrain<W> {
+    pub fn new(out: W) -> Self {
+        JsonDrain {
+            out: Mutex::new(out),
+        }
+    }
+}
+
+impl<W: Write> Drain for JsonDrain<W> {
+    type Ok = ();
+    type Err = std::io::Error;
+
+    fn log(&self, record: &Record<'_>, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        let mut span = JsonSerializer::default();
+        values.serialize(record, &mut span)?;
+
+        let mut fields = JsonSerializer::default();
+        record.kv().serialize(record, &mut fields)?;
+
+        let level: Level = record.level().into();
+
+        let mut obj = Map::new();
+        obj.insert(
+            "timestamp".into(),
+            Value::String(chrono::Utc::now().to_rfc3339()),
+        );
+        obj.insert("level".into(), serde_json::to_value(level)?);
+        for key in TOP_LEVEL_KEYS {
+            if let Some(val) = fields.map.remove(*key) {
+                obj.insert(key.to_string(), val);
+            }
+        }
+        obj.insert("message".into(), Value::String(record.msg().to_string()));
+        obj.insert("span".into(), Value::Object(span.map));
+        obj.insert("fields".into(), Value::Object(fields.map));
+
+        let mut out = self.out.lock().unwrap();
+        serde_json::to_writer(&mut *out, &obj)?;
+        out.write_all(b"\n")?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::o;
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buf {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_line() {
+        let buf = Buf::default();
+        let logger = slog::Logger::root(JsonDrain::new(buf.clone()).fuse(), o!("span_id" => "1"));
+
+        slog::crit!(logger, "disk failure"; "target" => "op_exec", "path" => "/dev/sda");
+
+        let out = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        assert!(out.ends_with('\n'));
+        let val: Value = serde_json::from_str(&out).unwrap();
+        assert_eq!("critical", val["level"]);
+        assert_eq!("op_exec", val["target"]);
+        assert_eq!("disk failure", val["message"]);
+        assert_eq!("1", val["span"]["span_id"]);
+        assert_eq!("/dev/sda", val["fields"]["path"]);
+        assert!(val["timestamp"].is_string());
+    }
+}
@@ -3,6 +3,8 @@ extern crate serde_derive;
 
 use crate::config::LogConfig;
 use crate::file::FileLayer;
+use crate::filter::TargetFilter;
+use crate::syslog::SyslogLayer;
 use crate::term::TermLayer;
 use std::fmt::Debug;
 
@@ -10,8 +12,24 @@ use tracing_subscriber::layer::SubscriberExt;
 
 pub mod config;
 mod file;
+mod filter;
+mod json;
+mod rotate;
+mod syslog;
 mod term;
 
+/// Event field marking an error event as critical, eg. `error!(critical = true, "...")`.
+/// Tracing has no critical level, so it is carried as a field.
+pub const CRITICAL_KEY: &str = "critical";
+
+/// Emits error event tagged as critical, rendered as `CRIT` by all op-log layers.
+#[macro_export]
+macro_rules! critical {
+    ($($arg:tt)+) => {
+        ::tracing::error!(critical = true, $($arg)+)
+    };
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Level {
@@ -36,6 +54,20 @@ impl std::fmt::Display for Level {
     }
 }
 
+impl Level {
+    /// Short upper-case tag used when rendering events.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+            Level::Critical => "CRIT",
+        }
+    }
+}
+
 impl PartialEq for Level {
     #[inline]
     fn eq(&self, other: &Level) -> bool {
@@ -56,6 +88,33 @@ impl Into<slog::Level> for Level {
     }
 }
 
+impl From<slog::Level> for Level {
+    fn from(level: slog::Level) -> Self {
+        match level {
+            slog::Level::Trace => Level::Trace,
+            slog::Level::Debug => Level::Debug,
+            slog::Level::Info => Level::Info,
+            slog::Level::Warning => Level::Warn,
+            slog::Level::Error => Level::Error,
+            slog::Level::Critical => Level::Critical,
+        }
+    }
+}
+
+impl From<&tracing::Level> for Level {
+    fn from(level: &tracing::Level) -> Self {
+        match *level {
+            tracing::Level::TRACE => Level::Trace,
+            tracing::Level::DEBUG => Level::Debug,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::WARN => Level::Warn,
+            _ => Level::Error,
+        }
+    }
+}
+
+/// Note that tracing has no critical level, `Level::Critical` maps to `ERROR`
+/// and is distinguished by the `CRITICAL_KEY` event field.
 impl Into<tracing::Level> for Level {
     fn into(self) -> tracing::Level {
         match self {
@@ -69,17 +128,18 @@ impl Into<tracing::Level> for Level {
     }
 }
 pub fn init_tracing(verbosity: u8, cfg: &LogConfig) {
-    let mut file_layer = FileLayer::new(cfg.level(), cfg.log_path());
+    let filter = TargetFilter::new(cfg.level(), cfg.targets());
+    let mut file_layer = FileLayer::new(filter.clone(), cfg);
 
     file_layer.init();
 
-    let level: tracing::Level = cfg.level().into();
     let subscriber = tracing_subscriber::registry()
         // tracing_subscriber::fmt()
         // .with_max_level(level)
         // .finish()
-        .with(TermLayer::new(verbosity))
-        .with(file_layer);
+        .with(TermLayer::new(verbosity, filter.clone()))
+        .with(file_layer)
+        .with(cfg.syslog().map(|c| SyslogLayer::new(c, filter)));
 
     tracing::subscriber::set_global_default(subscriber).unwrap()
 }
@@ -0,0 +1,148 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Log file writer rolling the active file to `<path>.1`, `<path>.2`, etc. once it exceeds
+/// `max_size` bytes, keeping at most `max_files` rolled files.
+///
+/// Rotation happens only on record boundaries (after a newline has been written), so a single
+/// record is never split between files. Writer is expected to be used behind a lock, as drains do.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: Option<u64>,
+    max_files: usize,
+    file: File,
+    size: u64,
+    at_line_start: bool,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, max_size: Option<u64>, max_files: usize) -> std::io::Result<Self> {
+        let file = open_log_file(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            max_size,
+            max_files,
+            file,
+            size,
+            at_line_start: true,
+        })
+    }
+
+    fn rolled_path(&self, index: usize) -> PathBuf {
+        let mut p = self.path.clone().into_os_string();
+        p.push(format!(".{}", index));
+        PathBuf::from(p)
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rolled_path(self.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for i in (1..self.max_files).rev() {
+                let from = self.rolled_path(i);
+                if from.exists() {
+                    std::fs::rename(&from, self.rolled_path(i + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rolled_path(1))?;
+        }
+
+        self.file = open_log_file(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn should_rotate(&self) -> bool {
+        match self.max_size {
+            Some(max_size) => self.at_line_start && self.size >= max_size,
+            None => false,
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.should_rotate() {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.size += n as u64;
+        self.at_line_start = buf[n - 1] == b'\n';
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("op-log-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rotate_by_size() {
+        let dir = tmp_dir("rotate");
+        let path = dir.join("op.log");
+        let mut f = RotatingFile::open(&path, Some(5), 2).unwrap();
+
+        for i in 0..4 {
+            // record split between writes must stay in one file
+            write!(f, "record-").unwrap();
+            writeln!(f, "{}", i).unwrap();
+        }
+        f.flush().unwrap();
+
+        assert_eq!("record-3\n", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(
+            "record-2\n",
+            std::fs::read_to_string(dir.join("op.log.1")).unwrap()
+        );
+        assert_eq!(
+            "record-1\n",
+            std::fs::read_to_string(dir.join("op.log.2")).unwrap()
+        );
+        assert!(!dir.join("op.log.3").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_rotation_without_max_size() {
+        let dir = tmp_dir("no-rotate");
+        let path = dir.join("op.log");
+        let mut f = RotatingFile::open(&path, None, 2).unwrap();
+
+        for i in 0..4 {
+            writeln!(f, "record-{}", i).unwrap();
+        }
+        f.flush().unwrap();
+
+        assert_eq!(36, std::fs::metadata(&path).unwrap().len());
+        assert!(!dir.join("op.log.1").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use crate::config::{SyslogConfig, SyslogFacility};
+use crate::filter::TargetFilter;
+use crate::{Level, CRITICAL_KEY};
+use std::fmt::{Debug, Write};
+use std::net::UdpSocket;
+use std::os::unix::net::UnixDatagram;
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+const SYSLOG_SOCKET_PATHS: &[&str] = &["/dev/log", "/var/run/syslog", "/var/run/log"];
+const SYSLOG_TAG: &str = "opereon";
+
+/// Syslog severities as defined in RFC 5424.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+    Debug = 7,
+}
+
+impl Into<Severity> for Level {
+    fn into(self) -> Severity {
+        match self {
+            Level::Trace => Severity::Debug,
+            Level::Debug => Severity::Debug,
+            Level::Info => Severity::Info,
+            Level::Warn => Severity::Warning,
+            Level::Error => Severity::Error,
+            Level::Critical => Severity::Critical,
+        }
+    }
+}
+
+impl SyslogFacility {
+    fn code(self) -> u8 {
+        match self {
+            SyslogFacility::User => 1,
+            SyslogFacility::Daemon => 3,
+            SyslogFacility::Local0 => 16,
+            SyslogFacility::Local1 => 17,
+            SyslogFacility::Local2 => 18,
+            SyslogFacility::Local3 => 19,
+            SyslogFacility::Local4 => 20,
+            SyslogFacility::Local5 => 21,
+            SyslogFacility::Local6 => 22,
+            SyslogFacility::Local7 => 23,
+        }
+    }
+}
+
+enum SyslogSocket {
+    Unix(UnixDatagram),
+    Udp(UdpSocket),
+}
+
+impl SyslogSocket {
+    fn connect(cfg: &SyslogConfig) -> std::io::Result<SyslogSocket> {
+        match cfg.remote() {
+            Some(remote) => {
+                let addr = remote.strip_prefix("udp://").ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        format!("unsupported syslog address '{}'", remote),
+                    )
+                })?;
+                let socket = UdpSocket::bind("0.0.0.0:0")?;
+                socket.connect(addr)?;
+                Ok(SyslogSocket::Udp(socket))
+            }
+            None => {
+                let socket = UnixDatagram::unbound()?;
+                let mut err = None;
+                for path in SYSLOG_SOCKET_PATHS {
+                    match socket.connect(path) {
+                        Ok(()) => return Ok(SyslogSocket::Unix(socket)),
+                        Err(e) => err = Some(e),
+                    }
+                }
+                Err(err.unwrap())
+            }
+        }
+    }
+
+    fn send(&self, msg: &[u8]) -> std::io::Result<usize> {
+        match *self {
+            SyslogSocket::Unix(ref s) => s.send(msg),
+            SyslogSocket::Udp(ref s) => s.send(msg),
+        }
+    }
+}
+
+struct SyslogEvent {
+    message: String,
+    kvs: String,
+    critical: bool,
+}
+
+impl SyslogEvent {
+    fn new(event: &Event<'_>) -> Self {
+        let mut evt = SyslogEvent {
+            message: String::new(),
+            kvs: String::new(),
+            critical: false,
+        };
+        event.record(&mut evt);
+        evt
+    }
+}
+
+impl Visit for SyslogEvent {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == CRITICAL_KEY {
+            self.critical = value
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.kvs, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
+/// Layer sending events to local syslog daemon or remote syslog server over UDP.
+/// If the connection cannot be established, a warning is printed and events are dropped.
+pub struct SyslogLayer {
+    filter: TargetFilter,
+    facility: SyslogFacility,
+    socket: Option<Mutex<SyslogSocket>>,
+}
+
+impl SyslogLayer {
+    pub fn new(cfg: &SyslogConfig, filter: TargetFilter) -> Self {
+        let socket = match SyslogSocket::connect(cfg) {
+            Ok(socket) => Some(Mutex::new(socket)),
+            Err(err) => {
+                eprintln!("Cannot connect to syslog, syslog logging disabled: {}", err);
+                None
+            }
+        };
+
+        SyslogLayer {
+            filter,
+            facility: cfg.facility(),
+            socket,
+        }
+    }
+
+    fn format(&self, level: Level, target: &str, evt: &SyslogEvent) -> String {
+        let severity: Severity = level.into();
+        let pri = self.facility.code() as u32 * 8 + severity as u32;
+        format!(
+            "<{}>{}[{}]: {}: {}{}",
+            pri,
+            SYSLOG_TAG,
+            std::process::id(),
+            target,
+            evt.message,
+            evt.kvs
+        )
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SyslogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let socket = match self.socket {
+            Some(ref socket) => socket,
+            None => return,
+        };
+
+        let meta = event.metadata();
+        let mut level: Level = meta.level().into();
+        if !self.filter.enabled(meta.target(), level) {
+            return;
+        }
+
+        let evt = SyslogEvent::new(event);
+        if evt.critical {
+            level = Level::Critical;
+        }
+
+        let msg = self.format(level, meta.target(), &evt);
+        // logging must never fail the caller, lost syslog messages are ignored
+        let _ = socket.lock().unwrap().send(msg.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn severity_mapping() {
+        let s: Severity = Level::Critical.into();
+        assert_eq!(Severity::Critical, s);
+        let s: Severity = Level::Trace.into();
+        assert_eq!(Severity::Debug, s);
+    }
+
+    #[test]
+    fn message_priority() {
+        let layer = SyslogLayer {
+            filter: TargetFilter::new(Level::Info, &BTreeMap::new()),
+            facility: SyslogFacility::Local0,
+            socket: None,
+        };
+        let evt = SyslogEvent {
+            message: "disk failure".into(),
+            kvs: " path=\"/dev/sda\"".into(),
+            critical: true,
+        };
+        let msg = layer.format(Level::Critical, "op_exec", &evt);
+        let expected = format!(
+            "<130>opereon[{}]: op_exec: disk failure path=\"/dev/sda\"",
+            std::process::id()
+        );
+        assert_eq!(expected, msg);
+    }
+}
@@ -1,10 +1,14 @@
+use crate::filter::TargetFilter;
+use crate::CRITICAL_KEY;
 use colored::Colorize;
-use std::fmt::Debug;
+use std::fmt::{Debug, Write};
 use tracing::field::Field;
 
 use tracing::span::{Attributes, Record};
 use tracing::{Event, Id, Level, Span, Subscriber};
 use tracing_subscriber::field::Visit;
+use tracing_subscriber::fmt::format::{DefaultFields, Format, FormatEvent, FormatFields};
+use tracing_subscriber::fmt::{FmtContext, FmtSpan};
 use tracing_subscriber::layer::Context;
 use tracing_subscriber::{registry, Layer};
 
@@ -48,17 +52,107 @@ impl<'a> Visit for VerbosityVisitor<'a> {
     fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
 }
 
+/// Collects critical event contents, since tracing has no critical level these events are
+/// rendered by `TermLayer` directly.
+struct CriticalVisitor {
+    critical: bool,
+    message: String,
+    kvs: String,
+}
+
+impl CriticalVisitor {
+    pub fn new(event: &'_ Event<'_>) -> Self {
+        let mut evt = CriticalVisitor {
+            critical: false,
+            message: String::new(),
+            kvs: String::new(),
+        };
+        event.record(&mut evt);
+        evt
+    }
+}
+
+impl Visit for CriticalVisitor {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == CRITICAL_KEY {
+            self.critical = value
+        } else {
+            self.record_debug(field, &value)
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else if field.name() != VERBOSITY_KEY {
+            let _ = write!(self.kvs, " {}={:?}", field.name(), value);
+        }
+    }
+}
+
 const VERBOSITY_KEY: &str = "verb";
 
+/// Event format prefixing events with source `file:line` when enabled.
+pub struct LocationFormat {
+    inner: Format,
+    location: bool,
+}
+
+impl<S, N> FormatEvent<S, N> for LocationFormat
+where
+    S: Subscriber + for<'a> registry::LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        writer: &mut dyn std::fmt::Write,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        if self.location {
+            let meta = event.metadata();
+            if let (Some(file), Some(line)) = (meta.file(), meta.line()) {
+                write!(writer, "{}:{} ", file, line)?;
+            }
+        }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+/// Terminal layer, printing events with `verb` field not greater than verbosity.
+///
+/// Verbosity also controls the output format:
+/// * `-v` - event target names,
+/// * `-vv` - span timings, printed when span closes,
+/// * `-vvv` - source `file:line` of events.
 pub struct TermLayer<S> {
     verbosity: u8,
-    inner: tracing_subscriber::fmt::Layer<S>,
+    filter: TargetFilter,
+    inner: tracing_subscriber::fmt::Layer<S, DefaultFields, LocationFormat>,
 }
 
-impl<S> TermLayer<S> {
-    pub fn new(verbosity: u8) -> Self {
-        let inner = tracing_subscriber::fmt::Layer::new();
-        TermLayer { verbosity, inner }
+impl<S> TermLayer<S>
+where
+    S: Subscriber + for<'a> registry::LookupSpan<'a>,
+{
+    pub fn new(verbosity: u8, filter: TargetFilter) -> Self {
+        let format = LocationFormat {
+            inner: Format::default().with_target(verbosity >= 1),
+            location: verbosity >= 3,
+        };
+        let span_events = if verbosity >= 2 {
+            FmtSpan::CLOSE
+        } else {
+            FmtSpan::NONE
+        };
+        let inner = tracing_subscriber::fmt::Layer::new()
+            .event_format(format)
+            .with_span_events(span_events);
+        TermLayer {
+            verbosity,
+            filter,
+            inner,
+        }
     }
 }
 
@@ -75,6 +169,26 @@ where
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let meta = event.metadata();
+        if !self.filter.enabled(meta.target(), meta.level().into()) {
+            return;
+        }
+
+        if *meta.level() == Level::ERROR && meta.fields().field(CRITICAL_KEY).is_some() {
+            let evt = CriticalVisitor::new(event);
+            if evt.critical {
+                // critical events are always printed, regardless of verbosity
+                eprintln!(
+                    "{} {}: {}{}",
+                    crate::Level::Critical.tag().red().bold(),
+                    meta.target(),
+                    evt.message,
+                    evt.kvs
+                );
+                return;
+            }
+        }
+
         let verbosity = event.metadata().fields().field(VERBOSITY_KEY);
         if verbosity.is_none() {
             return;
@@ -0,0 +1,166 @@
+use super::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelChangeKind {
+    Added,
+    Removed,
+    Updated,
+    Moved,
+}
+
+impl From<ChangeKind> for ModelChangeKind {
+    fn from(kind: ChangeKind) -> Self {
+        match kind {
+            ChangeKind::Added => ModelChangeKind::Added,
+            ChangeKind::Removed => ModelChangeKind::Removed,
+            ChangeKind::Updated => ModelChangeKind::Updated,
+            ChangeKind::Moved => ModelChangeKind::Moved,
+        }
+    }
+}
+
+/// Single node change between two model trees, identified by opath.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelChange {
+    kind: ModelChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_value: Option<NodeRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_value: Option<NodeRef>,
+}
+
+impl ModelChange {
+    pub fn kind(&self) -> ModelChangeKind {
+        self.kind
+    }
+
+    pub fn old_path(&self) -> Option<&str> {
+        self.old_path.as_deref()
+    }
+
+    pub fn new_path(&self) -> Option<&str> {
+        self.new_path.as_deref()
+    }
+
+    pub fn old_value(&self) -> Option<&NodeRef> {
+        self.old_value.as_ref()
+    }
+
+    pub fn new_value(&self) -> Option<&NodeRef> {
+        self.new_value.as_ref()
+    }
+}
+
+/// Machine-readable semantic diff of two model trees. Values are only included
+/// for updated nodes, added and removed subtrees are reported by path.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelChangeset {
+    changes: Vec<ModelChange>,
+}
+
+impl ModelChangeset {
+    pub fn from_models(
+        model1: &Model,
+        model2: &Model,
+        opts: &NodeDiffOptions,
+    ) -> ModelResult<ModelChangeset> {
+        Self::diff(model1.root(), model2.root(), opts)
+    }
+
+    pub fn diff(
+        root1: &NodeRef,
+        root2: &NodeRef,
+        opts: &NodeDiffOptions,
+    ) -> ModelResult<ModelChangeset> {
+        let diff = NodeDiff::diff(root1, root2, opts);
+
+        let mut changes = Vec::with_capacity(diff.changes().len());
+        for c in diff.changes().iter() {
+            let kind = ModelChangeKind::from(c.kind());
+            let (old_value, new_value) = if kind == ModelChangeKind::Updated {
+                (
+                    resolve_value(root1, c.old_path())?,
+                    resolve_value(root2, c.new_path())?,
+                )
+            } else {
+                (None, None)
+            };
+            changes.push(ModelChange {
+                kind,
+                old_path: c.old_path().map(|p| p.to_string()),
+                new_path: c.new_path().map(|p| p.to_string()),
+                old_value,
+                new_value,
+            });
+        }
+
+        Ok(ModelChangeset { changes })
+    }
+
+    pub fn changes(&self) -> &[ModelChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn resolve_value(root: &NodeRef, path: Option<&Opath>) -> ModelResult<Option<NodeRef>> {
+    match path {
+        Some(p) => {
+            let res = p
+                .apply(root, root)
+                .map_err_as_cause(|| ModelErrorDetail::ModelDiff)?;
+            Ok(res.into_one())
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_changes() {
+        // language=json
+        let n1 = NodeRef::from_json(r#"{"a": 1, "b": "removed", "c": {"d": "old"}}"#).unwrap();
+        // language=json
+        let n2 = NodeRef::from_json(r#"{"a": 1, "c": {"d": "new"}, "e": true}"#).unwrap();
+
+        let opts = NodeDiffOptions::new(false, None, None);
+        let cs = ModelChangeset::diff(&n1, &n2, &opts).unwrap();
+
+        let removed: Vec<_> = cs
+            .changes()
+            .iter()
+            .filter(|c| c.kind() == ModelChangeKind::Removed)
+            .collect();
+        assert_eq!(1, removed.len());
+        assert_eq!(Some("$.b"), removed[0].old_path());
+
+        let added: Vec<_> = cs
+            .changes()
+            .iter()
+            .filter(|c| c.kind() == ModelChangeKind::Added)
+            .collect();
+        assert_eq!(1, added.len());
+        assert_eq!(Some("$.e"), added[0].new_path());
+
+        let updated: Vec<_> = cs
+            .changes()
+            .iter()
+            .filter(|c| c.kind() == ModelChangeKind::Updated)
+            .collect();
+        assert_eq!(1, updated.len());
+        assert_eq!(Some("$.c.d"), updated[0].new_path());
+        assert_eq!("old", updated[0].old_value().unwrap().as_string());
+        assert_eq!("new", updated[0].new_value().unwrap().as_string());
+    }
+}
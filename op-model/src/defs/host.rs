@@ -7,14 +7,55 @@ pub struct HostDef {
     #[serde(skip)]
     node: NodeRef,
     hostname: String,
+    vars: NodeRef,
+    #[serde(skip)]
+    scope_def: ScopeDef,
 }
 
 impl HostDef {
     pub fn new(root: NodeRef, node: NodeRef) -> DefsResult<HostDef> {
+        Self::with_vars(root, node, None)
+    }
+
+    /// Creates host definition with variables from `globals` overridden by host-level `vars` property.
+    pub fn with_vars(
+        root: NodeRef,
+        node: NodeRef,
+        globals: Option<&NodeRef>,
+    ) -> DefsResult<HostDef> {
+        let vars = match globals {
+            Some(g) => g.deep_copy(),
+            None => NodeRef::object(Properties::new()),
+        };
+        if let Some(hv) = node.get_child_key("vars") {
+            match *hv.data().value() {
+                Value::Object(_) => {
+                    vars.extend(hv.deep_copy(), None).map_err_as_cause(|| {
+                        DefsErrorDetail::PropParse {
+                            prop: "vars".into(),
+                        }
+                    })?;
+                }
+                Value::Null => {}
+                _ => {
+                    return Err(DefsErrorDetail::UnexpectedPropType {
+                        kind: hv.data().kind(),
+                        expected: vec![Kind::Object],
+                    }
+                    .into())
+                }
+            }
+        }
+
+        let mut scope_def = ScopeDef::new();
+        scope_def.set_var_def("$vars".into(), ValueDef::Static(vars.clone()));
+
         let mut h = HostDef {
             root,
             node,
             hostname: String::new(),
+            vars,
+            scope_def,
         };
         h.hostname = get_expr(&h, "fqdn or hostname")?;
         Ok(h)
@@ -23,12 +64,32 @@ impl HostDef {
     pub fn hostname(&self) -> &str {
         &self.hostname
     }
+
+    /// Host variables. Global variables defined in manifest `[vars]` section,
+    /// overridden by host `vars` property.
+    pub fn vars(&self) -> &NodeRef {
+        &self.vars
+    }
+
+    /// Variable definitions registered in host scope, `$vars` is bound to host variables.
+    pub fn scope_def(&self) -> &ScopeDef {
+        &self.scope_def
+    }
+
+    /// Creates host scope as a child of `parent` (model or proc scope), in which `$vars`
+    /// shadows global variables with host variables.
+    pub fn scope(&self, parent: &ScopeMut) -> DefsResult<ScopeMut> {
+        let scope = ScopeMut::child(parent.clone().into());
+        self.scope_def.resolve(&self.root, &self.node, &scope)?;
+        Ok(scope)
+    }
 }
 
 impl Remappable for HostDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.root = node_map.get(&self.root.data_ptr()).unwrap().clone();
-        self.node = node_map.get(&self.node.data_ptr()).unwrap().clone();
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.root = remap_node(&self.root, node_map)?;
+        self.node = remap_node(&self.node, node_map)?;
+        Ok(())
     }
 }
 
@@ -45,20 +106,112 @@ impl ModelDef for HostDef {
 impl ParsedModelDef for HostDef {
     fn parse(_model: &Model, parent: &Scoped, node: &NodeRef) -> DefsResult<Self> {
         let kind = node.data().kind();
+        let defaults = match parent.scope_def().get_var_def("$defaults") {
+            Some(&ValueDef::Static(ref n)) => Some(n),
+            _ => None,
+        };
         match *node.data().value() {
             Value::Object(ref props) => {
                 if !props.contains_key("hostname") {
                     return Err(DefsErrorDetail::HostMissingHostname.into());
                 }
 
-                if !props.contains_key("ssh_dest") {
-                    return Err(DefsErrorDetail::HostMissingSshDest.into());
+                match props.get("ssh_dest") {
+                    Some(dest) => {
+                        if let Some(defaults) = defaults {
+                            apply_ssh_defaults(dest, defaults)?;
+                        }
+                        validate_ssh_dest(dest)?
+                    }
+                    None => return Err(DefsErrorDetail::HostMissingSshDest.into()),
                 }
             }
             _ => {
                 return Err(DefsErrorDetail::HostNonObject { kind }.into());
             }
         }
-        Ok(HostDef::new(parent.root().clone(), node.clone())?)
+        let globals = match parent.scope_def().get_var_def("$vars") {
+            Some(&ValueDef::Static(ref n)) => Some(n),
+            _ => None,
+        };
+        Ok(HostDef::with_vars(
+            parent.root().clone(),
+            node.clone(),
+            globals,
+        )?)
+    }
+}
+
+/// Fills `username` and `port` omitted in `ssh_dest` with manifest `[defaults]`
+/// values. Values defined by the host are left intact.
+fn apply_ssh_defaults(dest: &NodeRef, defaults: &NodeRef) -> DefsResult<()> {
+    if dest.data().kind() != Kind::Object {
+        return Ok(());
+    }
+
+    let mut props = Properties::new();
+    for &(key, default_key) in &[("username", "ssh_user"), ("port", "ssh_port")] {
+        let omitted = match dest.get_child_key(key) {
+            Some(n) => match *n.data().value() {
+                Value::Null => true,
+                _ => false,
+            },
+            None => true,
+        };
+        if omitted {
+            if let Some(value) = defaults.get_child_key(default_key) {
+                props.insert(key.into(), value.deep_copy());
+            }
+        }
+    }
+
+    if !props.is_empty() {
+        dest.extend(NodeRef::object(props), None)
+            .map_err_as_cause(|| DefsErrorDetail::PropParse {
+                prop: "ssh_dest".into(),
+            })?;
+    }
+    Ok(())
+}
+
+/// Checks `ssh_dest` fields that would otherwise only fail when connecting to the host.
+/// Empty or missing `username` is allowed, current user is used in that case.
+fn validate_ssh_dest(dest: &NodeRef) -> DefsResult<()> {
+    if let Some(port) = dest.get_child_key("port") {
+        let valid = match *port.data().value() {
+            Value::Integer(p) => p >= 1 && p <= i64::from(u16::MAX),
+            _ => false,
+        };
+        if !valid {
+            return Err(DefsErrorDetail::HostSshDestPort {
+                value: port.data().as_string().to_string(),
+            }
+            .into());
+        }
+    }
+
+    if let Some(username) = dest.get_child_key("username") {
+        let valid = match *username.data().value() {
+            Value::String(ref u) => is_valid_username(u),
+            _ => false,
+        };
+        if !valid {
+            return Err(DefsErrorDetail::HostSshDestUsername {
+                value: username.data().as_string().to_string(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+fn is_valid_username(username: &str) -> bool {
+    let mut chars = username.chars();
+    match chars.next() {
+        None => true,
+        Some(c) if c.is_ascii_alphanumeric() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+        }
+        Some(_) => false,
     }
 }
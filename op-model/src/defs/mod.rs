@@ -37,8 +37,12 @@ pub enum DefsErrorDetail {
     #[display(fmt = "cannot parse model watch")]
     ProcModelWatchParse,
 
-    #[display(fmt = "cannot parse file watch : {err}")]
-    ProcFileWatchParse { err: globset::Error },
+    #[display(fmt = "cannot parse file watch pattern '{pattern}' in proc '{proc}': {err}")]
+    ProcFileWatchParse {
+        pattern: String,
+        proc: String,
+        err: globset::Error,
+    },
 
     #[display(fmt = "'hosts' property must be a dynamic expression in step definition")]
     StepStaticHosts,
@@ -118,6 +122,85 @@ pub enum DefsErrorDetail {
 
     #[display(fmt = "cannot evaluate expression")]
     ExprErr,
+
+    #[display(fmt = "manifest syntax error at line {line}: {message}")]
+    ManifestSyntax { line: usize, message: String },
+
+    #[display(fmt = "manifest must contain '{prop}' property (line {line})")]
+    ManifestMissingProp { prop: String, line: usize },
+
+    #[display(fmt = "unknown manifest property '{prop}' (line {line})")]
+    ManifestUnknownProp { prop: String, line: usize },
+
+    #[display(
+        fmt = "manifest property '{prop}' must be {expected}, found: '{found}' (line {line})"
+    )]
+    ManifestPropType {
+        prop: String,
+        found: String,
+        expected: String,
+        line: usize,
+    },
+
+    #[display(fmt = "include cycle detected, file '{path}' includes itself")]
+    IncludeCycle { path: String },
+
+    #[display(fmt = "'$include' must be a path or an array of paths")]
+    IncludeParse,
+
+    #[display(fmt = "cannot include '{path}', only yaml, json and toml files can be included")]
+    IncludeUnsupported { path: String },
+
+    #[display(fmt = "included file '{path}' must contain an object")]
+    IncludeNonObject { path: String },
+
+    #[display(fmt = "invalid {kind} definition '{path}' in file '{file}'")]
+    DefSource {
+        kind: String,
+        path: String,
+        file: String,
+    },
+
+    #[display(fmt = "cannot import scope from '{expr}', expression must point to an object")]
+    ScopeImport { expr: String },
+
+    #[display(
+        fmt = "circular scope resolution in '{path}', involving keys: '{keys}'",
+        keys = "ListDisplay(keys)"
+    )]
+    ScopeCycle { path: String, keys: Vec<String> },
+
+    #[display(fmt = "cannot remap node '{path}' ({ptr}), node is missing from node map")]
+    RemapMissingNode { path: String, ptr: String },
+
+    #[display(fmt = "host 'ssh_dest.port' must be an integer in range 1-65535, found: '{value}'")]
+    HostSshDestPort { value: String },
+
+    #[display(fmt = "host 'ssh_dest.username' contains invalid characters: '{value}'")]
+    HostSshDestUsername { value: String },
+    #[display(fmt = "cannot resolve directory of file '{path}' to include files from")]
+    IncludeBaseDir { path: String },
+}
+
+impl DefsErrorDetail {
+    /// Error context pointing at definition `node`: its path in model tree and the file
+    /// it was loaded from, if known.
+    pub(crate) fn def_source(kind: &str, root: &NodeRef, node: &NodeRef) -> DefsErrorDetail {
+        let expr = Opath::parse("@.@file_path_abs").unwrap();
+        let file = expr
+            .apply(root, node)
+            .ok()
+            .and_then(|res| res.into_one())
+            .map(|n| n.data().as_string().to_string())
+            .filter(|f| !f.is_empty())
+            .unwrap_or_else(|| "<unknown>".to_string());
+
+        DefsErrorDetail::DefSource {
+            kind: kind.to_string(),
+            path: node.path().to_string(),
+            file,
+        }
+    }
 }
 
 mod host;
@@ -125,6 +208,26 @@ mod proc;
 mod scope;
 mod user;
 
+/// Definitions holding references to model tree nodes, which must be remapped to
+/// corresponding nodes when the tree is deep copied.
+pub trait Remappable {
+    /// Replaces referenced nodes with their counterparts from `node_map`. Fails with
+    /// `RemapMissingNode` if `node_map` is incomplete.
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()>;
+}
+
+/// Returns node corresponding to `node` from `node_map`.
+pub(crate) fn remap_node(node: &NodeRef, node_map: &NodeMap) -> DefsResult<NodeRef> {
+    match node_map.get(&node.data_ptr()) {
+        Some(n) => Ok(n.clone()),
+        None => Err(DefsErrorDetail::RemapMissingNode {
+            path: node.path().to_string(),
+            ptr: format!("{:?}", node.data_ptr()),
+        }
+        .into()),
+    }
+}
+
 pub trait ModelDef: Remappable + 'static {
     fn root(&self) -> &NodeRef;
 
@@ -173,6 +276,14 @@ fn get_expr<T: Primitive>(def: &dyn ModelDef, expr: &str) -> DefsResult<T> {
     }
 }
 
+/// Resolution state of `Scoped`, `InProgress` is used to detect circular resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResolveState {
+    Unresolved,
+    InProgress,
+    Resolved,
+}
+
 #[derive(Debug, Serialize)]
 pub struct Scoped {
     #[serde(skip)]
@@ -187,7 +298,7 @@ pub struct Scoped {
     #[serde(skip)]
     children: RefCell<Vec<&'static Scoped>>,
     #[serde(skip)]
-    resolved: Cell<bool>,
+    resolved: Cell<ResolveState>,
 }
 
 impl Scoped {
@@ -199,7 +310,7 @@ impl Scoped {
             scope: ScopeMut::new(),
             parent: Cell::new(None),
             children: RefCell::new(Vec::new()),
-            resolved: Cell::new(false),
+            resolved: Cell::new(ResolveState::Unresolved),
         }
     }
 
@@ -239,7 +350,7 @@ impl Scoped {
             .as_scoped()
             .scope
             .set_parent(Some(self.scope.clone().into()));
-        child.as_scoped().resolved.set(false);
+        child.as_scoped().resolved.set(ResolveState::Unresolved);
         self.children
             .borrow_mut()
             .push(std::mem::transmute::<&Scoped, &'static Scoped>(
@@ -249,26 +360,45 @@ impl Scoped {
 
     pub fn clear_scope(&self) {
         self.scope.clear_vars();
-        self.resolved.set(false);
+        self.resolved.set(ResolveState::Unresolved);
         for s in self.children.borrow().iter().cloned() {
             s.clear_scope();
         }
     }
 
+    /// Resolves parent scopes and then own scope definition. Re-entering a scope that is
+    /// still being resolved means circular resolution and fails with `ScopeCycle`.
     fn resolve(&self) -> DefsResult<()> {
-        if !self.resolved.get() {
-            if let Some(p) = self.parent.get() {
-                p.resolve()?;
+        match self.resolved.get() {
+            ResolveState::Resolved => Ok(()),
+            ResolveState::InProgress => Err(DefsErrorDetail::ScopeCycle {
+                path: self.node.path().to_string(),
+                keys: self.scope_def.var_names().map(|k| k.to_string()).collect(),
+            }
+            .into()),
+            ResolveState::Unresolved => {
+                self.resolved.set(ResolveState::InProgress);
+                let res = self.resolve_defs();
+                if res.is_ok() {
+                    self.resolved.set(ResolveState::Resolved);
+                } else {
+                    self.resolved.set(ResolveState::Unresolved);
+                }
+                res
             }
-            self.scope_def
-                .resolve(self.root(), self.node(), &self.scope)?;
-            self.resolved.set(true);
         }
-        Ok(())
+    }
+
+    fn resolve_defs(&self) -> DefsResult<()> {
+        if let Some(p) = self.parent.get() {
+            p.resolve()?;
+        }
+        self.scope_def
+            .resolve(self.root(), self.node(), &self.scope)
     }
 
     fn invalidate(&self) {
-        self.resolved.set(false);
+        self.resolved.set(ResolveState::Unresolved);
         for s in self.children.borrow().iter().cloned() {
             s.invalidate();
         }
@@ -276,12 +406,13 @@ impl Scoped {
 }
 
 impl Remappable for Scoped {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.root = node_map.get(&self.root.data_ptr()).unwrap().clone();
-        self.node = node_map.get(&self.node.data_ptr()).unwrap().clone();
-        self.scope_def.remap(node_map);
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.root = remap_node(&self.root, node_map)?;
+        self.node = remap_node(&self.node, node_map)?;
+        self.scope_def.remap(node_map)?;
         self.scope.clear_vars();
-        self.resolved.set(false);
+        self.resolved.set(ResolveState::Unresolved);
+        Ok(())
     }
 }
 
@@ -90,9 +90,9 @@ impl AsScoped for ProcDef {
 }
 
 impl Remappable for ProcDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.scoped.remap(node_map);
-        self.run.remap(node_map);
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.scoped.remap(node_map)?;
+        self.run.remap(node_map)
     }
 }
 
@@ -136,6 +136,8 @@ impl ParsedModelDef for ProcDef {
 
         match *node.data().value() {
             Value::Object(ref props) => {
+                p.id = get_expr(&p, "@.id or @.@key")?;
+
                 if props.get("when").is_some() {
                     p.kind = ProcKind::Exec;
                 } else if let Some(n) = props.get("proc") {
@@ -163,7 +165,8 @@ impl ParsedModelDef for ProcDef {
                         match *wn.data().value() {
                             Value::Object(ref props) => {
                                 for (k, v) in props.iter() {
-                                    let w = FileWatch::parse(k.as_ref(), &v.data().as_string())?;
+                                    let w =
+                                        FileWatch::parse(&p.id, k.as_ref(), &v.data().as_string())?;
                                     p.file_watches.push(w);
                                 }
                             }
@@ -185,7 +188,6 @@ impl ParsedModelDef for ProcDef {
             }
         }
 
-        p.id = get_expr(&p, "@.id or @.@key")?;
         p.label = get_expr(&p, "@.label or @.id or @.@key")?;
         p.path = get_expr(&p, "@.@file_path_abs")?;
         p.dir = get_expr(&p, "@.@dir_abs")?;
@@ -61,8 +61,11 @@ impl ParsedModelDef for RunDef {
 }
 
 impl Remappable for RunDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.steps.iter_mut().for_each(|s| s.remap(node_map));
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        for s in self.steps.iter_mut() {
+            s.remap(node_map)?;
+        }
+        Ok(())
     }
 }
 
@@ -164,7 +167,10 @@ impl ParsedModelDef for Step {
 }
 
 impl Remappable for Step {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.tasks.iter_mut().for_each(|t| t.remap(node_map));
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        for t in self.tasks.iter_mut() {
+            t.remap(node_map)?;
+        }
+        Ok(())
     }
 }
@@ -55,11 +55,12 @@ impl AsScoped for TaskDef {
 }
 
 impl Remappable for TaskDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.scoped.remap(node_map);
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.scoped.remap(node_map)?;
         if let Some(ref mut switch) = self.switch {
-            switch.remap(node_map);
+            switch.remap(node_map)?;
         }
+        Ok(())
     }
 }
 
@@ -196,7 +197,9 @@ pub enum OutputMode {
 pub struct TaskOutput {
     #[serde(flatten)]
     mode: OutputMode,
-    format: FileFormat,
+    /// Output format, `None` if it should be detected from the output itself (`auto`).
+    #[serde(with = "output_format")]
+    format: Option<FileFormat>,
 }
 
 impl TaskOutput {
@@ -207,7 +210,7 @@ impl TaskOutput {
                 Ok(out)
             }
             Value::String(ref s) => {
-                let format = FileFormat::from(s);
+                let format = output_format::parse(s);
                 Ok(TaskOutput {
                     format,
                     ..Default::default()
@@ -225,7 +228,7 @@ impl TaskOutput {
         &self.mode
     }
 
-    pub fn format(&self) -> FileFormat {
+    pub fn format(&self) -> Option<FileFormat> {
         self.format
     }
 
@@ -253,11 +256,44 @@ impl Default for TaskOutput {
     fn default() -> Self {
         TaskOutput {
             mode: OutputMode::Var("output".into()),
-            format: FileFormat::Yaml,
+            format: Some(FileFormat::Yaml),
         }
     }
 }
 
+/// (De)serializes output format, mapping `"auto"` to `None`.
+mod output_format {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub const AUTO: &str = "auto";
+
+    pub fn parse(s: &str) -> Option<FileFormat> {
+        if s.eq_ignore_ascii_case(AUTO) {
+            None
+        } else {
+            Some(FileFormat::from(s))
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        format: &Option<FileFormat>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match format {
+            Some(format) => format.serialize(serializer),
+            None => serializer.serialize_str(AUTO),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<FileFormat>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(parse(&s))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case", tag = "kind", content = "value")]
 pub enum TaskEnv {
@@ -342,8 +378,11 @@ impl ParsedModelDef for Switch {
 }
 
 impl Remappable for Switch {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.cases.iter_mut().for_each(|c| c.remap(node_map));
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        for c in self.cases.iter_mut() {
+            c.remap(node_map)?;
+        }
+        Ok(())
     }
 }
 
@@ -393,7 +432,7 @@ impl ParsedModelDef for Case {
 }
 
 impl Remappable for Case {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.proc.remap(node_map);
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.proc.remap(node_map)
     }
 }
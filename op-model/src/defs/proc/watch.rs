@@ -41,10 +41,17 @@ pub struct FileWatch {
 }
 
 impl FileWatch {
-    pub fn parse(glob: &str, mask: &str) -> DefsResult<FileWatch> {
-        let glob = GlobBuilder::new(glob)
-            .build()
-            .map_err(|err| DefsErrorDetail::ProcFileWatchParse { err })?;
+    /// Parses file watch `glob` pattern. `proc` is the id of the owning procedure,
+    /// reported together with the pattern when parsing fails.
+    pub fn parse(proc: &str, glob: &str, mask: &str) -> DefsResult<FileWatch> {
+        let glob =
+            GlobBuilder::new(glob)
+                .build()
+                .map_err(|err| DefsErrorDetail::ProcFileWatchParse {
+                    pattern: glob.to_string(),
+                    proc: proc.to_string(),
+                    err,
+                })?;
         Ok(FileWatch {
             glob,
             mask: ChangeKindMask::parse(mask),
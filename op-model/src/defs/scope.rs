@@ -43,7 +43,9 @@ impl ValueDef {
 }
 
 impl Remappable for ValueDef {
-    fn remap(&mut self, node_map: &NodeMap) {
+    /// Static values not found in `node_map` are deep copied, since they do not have to be
+    /// part of the model tree.
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
         match *self {
             ValueDef::Static(ref mut n) => {
                 if let Some(nn) = node_map.get(&n.data_ptr()) {
@@ -54,6 +56,7 @@ impl Remappable for ValueDef {
             }
             ValueDef::Resolvable(..) => {}
         }
+        Ok(())
     }
 }
 
@@ -94,6 +97,8 @@ impl ScopeDef {
     }
 
     pub fn set_var_def(&mut self, name: Symbol, value: ValueDef) {
+        // remove first, so that redefined value is resolved after values it may depend on
+        self.values.remove(&name);
         self.values.insert(name, value);
     }
 
@@ -105,6 +110,10 @@ impl ScopeDef {
         self.values.get(name)
     }
 
+    pub fn var_names(&self) -> impl Iterator<Item = &Symbol> {
+        self.values.keys()
+    }
+
     pub fn resolve(&self, root: &NodeRef, current: &NodeRef, scope: &ScopeMut) -> DefsResult<()> {
         for (name, value) in self.values.iter() {
             let rval = value.resolve(root, current, &scope).map_err_as_cause(|| {
@@ -119,25 +128,92 @@ impl ScopeDef {
 }
 
 impl Remappable for ScopeDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.values.iter_mut().for_each(|(_, v)| v.remap(node_map));
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        for (_, v) in self.values.iter_mut() {
+            v.remap(node_map)?;
+        }
+        Ok(())
+    }
+}
+
+impl ScopeDef {
+    fn parse_values(&mut self, props: &Properties) -> DefsResult<()> {
+        for (k, v) in props.iter() {
+            let val = ValueDef::parse(v)
+                .map_err_as_cause(|| DefsErrorDetail::ScopeValParse { key: k.to_string() })?;
+            self.set_var_def(k.clone(), val);
+        }
+        Ok(())
+    }
+
+    /// Imports scope definitions from shared library objects pointed to by `scope_import`
+    /// property, which is an opath expression or an array of expressions evaluated
+    /// against the definition node.
+    fn parse_imports(&mut self, root: &NodeRef, node: &NodeRef) -> DefsResult<()> {
+        let exprs = match node.get_child_key("scope_import") {
+            Some(n) => match *n.data().value() {
+                Value::String(ref s) => vec![s.to_string()],
+                Value::Array(ref elems) => {
+                    let mut exprs = Vec::with_capacity(elems.len());
+                    for e in elems.iter() {
+                        match *e.data().value() {
+                            Value::String(ref s) => exprs.push(s.to_string()),
+                            _ => {
+                                return Err(DefsErrorDetail::UnexpectedPropType {
+                                    kind: e.data().kind(),
+                                    expected: vec![Kind::String],
+                                }
+                                .into())
+                            }
+                        }
+                    }
+                    exprs
+                }
+                Value::Null => return Ok(()),
+                _ => {
+                    return Err(DefsErrorDetail::UnexpectedPropType {
+                        kind: n.data().kind(),
+                        expected: vec![Kind::Array, Kind::String],
+                    }
+                    .into())
+                }
+            },
+            None => return Ok(()),
+        };
+
+        for expr in exprs {
+            let err = || DefsErrorDetail::ScopeImport { expr: expr.clone() };
+            let lib = Opath::parse(&expr)
+                .map_err_as_cause(|| DefsErrorDetail::OpathParse)
+                .and_then(|e| {
+                    e.apply(root, node)
+                        .map_err_as_cause(|| DefsErrorDetail::ExprErr)
+                })
+                .map_err_as_cause(err)?
+                .into_one()
+                .ok_or_else(|| DefsError::from(err()))?;
+
+            match *lib.data().value() {
+                Value::Object(ref props) => self.parse_values(props)?,
+                _ => return Err(err().into()),
+            }
+        }
+        Ok(())
     }
 }
 
 impl ParsedModelDef for ScopeDef {
-    fn parse(_model: &Model, _parent: &Scoped, node: &NodeRef) -> DefsResult<Self> {
+    /// Parses `scope` property of definition `node`. Values imported with `scope_import` come
+    /// first, in order of imports, so that later imports and local `scope` values shadow
+    /// earlier definitions with the same name.
+    fn parse(_model: &Model, parent: &Scoped, node: &NodeRef) -> DefsResult<Self> {
         let mut scope = ScopeDef::new();
 
+        scope.parse_imports(parent.root(), node)?;
+
         if let Some(sn) = node.get_child_key("scope") {
             match *sn.data().value() {
-                Value::Object(ref props) => {
-                    for (k, v) in props.iter() {
-                        let val = ValueDef::parse(v).map_err_as_cause(|| {
-                            DefsErrorDetail::ScopeValParse { key: k.to_string() }
-                        })?;
-                        scope.set_var_def(k.clone(), val);
-                    }
-                }
+                Value::Object(ref props) => scope.parse_values(props)?,
                 Value::Null => {}
                 _ => {
                     return Err(DefsErrorDetail::ScopeNonObject {
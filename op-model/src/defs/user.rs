@@ -26,9 +26,10 @@ impl UserDef {
 }
 
 impl Remappable for UserDef {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.root = node_map.get(&self.root.data_ptr()).unwrap().clone();
-        self.node = node_map.get(&self.node.data_ptr()).unwrap().clone();
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.root = remap_node(&self.root, node_map)?;
+        self.node = remap_node(&self.node, node_map)?;
+        Ok(())
     }
 }
 
@@ -0,0 +1,74 @@
+use super::*;
+
+/// Computes SHA-1 hash (as hex string) of `node` tree, normalized to canonical JSON form:
+/// object properties sorted by key, no insignificant whitespace.
+pub fn fingerprint(node: &NodeRef) -> String {
+    let value = serde_json::to_value(node).expect("node tree is always serializable to json");
+    let mut canonical = String::new();
+    write_canonical(&value, &mut canonical);
+
+    let mut hash = sha1::Sha1::new();
+    hash.update(canonical.as_bytes());
+    hash.digest().to_string()
+}
+
+fn write_canonical(value: &serde_json::Value, out: &mut String) {
+    match *value {
+        serde_json::Value::Array(ref elems) => {
+            out.push('[');
+            for (i, e) in elems.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(e, out);
+            }
+            out.push(']');
+        }
+        serde_json::Value::Object(ref props) => {
+            let mut props: Vec<_> = props.iter().collect();
+            props.sort_by(|a, b| a.0.cmp(b.0));
+
+            out.push('{');
+            for (i, (key, value)) in props.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        ref value => out.push_str(&value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_for_different_formats_and_ordering() {
+        let json = NodeRef::from_json(r#"{"a": 1, "b": {"c": [1, 2], "d": "x"}}"#).unwrap();
+        // language=yaml
+        let yaml = indoc!(
+            r#"
+            b:
+              d: x
+              c: [1, 2]
+            a: 1
+            "#
+        );
+        let yaml = NodeRef::from_str(yaml.into(), FileFormat::Yaml).unwrap();
+
+        assert_eq!(fingerprint(&json), fingerprint(&yaml));
+    }
+
+    #[test]
+    fn differs_for_different_values() {
+        let n1 = NodeRef::from_json(r#"{"a": 1, "b": [1, 2]}"#).unwrap();
+        let n2 = NodeRef::from_json(r#"{"a": 1, "b": [2, 1]}"#).unwrap();
+
+        assert_ne!(fingerprint(&n1), fingerprint(&n2));
+    }
+}
@@ -42,15 +42,23 @@ path = "*.sh"
 "#;
 
 
+pub use self::changeset::*;
 pub use self::config::*;
 pub use self::defs::*;
+// shadows `kg_tree::Remappable`, defs remapping is fallible
+pub use self::defs::Remappable;
+pub use self::fingerprint::*;
+pub use self::lint::*;
 pub use self::load_file::*;
 pub use self::manifest::*;
 pub use self::model::*;
 pub use self::update::*;
 
+mod changeset;
 mod config;
 mod defs;
+mod fingerprint;
+mod lint;
 mod load_file;
 mod manifest;
 mod model;
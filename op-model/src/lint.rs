@@ -0,0 +1,189 @@
+use super::*;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LintSeverity {
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for LintSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            LintSeverity::Warning => write!(f, "warning"),
+            LintSeverity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Single structural problem found in model by `Model::lint()`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintFinding {
+    severity: LintSeverity,
+    path: String,
+    message: String,
+}
+
+impl LintFinding {
+    fn new(severity: LintSeverity, node: &NodeRef, message: String) -> LintFinding {
+        LintFinding {
+            severity,
+            path: node.path().to_string(),
+            message,
+        }
+    }
+
+    pub fn severity(&self) -> LintSeverity {
+        self.severity
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl std::fmt::Display for LintFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {} ({})", self.severity, self.message, self.path)
+    }
+}
+
+pub(crate) fn lint(model: &Model) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+
+    lint_hosts(model, &mut findings);
+
+    let model_vars: Vec<Symbol> = model.scope_def().var_names().cloned().collect();
+    for p in model.procs() {
+        lint_proc(model, p, &model_vars, &mut findings);
+    }
+
+    findings
+}
+
+fn lint_hosts(model: &Model, findings: &mut Vec<LintFinding>) {
+    let mut hostnames: HashMap<&str, &HostDef> = HashMap::with_capacity(model.hosts().len());
+    for h in model.hosts() {
+        if let Some(first) = hostnames.get(h.hostname()) {
+            findings.push(LintFinding::new(
+                LintSeverity::Error,
+                h.node(),
+                format!(
+                    "duplicate host name '{}', already defined in '{}'",
+                    h.hostname(),
+                    first.node().path()
+                ),
+            ));
+        } else {
+            hostnames.insert(h.hostname(), h);
+        }
+    }
+}
+
+fn lint_proc(
+    model: &Model,
+    proc: &ProcDef,
+    parent_vars: &[Symbol],
+    findings: &mut Vec<LintFinding>,
+) {
+    lint_scope(proc.node(), proc.scope_def(), parent_vars, findings);
+
+    let mut proc_vars = parent_vars.to_vec();
+    proc_vars.extend(proc.scope_def().var_names().cloned());
+
+    for step in proc.run().steps() {
+        if step.tasks().is_empty() {
+            findings.push(LintFinding::new(
+                LintSeverity::Warning,
+                proc.node(),
+                format!("step {} has no tasks", step.index()),
+            ));
+        }
+
+        if let Some(expr) = step.hosts() {
+            match proc.scope() {
+                Ok(scope) => match expr.apply_ext(proc.root(), proc.node(), scope) {
+                    Ok(hosts) => {
+                        if hosts.is_empty() {
+                            findings.push(LintFinding::new(
+                                LintSeverity::Warning,
+                                proc.node(),
+                                format!(
+                                    "hosts expression '{}' in step {} matches no hosts",
+                                    expr,
+                                    step.index()
+                                ),
+                            ));
+                        }
+                        for n in hosts.iter() {
+                            if model.get_host(n).is_none() {
+                                findings.push(LintFinding::new(
+                                    LintSeverity::Error,
+                                    proc.node(),
+                                    format!(
+                                        "hosts expression '{}' in step {} references '{}' which is not a host definition",
+                                        expr,
+                                        step.index(),
+                                        n.path()
+                                    ),
+                                ));
+                            }
+                        }
+                    }
+                    Err(err) => findings.push(LintFinding::new(
+                        LintSeverity::Error,
+                        proc.node(),
+                        format!(
+                            "cannot resolve hosts expression '{}' in step {}: {}",
+                            expr,
+                            step.index(),
+                            err
+                        ),
+                    )),
+                },
+                Err(err) => findings.push(LintFinding::new(
+                    LintSeverity::Error,
+                    proc.node(),
+                    format!("cannot resolve procedure scope: {}", err),
+                )),
+            }
+        }
+
+        for t in step.tasks() {
+            lint_scope(t.node(), t.scope_def(), &proc_vars, findings);
+            if let Some(switch) = t.switch() {
+                let mut task_vars = proc_vars.clone();
+                task_vars.extend(t.scope_def().var_names().cloned());
+                for c in switch.cases() {
+                    lint_proc(model, c.proc(), &task_vars, findings);
+                }
+            }
+        }
+    }
+}
+
+fn lint_scope(
+    node: &NodeRef,
+    scope_def: &ScopeDef,
+    parent_vars: &[Symbol],
+    findings: &mut Vec<LintFinding>,
+) {
+    for name in scope_def.var_names() {
+        if parent_vars.contains(name) {
+            findings.push(LintFinding::new(
+                LintSeverity::Warning,
+                node,
+                format!(
+                    "scope variable '{}' shadows variable defined in parent scope",
+                    name
+                ),
+            ));
+        }
+    }
+}
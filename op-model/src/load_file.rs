@@ -1,11 +1,58 @@
 use super::*;
 
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Maximum number of model files read concurrently.
+const MAX_CONCURRENT_READS: usize = 16;
+
+/// Contents of structured model files read concurrently, before the model tree is assembled.
+/// Files are only read ahead, parsing into `NodeRef` is done when the file is loaded.
+#[derive(Debug, Default)]
+pub struct PrefetchedFiles {
+    files: HashMap<PathBuf, String>,
+}
+
+impl PrefetchedFiles {
+    /// Read structured files from `paths` on blocking thread pool, with at most
+    /// `MAX_CONCURRENT_READS` files read at a time. Files that cannot be read are skipped,
+    /// they will be read again (and errors reported) when loaded.
+    pub async fn read(paths: Vec<PathBuf>) -> PrefetchedFiles {
+        use futures::stream::{self, StreamExt};
+
+        let reads = paths
+            .into_iter()
+            .filter(|p| structured_format(p, None).is_some())
+            .map(|path| {
+                tokio::task::spawn_blocking(move || {
+                    let mut content = String::new();
+                    fs::read_to_string(&path, &mut content)
+                        .ok()
+                        .map(|_| (path, content))
+                })
+            });
+
+        let files = stream::iter(reads)
+            .buffer_unordered(MAX_CONCURRENT_READS)
+            .filter_map(|res| async move { res.ok().flatten() })
+            .collect()
+            .await;
+
+        PrefetchedFiles { files }
+    }
+
+    fn get(&self, path: &Path) -> Option<&str> {
+        self.files.get(path).map(|c| c.as_str())
+    }
+}
+
 /// Function to load file form git repository.
 /// Path must be relative to repository dir.
 #[derive(Debug, Clone)]
 pub struct LoadFileFunc {
     model_dir: PathBuf,
     current_dir: PathBuf,
+    prefetched: Option<Arc<PrefetchedFiles>>,
 }
 
 impl LoadFileFunc {
@@ -13,6 +60,20 @@ impl LoadFileFunc {
         Self {
             model_dir,
             current_dir,
+            prefetched: None,
+        }
+    }
+
+    /// Creates function using file contents already read by `PrefetchedFiles`.
+    pub fn with_prefetched(
+        model_dir: PathBuf,
+        current_dir: PathBuf,
+        prefetched: Arc<PrefetchedFiles>,
+    ) -> Self {
+        Self {
+            model_dir,
+            current_dir,
+            prefetched: Some(prefetched),
         }
     }
 }
@@ -31,7 +92,8 @@ impl FuncCallable for LoadFileFunc {
             for path in paths.into_iter() {
                 let path = self.resolve_path(&path);
 
-                let node = NodeRef::from_file(&path, None)
+                let node = self
+                    .read_file(&path, None)
                     .map_err(|err| FuncCallErrorDetail::custom_func(&func_id, err))?;
                 out.add(node)
             }
@@ -43,9 +105,10 @@ impl FuncCallable for LoadFileFunc {
             for (path, format) in paths.into_iter().zip(formats.into_iter()) {
                 let path = self.resolve_path(&path);
 
-                let format: FileFormat = format.data().as_string().as_ref().into();
+                let format = file_format(&format.data().as_string());
 
-                let node = NodeRef::from_file(&path, Some(format))
+                let node = self
+                    .read_file(&path, Some(format))
                     .map_err(|err| FuncCallErrorDetail::custom_func(&func_id, err))?;
                 out.add(node)
             }
@@ -64,4 +127,344 @@ impl LoadFileFunc {
         let path = PathBuf::from(path.as_string());
         resolve_model_path(path, &self.current_dir, &self.model_dir)
     }
+
+    fn read_file(&self, path: &Path, format: Option<FileFormat>) -> ModelResult<NodeRef> {
+        let prefetched = self.prefetched.as_ref().map(|p| p.as_ref());
+        read_file(path, format, prefetched)
+    }
+}
+
+/// Key used to splice content of other file into the current node.
+pub const INCLUDE_KEY: &str = "$include";
+
+/// YAML merge key, mappings it points to are merged into the mapping containing it.
+const MERGE_KEY: &str = "<<";
+
+/// Read file into `NodeRef`, using content from `prefetched` if the file has been read ahead.
+/// Include directives (`$include`) in structured files (YAML, JSON, TOML) and merge keys (`<<`)
+/// in YAML files are expanded after the file is parsed.
+fn read_file(
+    path: &Path,
+    format: Option<FileFormat>,
+    prefetched: Option<&PrefetchedFiles>,
+) -> ModelResult<NodeRef> {
+    match structured_format(path, format) {
+        Some(format) => {
+            let mut visited = HashSet::new();
+            load_structured_file(path, format, prefetched, &mut visited)
+        }
+        None => Ok(NodeRef::from_file(path, format)?),
+    }
+}
+
+/// Load structured file with merge keys and includes expanded.
+/// `visited` contains files currently being loaded and is used to detect include cycles.
+fn load_structured_file(
+    path: &Path,
+    format: FileFormat,
+    prefetched: Option<&PrefetchedFiles>,
+    visited: &mut HashSet<PathBuf>,
+) -> ModelResult<NodeRef> {
+    let path_abs = fs::canonicalize(path)?;
+    if !visited.insert(path_abs.clone()) {
+        return Err(DefsErrorDetail::IncludeCycle {
+            path: path_abs.to_string_lossy().to_string(),
+        }
+        .into());
+    }
+
+    let node = match prefetched.and_then(|p| p.get(path)) {
+        Some(content) => {
+            let node = NodeRef::from_str(content.into(), format)?;
+            set_file_info(&node, path, format);
+            node
+        }
+        None => NodeRef::from_file(path, Some(format))?,
+    };
+
+    let dir = path_abs
+        .parent()
+        .ok_or_else(|| DefsErrorDetail::IncludeBaseDir {
+            path: path_abs.to_string_lossy().to_string(),
+        })?;
+
+    let expander = Expander {
+        dir,
+        merge_keys: format == FileFormat::Yaml,
+        prefetched,
+    };
+    let node = expander.expand(&node, visited)?.unwrap_or(node);
+    set_missing_file_info(&node, path, format);
+
+    visited.remove(&path_abs);
+    Ok(node)
+}
+
+fn set_file_info(node: &NodeRef, path: &Path, format: FileFormat) {
+    let file_info = FileInfo::new(path, FileType::File, format);
+    node.visit_recursive(|_, _, n| {
+        n.data_mut().set_file(Some(file_info.clone()));
+        true
+    });
+}
+
+/// Sets file info on nodes created during expansion, nodes from included files
+/// keep pointing at the file they were loaded from.
+fn set_missing_file_info(node: &NodeRef, path: &Path, format: FileFormat) {
+    let file_info = FileInfo::new(path, FileType::File, format);
+    node.visit_recursive(|_, _, n| {
+        if n.data().file().is_none() {
+            n.data_mut().set_file(Some(file_info.clone()));
+        }
+        true
+    });
+}
+
+/// Expands include directives and merge keys in a tree parsed from a single file.
+struct Expander<'a> {
+    /// Directory of the expanded file, include paths are resolved relative to it.
+    dir: &'a Path,
+    /// Merge keys are only expanded in YAML files.
+    merge_keys: bool,
+    prefetched: Option<&'a PrefetchedFiles>,
+}
+
+impl<'a> Expander<'a> {
+    /// Returns expanded copy of `node`, or `None` if there is nothing to expand in `node`.
+    fn expand(
+        &self,
+        node: &NodeRef,
+        visited: &mut HashSet<PathBuf>,
+    ) -> ModelResult<Option<NodeRef>> {
+        let (props, elems) = match *node.data().value() {
+            Value::Object(ref props) => {
+                let props: Vec<(Symbol, NodeRef)> =
+                    props.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+                (Some(props), None)
+            }
+            Value::Array(ref elems) => (None, Some(elems.to_vec())),
+            _ => (None, None),
+        };
+
+        if let Some(props) = props {
+            return self.expand_object(props, visited);
+        }
+        if let Some(elems) = elems {
+            let mut changed = false;
+            let mut expanded = Vec::with_capacity(elems.len());
+            for e in elems {
+                match self.expand(&e, visited)? {
+                    Some(n) => {
+                        changed = true;
+                        expanded.push(n);
+                    }
+                    None => expanded.push(e),
+                }
+            }
+            if changed {
+                return Ok(Some(NodeRef::array(expanded)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Properties defined directly in a mapping take precedence over merged ones, merged
+    /// properties take precedence over included ones. Earlier merged mappings take precedence
+    /// over later ones, while later included files take precedence over earlier ones.
+    fn expand_object(
+        &self,
+        props: Vec<(Symbol, NodeRef)>,
+        visited: &mut HashSet<PathBuf>,
+    ) -> ModelResult<Option<NodeRef>> {
+        let mut changed = false;
+        let mut own = Properties::new();
+        let mut merged = Vec::new();
+        let mut include = None;
+
+        for (k, v) in props {
+            if self.merge_keys && k.as_ref() == MERGE_KEY {
+                merged = merge_sources(&v);
+                changed = true;
+            } else if k.as_ref() == INCLUDE_KEY {
+                include = Some(v);
+                changed = true;
+            } else {
+                match self.expand(&v, visited)? {
+                    Some(n) => {
+                        changed = true;
+                        own.insert(k, n);
+                    }
+                    None => {
+                        own.insert(k, v);
+                    }
+                }
+            }
+        }
+
+        if !changed {
+            return Ok(None);
+        }
+
+        let object = NodeRef::object(Properties::new());
+
+        if let Some(include) = include {
+            let paths = include_paths(&include)?;
+            for p in paths.iter() {
+                let node = self.load_include(p, visited)?;
+                if node.data().kind() != Kind::Object {
+                    if paths.len() == 1 && own.is_empty() && merged.is_empty() {
+                        return Ok(Some(node));
+                    }
+                    return Err(DefsErrorDetail::IncludeNonObject { path: p.clone() }.into());
+                }
+                object
+                    .extend(node, None)
+                    .map_err_as_cause(|| DefsErrorDetail::PropParse {
+                        prop: INCLUDE_KEY.into(),
+                    })?;
+            }
+        }
+
+        for src in merged.into_iter().rev() {
+            let src = match self.expand(&src, visited)? {
+                Some(n) => n,
+                None => src.deep_copy(),
+            };
+            object
+                .extend(src, None)
+                .map_err_as_cause(|| DefsErrorDetail::PropParse {
+                    prop: MERGE_KEY.into(),
+                })?;
+        }
+
+        object
+            .extend(NodeRef::object(own), None)
+            .map_err_as_cause(|| DefsErrorDetail::PropParse {
+                prop: INCLUDE_KEY.into(),
+            })?;
+
+        Ok(Some(object))
+    }
+
+    fn load_include(&self, p: &str, visited: &mut HashSet<PathBuf>) -> ModelResult<NodeRef> {
+        let path = self.dir.join(p);
+        match structured_format(&path, None) {
+            Some(format) => load_structured_file(&path, format, self.prefetched, visited),
+            None => Err(DefsErrorDetail::IncludeUnsupported {
+                path: p.to_string(),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Mappings pointed to by merge key, either a single mapping or a sequence of mappings.
+fn merge_sources(value: &NodeRef) -> Vec<NodeRef> {
+    match *value.data().value() {
+        Value::Object(_) => vec![value.clone()],
+        Value::Array(ref elems) => elems
+            .iter()
+            .filter(|e| e.data().kind() == Kind::Object)
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Paths from `$include` directive, either a single path or an array of paths.
+fn include_paths(include: &NodeRef) -> DefsResult<Vec<String>> {
+    match *include.data().value() {
+        Value::String(ref p) => Ok(vec![p.to_string()]),
+        Value::Array(ref elems) => {
+            let mut paths = Vec::with_capacity(elems.len());
+            for e in elems.iter() {
+                match *e.data().value() {
+                    Value::String(ref p) => paths.push(p.to_string()),
+                    _ => return Err(DefsErrorDetail::IncludeParse.into()),
+                }
+            }
+            Ok(paths)
+        }
+        _ => Err(DefsErrorDetail::IncludeParse.into()),
+    }
+}
+
+/// Maps format name passed to `loadFile()` (usually file extension) to `FileFormat`.
+/// Names are case-insensitive, both `yaml` and `yml` denote YAML.
+fn file_format(format: &str) -> FileFormat {
+    match format.to_ascii_lowercase().as_str() {
+        "yaml" | "yml" => FileFormat::Yaml,
+        "json" => FileFormat::Json,
+        "toml" => FileFormat::Toml,
+        f => f.into(),
+    }
+}
+
+fn structured_format(path: &Path, format: Option<FileFormat>) -> Option<FileFormat> {
+    match format {
+        Some(FileFormat::Yaml) => Some(FileFormat::Yaml),
+        Some(FileFormat::Json) => Some(FileFormat::Json),
+        Some(FileFormat::Toml) => Some(FileFormat::Toml),
+        Some(_) => None,
+        None => match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => match file_format(ext) {
+                FileFormat::Yaml => Some(FileFormat::Yaml),
+                FileFormat::Json => Some(FileFormat::Json),
+                FileFormat::Toml => Some(FileFormat::Toml),
+                _ => None,
+            },
+            None => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn structured_format_by_extension() {
+        let f = |p: &str| structured_format(Path::new(p), None);
+
+        assert_eq!(Some(FileFormat::Yaml), f("conf/hosts/a.yaml"));
+        assert_eq!(Some(FileFormat::Yaml), f("conf/hosts/a.yml"));
+        assert_eq!(Some(FileFormat::Yaml), f("conf/hosts/a.YML"));
+        assert_eq!(Some(FileFormat::Json), f("conf/hosts/a.json"));
+        assert_eq!(Some(FileFormat::Toml), f("conf/hosts/a.toml"));
+        assert_eq!(None, f("conf/hosts/a.txt"));
+        assert_eq!(None, f("conf/hosts/a"));
+    }
+
+    #[test]
+    fn expand_merge_keys_precedence() {
+        // language=yaml
+        let yaml = r#"
+base: &base
+  port: 22
+  username: root
+other: &other
+  username: admin
+  shell: bash
+host:
+  <<: [*base, *other]
+  port: 2222
+"#;
+        let node = NodeRef::from_str(yaml.into(), FileFormat::Yaml).unwrap();
+        let expander = Expander {
+            dir: Path::new("."),
+            merge_keys: true,
+            prefetched: None,
+        };
+        let node = expander
+            .expand(&node, &mut HashSet::new())
+            .unwrap()
+            .unwrap();
+
+        let host = node.get_child_key("host").unwrap();
+        let prop = |key: &str| host.get_child_key(key).unwrap();
+        assert_eq!(Some(2222), prop("port").as_integer());
+        assert_eq!("root", prop("username").as_string());
+        assert_eq!("bash", prop("shell").as_string());
+        assert!(host.get_child_key("<<").is_none());
+    }
 }
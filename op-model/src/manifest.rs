@@ -1,5 +1,8 @@
 use super::*;
 
+use serde::de;
+use std::collections::HashMap;
+
 
 #[inline(always)]
 fn user_defined() -> bool {
@@ -67,7 +70,17 @@ impl Default for Defines {
     }
 }
 
+/// Host group definition. Group can be defined either as an explicit list of hostnames,
+/// or as an opath expression resolving to host definitions nodes.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum HostGroup {
+    Hosts(Vec<String>),
+    Expr(Opath),
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
 pub struct ManifestInfo {
     authors: Vec<String>,
     description: String,
@@ -91,11 +104,55 @@ impl Default for ManifestInfo {
     }
 }
 
+/// Defaults from manifest `[defaults]` section, used by host definitions
+/// that do not specify these values in `ssh_dest`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ManifestDefaults {
+    ssh_user: Option<String>,
+    ssh_port: Option<u16>,
+}
+
+impl ManifestDefaults {
+    pub fn ssh_user(&self) -> Option<&str> {
+        self.ssh_user.as_ref().map(|u| u.as_str())
+    }
+
+    pub fn ssh_port(&self) -> Option<u16> {
+        self.ssh_port
+    }
+
+    pub fn to_node(&self) -> NodeRef {
+        let mut p = Properties::new();
+        if let Some(ref user) = self.ssh_user {
+            p.insert("ssh_user".into(), NodeRef::string(user.as_str()));
+        }
+        if let Some(port) = self.ssh_port {
+            p.insert("ssh_port".into(), NodeRef::integer(i64::from(port)));
+        }
+        NodeRef::object(p)
+    }
+}
+
+impl Default for ManifestDefaults {
+    fn default() -> Self {
+        ManifestDefaults {
+            ssh_user: None,
+            ssh_port: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Manifest {
     info: ManifestInfo,
     defines: Defines,
+    defaults: ManifestDefaults,
+    groups: LinkedHashMap<String, HostGroup>,
+    vars: LinkedHashMap<String, NodeRef>,
+    #[serde(skip)]
+    source: Option<String>,
 }
 
 impl Default for Manifest {
@@ -103,6 +160,10 @@ impl Default for Manifest {
         Manifest {
             info: ManifestInfo::default(),
             defines: Defines::default(),
+            defaults: ManifestDefaults::default(),
+            groups: LinkedHashMap::new(),
+            vars: LinkedHashMap::new(),
+            source: None,
         }
     }
 }
@@ -115,6 +176,281 @@ impl Manifest {
     pub fn info(&self) -> &ManifestInfo {
         &self.info
     }
+
+    pub fn defaults(&self) -> &ManifestDefaults {
+        &self.defaults
+    }
+
+    pub fn groups(&self) -> &LinkedHashMap<String, HostGroup> {
+        &self.groups
+    }
+
+    pub fn vars(&self) -> &LinkedHashMap<String, NodeRef> {
+        &self.vars
+    }
+
+    pub(crate) fn set_source(&mut self, source: String) {
+        self.source = Some(source);
+    }
+
+    /// Validate manifest source against expected manifest structure. Checks required properties,
+    /// unknown properties and property value types. Reported errors contain line number of the
+    /// offending property. Manifests without source (e.g. default one) are always valid.
+    pub fn validate(&self) -> DefsResult<()> {
+        let source = match self.source {
+            Some(ref s) => s,
+            None => return Ok(()),
+        };
+
+        let value: toml::Value =
+            toml::from_str(source).map_err(|err| DefsErrorDetail::ManifestSyntax {
+                line: err.line_col().map(|(line, _)| line + 1).unwrap_or(1),
+                message: err.to_string(),
+            })?;
+
+        let root = match value.as_table() {
+            Some(root) => root,
+            None => return Ok(()),
+        };
+        let lines = &KeyLines::parse(source);
+
+        if !root.contains_key("info") {
+            return Err(DefsErrorDetail::ManifestMissingProp {
+                prop: "info".into(),
+                line: 1,
+            }
+            .into());
+        }
+
+        for (key, value) in root.iter() {
+            match key.as_str() {
+                "info" => {
+                    let info = expect_table(lines, "", key, value)?;
+                    for required in &["authors", "description"] {
+                        if !info.contains_key(*required) {
+                            return Err(DefsErrorDetail::ManifestMissingProp {
+                                prop: format!("info.{}", required),
+                                line: lines.line("", key),
+                            }
+                            .into());
+                        }
+                    }
+                    for (k, v) in info.iter() {
+                        match k.as_str() {
+                            "authors" => {
+                                let authors = expect_array(lines, key, k, v)?;
+                                for a in authors.iter() {
+                                    if !a.is_str() {
+                                        return Err(prop_type_err(lines, key, k, a, "string"));
+                                    }
+                                }
+                            }
+                            "description" => {
+                                if !v.is_str() {
+                                    return Err(prop_type_err(lines, key, k, v, "string"));
+                                }
+                            }
+                            _ => return Err(unknown_prop_err(lines, key, k)),
+                        }
+                    }
+                }
+                "defines" => {
+                    for (k, v) in expect_table(lines, "", key, value)?.iter() {
+                        if !v.is_str() {
+                            return Err(prop_type_err(lines, key, k, v, "string"));
+                        }
+                    }
+                }
+                "defaults" => {
+                    for (k, v) in expect_table(lines, "", key, value)?.iter() {
+                        match k.as_str() {
+                            "ssh_user" => {
+                                if !v.is_str() {
+                                    return Err(prop_type_err(lines, key, k, v, "string"));
+                                }
+                            }
+                            "ssh_port" => {
+                                if !v.is_integer() {
+                                    return Err(prop_type_err(lines, key, k, v, "integer"));
+                                }
+                            }
+                            _ => return Err(unknown_prop_err(lines, key, k)),
+                        }
+                    }
+                }
+                "groups" => {
+                    for (k, v) in expect_table(lines, "", key, value)?.iter() {
+                        match *v {
+                            toml::Value::String(_) => {}
+                            toml::Value::Array(ref hosts) if hosts.iter().all(|h| h.is_str()) => {}
+                            _ => {
+                                return Err(prop_type_err(
+                                    lines,
+                                    key,
+                                    k,
+                                    v,
+                                    "string or array of strings",
+                                ))
+                            }
+                        }
+                    }
+                }
+                "vars" => {
+                    expect_table(lines, "", key, value)?;
+                }
+                _ => return Err(unknown_prop_err(lines, "", key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Global variables from `[vars]` section as an object node.
+    pub fn vars_node(&self) -> NodeRef {
+        let mut p = Properties::new();
+        for (name, value) in self.vars.iter() {
+            p.insert(name.into(), value.deep_copy());
+        }
+        NodeRef::object(p)
+    }
+}
+
+fn prop_name(section: &str, key: &str) -> String {
+    if section.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", section, key)
+    }
+}
+
+fn prop_type_err(
+    lines: &KeyLines,
+    section: &str,
+    key: &str,
+    value: &toml::Value,
+    expected: &str,
+) -> DefsError {
+    DefsErrorDetail::ManifestPropType {
+        prop: prop_name(section, key),
+        found: value.type_str().to_string(),
+        expected: expected.to_string(),
+        line: lines.line(section, key),
+    }
+    .into()
+}
+
+fn unknown_prop_err(lines: &KeyLines, section: &str, key: &str) -> DefsError {
+    DefsErrorDetail::ManifestUnknownProp {
+        prop: prop_name(section, key),
+        line: lines.line(section, key),
+    }
+    .into()
+}
+
+fn expect_table<'a>(
+    lines: &KeyLines,
+    section: &str,
+    key: &str,
+    value: &'a toml::Value,
+) -> DefsResult<&'a toml::value::Table> {
+    value
+        .as_table()
+        .ok_or_else(|| prop_type_err(lines, section, key, value, "table"))
+}
+
+fn expect_array<'a>(
+    lines: &KeyLines,
+    section: &str,
+    key: &str,
+    value: &'a toml::Value,
+) -> DefsResult<&'a Vec<toml::Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| prop_type_err(lines, section, key, value, "array"))
+}
+
+/// Line numbers (1-based) of property keys in manifest source, taken from toml parser spans.
+/// Keys of root table (including table headers) are stored as `key`,
+/// keys of nested tables as `section.key`.
+struct KeyLines(HashMap<String, usize>);
+
+impl KeyLines {
+    /// If keys cannot be collected from `source`, all properties are reported at line 1.
+    fn parse(source: &str) -> KeyLines {
+        let line = |key: &toml::Spanned<String>| source[..key.start()].matches('\n').count() + 1;
+
+        let mut lines = HashMap::new();
+        if let Ok(root) = toml::from_str::<SpannedKeys<SpannedKeys<de::IgnoredAny>>>(source) {
+            for (section, keys) in root.0.iter() {
+                lines.insert(section.get_ref().clone(), line(section));
+                for (key, _) in keys.0.iter() {
+                    lines.insert(prop_name(section.get_ref(), key.get_ref()), line(key));
+                }
+            }
+        }
+        KeyLines(lines)
+    }
+
+    /// Returns line of property `key` defined in table `section`, empty `section` denotes
+    /// root table. Returns line of the section header (or first line) when property
+    /// definition cannot be found.
+    fn line(&self, section: &str, key: &str) -> usize {
+        self.0
+            .get(&prop_name(section, key))
+            .or_else(|| self.0.get(section))
+            .copied()
+            .unwrap_or(1)
+    }
+}
+
+/// Keys of a toml table with their parser spans. Values that are not tables have no keys.
+struct SpannedKeys<T>(Vec<(toml::Spanned<String>, T)>);
+
+impl<'de, T: de::Deserialize<'de>> de::Deserialize<'de> for SpannedKeys<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct KeysVisitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T: de::Deserialize<'de>> de::Visitor<'de> for KeysVisitor<T> {
+            type Value = SpannedKeys<T>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("toml value")
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut keys = Vec::new();
+                while let Some(key) = map.next_key()? {
+                    keys.push((key, map.next_value()?));
+                }
+                Ok(SpannedKeys(keys))
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+                Ok(SpannedKeys(Vec::new()))
+            }
+
+            fn visit_bool<E: de::Error>(self, _v: bool) -> Result<Self::Value, E> {
+                Ok(SpannedKeys(Vec::new()))
+            }
+
+            fn visit_i64<E: de::Error>(self, _v: i64) -> Result<Self::Value, E> {
+                Ok(SpannedKeys(Vec::new()))
+            }
+
+            fn visit_f64<E: de::Error>(self, _v: f64) -> Result<Self::Value, E> {
+                Ok(SpannedKeys(Vec::new()))
+            }
+
+            fn visit_str<E: de::Error>(self, _v: &str) -> Result<Self::Value, E> {
+                Ok(SpannedKeys(Vec::new()))
+            }
+        }
+
+        deserializer.deserialize_any(KeysVisitor(std::marker::PhantomData))
+    }
 }
 
 #[cfg(test)]
@@ -168,4 +504,221 @@ mod tests {
             assert!(d.is_user_defined());
         }
     }
+
+    mod groups {
+        use super::*;
+
+        #[test]
+        fn deserialize_groups() {
+            // language=toml
+            let toml = r#"
+            [groups]
+            web = ["web1.example.com", "web2.example.com"]
+            db = "$.conf.hosts[@.role == 'db']"
+            "#;
+
+            let m: Manifest = kg_tree::serial::toml::from_str(toml).unwrap();
+            assert_eq!(m.groups().len(), 2);
+            assert_eq!(
+                m.groups()["web"],
+                HostGroup::Hosts(vec!["web1.example.com".into(), "web2.example.com".into()])
+            );
+            assert_eq!(
+                m.groups()["db"],
+                HostGroup::Expr(Opath::parse("$.conf.hosts[@.role == 'db']").unwrap())
+            );
+        }
+    }
+
+    mod validate {
+        use super::*;
+
+        fn manifest(source: &str) -> Manifest {
+            let mut m: Manifest = kg_tree::serial::toml::from_str(source).unwrap();
+            m.set_source(source.to_string());
+            m
+        }
+
+        #[test]
+        fn valid() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = ["author@example.com"]
+description = "Opereon model"
+
+[defines]
+hosts = "$.hosts"
+
+[groups]
+web = ["web1.example.com"]
+
+[vars]
+domain = "example.com"
+"#;
+            manifest(toml).validate().unwrap();
+        }
+
+        #[test]
+        fn unknown_info_prop() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = [""]
+description = ""
+descripton = "typo"
+"#;
+            let err = manifest(toml).validate().unwrap_err();
+            match err.detail().downcast_ref::<DefsErrorDetail>() {
+                Some(DefsErrorDetail::ManifestUnknownProp { prop, line }) => {
+                    assert_eq!("info.descripton", prop);
+                    assert_eq!(5, *line);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn missing_info_prop() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = [""]
+"#;
+            let err = manifest(toml).validate().unwrap_err();
+            match err.detail().downcast_ref::<DefsErrorDetail>() {
+                Some(DefsErrorDetail::ManifestMissingProp { prop, line }) => {
+                    assert_eq!("info.description", prop);
+                    assert_eq!(2, *line);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn invalid_defaults_type() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = [""]
+description = ""
+
+[defaults]
+ssh_port = "twenty-two"
+"#;
+            let err = manifest(toml).validate().unwrap_err();
+            match err.detail().downcast_ref::<DefsErrorDetail>() {
+                Some(DefsErrorDetail::ManifestPropType { prop, line, .. }) => {
+                    assert_eq!("defaults.ssh_port", prop);
+                    assert_eq!(7, *line);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn invalid_group_type() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = [""]
+description = ""
+
+[groups]
+web = 1
+"#;
+            let err = manifest(toml).validate().unwrap_err();
+            match err.detail().downcast_ref::<DefsErrorDetail>() {
+                Some(DefsErrorDetail::ManifestPropType { prop, line, .. }) => {
+                    assert_eq!("groups.web", prop);
+                    assert_eq!(7, *line);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+
+        #[test]
+        fn prop_line_ignores_string_content() {
+            // language=toml
+            let toml = r#"
+[info]
+authors = [""]
+description = """
+[defaults]
+ssh_port = 22
+"""
+
+[defaults]
+ssh_port = "twenty-two"
+"#;
+            let err = manifest(toml).validate().unwrap_err();
+            match err.detail().downcast_ref::<DefsErrorDetail>() {
+                Some(DefsErrorDetail::ManifestPropType { prop, line, .. }) => {
+                    assert_eq!("defaults.ssh_port", prop);
+                    assert_eq!(10, *line);
+                }
+                other => panic!("unexpected error: {:?}", other),
+            }
+        }
+    }
+
+    mod defaults {
+        use super::*;
+
+        #[test]
+        fn deserialize_defaults() {
+            // language=toml
+            let toml = r#"
+            [defaults]
+            ssh_user = "deploy"
+            ssh_port = 2222
+            "#;
+
+            let m: Manifest = kg_tree::serial::toml::from_str(toml).unwrap();
+            assert_eq!(m.defaults().ssh_user(), Some("deploy"));
+            assert_eq!(m.defaults().ssh_port(), Some(2222));
+
+            let defaults = m.defaults().to_node();
+            assert_eq!(
+                defaults.get_child_key("ssh_user").unwrap().as_string(),
+                "deploy"
+            );
+            assert_eq!(
+                defaults.get_child_key("ssh_port").unwrap().as_integer(),
+                Some(2222)
+            );
+        }
+
+        #[test]
+        fn deserialize_no_defaults() {
+            let m: Manifest = kg_tree::serial::toml::from_str("").unwrap();
+            assert_eq!(m.defaults().ssh_user(), None);
+            assert_eq!(m.defaults().ssh_port(), None);
+            assert!(m.defaults().to_node().get_child_key("ssh_user").is_none());
+        }
+    }
+
+    mod vars {
+        use super::*;
+
+        #[test]
+        fn deserialize_vars() {
+            // language=toml
+            let toml = r#"
+            [vars]
+            domain = "example.com"
+            port = 22
+            "#;
+
+            let m: Manifest = kg_tree::serial::toml::from_str(toml).unwrap();
+            assert_eq!(m.vars().len(), 2);
+
+            let vars = m.vars_node();
+            assert_eq!(
+                vars.get_child_key("domain").unwrap().as_string(),
+                "example.com"
+            );
+            assert_eq!(vars.get_child_key("port").unwrap().as_integer(), Some(22));
+        }
+    }
 }
@@ -6,7 +6,7 @@ use std::sync::Arc;
 
 use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
 
-use super::load_file::LoadFileFunc;
+use super::load_file::{LoadFileFunc, PrefetchedFiles};
 use super::*;
 use kg_diag::{BasicDiag, Severity};
 
@@ -51,6 +51,87 @@ pub enum ModelErrorDetail {
 
     #[display(fmt = "cannot generate model diff")]
     ModelDiff,
+
+    #[display(fmt = "unknown host group '{group}'")]
+    HostGroupUnknown { group: String },
+
+    #[display(fmt = "host '{hostname}' from group '{group}' is not defined in model")]
+    HostGroupUnknownHost { group: String, hostname: String },
+
+    #[display(fmt = "node '{path}' from group '{group}' is not a host definition")]
+    HostGroupNonHost { group: String, path: String },
+
+    #[display(fmt = "invalid manifest file '{p}'", p = "path.display()")]
+    ManifestInvalid { path: PathBuf },
+
+    #[display(fmt = "cannot parse file '{p}': {err}", p = "path.display()")]
+    FileParse { path: PathBuf, err: String },
+
+    #[display(fmt = "invalid host limit pattern '{pattern}': {err}")]
+    HostLimitPattern { pattern: String, err: String },
+
+    #[display(fmt = "no hosts match limit '{limit}'")]
+    HostLimitEmpty { limit: String },
+
+    #[display(fmt = "cannot copy model")]
+    DeepCopy,
+}
+
+/// Restricts a run to hosts with hostname matching any of the glob patterns,
+/// eg. `web1.example.com` or `db*`. Empty limit matches all hosts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostLimit {
+    patterns: Vec<String>,
+}
+
+impl HostLimit {
+    pub fn new(patterns: Vec<String>) -> HostLimit {
+        HostLimit { patterns }
+    }
+
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    fn glob_set(&self) -> ModelResult<globset::GlobSet> {
+        let mut builder = globset::GlobSetBuilder::new();
+        for p in self.patterns.iter() {
+            let glob = globset::Glob::new(p).map_err(|err| ModelErrorDetail::HostLimitPattern {
+                pattern: p.clone(),
+                err: err.to_string(),
+            })?;
+            builder.add(glob);
+        }
+        Ok(builder.build().unwrap())
+    }
+
+    /// Returns hosts matching the limit. Returns error if limit is not empty and no host matches.
+    pub fn apply<'a, I>(&self, hosts: I) -> ModelResult<Vec<&'a HostDef>>
+    where
+        I: IntoIterator<Item = &'a HostDef>,
+    {
+        if self.is_empty() {
+            return Ok(hosts.into_iter().collect());
+        }
+
+        let globs = self.glob_set()?;
+        let hosts: Vec<&HostDef> = hosts
+            .into_iter()
+            .filter(|h| globs.is_match(h.hostname()))
+            .collect();
+        if hosts.is_empty() {
+            return Err(ModelErrorDetail::HostLimitEmpty {
+                limit: self.patterns.join(","),
+            }
+            .into());
+        }
+        Ok(hosts)
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -61,6 +142,8 @@ pub struct Model {
     hosts: Vec<HostDef>,
     users: Vec<UserDef>,
     procs: Vec<ProcDef>,
+    #[serde(skip_serializing_if = "LinkedHashMap::is_empty")]
+    groups: LinkedHashMap<String, HostGroup>,
     #[serde(skip)]
     lookup: ModelLookup,
 }
@@ -74,6 +157,7 @@ impl Model {
             hosts: Vec::new(),
             users: Vec::new(),
             procs: Vec::new(),
+            groups: LinkedHashMap::new(),
             lookup: ModelLookup::new(),
         }
     }
@@ -119,14 +203,23 @@ impl Model {
         fs::read_to_string(&path, &mut content)
             .into_diag_res()
             .map_err_as_cause(|| ModelErrorDetail::ManifestRead { path: path.clone() })?;
-        let manifest: Manifest = kg_tree::serial::toml::from_str(&content)
+        let mut manifest: Manifest = kg_tree::serial::toml::from_str(&content)
             .map_err_as_cause(|| ModelErrorDetail::ManifestParse { path: path.clone() })?;
+        manifest.set_source(content);
+        manifest
+            .validate()
+            .map_err_as_cause(|| ModelErrorDetail::ManifestInvalid { path: path.clone() })?;
         Ok(manifest)
     }
     #[instrument(
         name = "Model::read",
     )]
     pub fn read(rev_info: RevInfo) -> ModelResult<Model> {
+        Self::read_prefetched(rev_info, PrefetchedFiles::default())
+    }
+
+    /// Read model, using content of model files already read by `PrefetchedFiles`.
+    pub fn read_prefetched(rev_info: RevInfo, prefetched: PrefetchedFiles) -> ModelResult<Model> {
         let manifest = Model::load_manifest(rev_info.path())?;
         info!("Reading model");
 
@@ -134,6 +227,7 @@ impl Model {
 
         let mut m = Model {
             rev_info,
+            groups: manifest.groups().clone(),
             ..Model::empty()
         };
 
@@ -152,7 +246,7 @@ impl Model {
         // TODO
         // Error messages from following functions are not very detailed.
         // Consider more detailed errors. See test cases 'tests/model.rs'
-        m.resolve_includes(&cr, &scope)
+        m.resolve_includes(&cr, &scope, prefetched)
             .map_err_as_cause(|| ModelErrorDetail::IncludesResolve)?;
         m.set_defines(&manifest);
         m.resolve_overrides(&cr, &scope)
@@ -178,74 +272,73 @@ impl Model {
         Self::read(rev_info)
     }
 
-    /// Walk through each entry in model directory, resolve matching `Includes` and apply changes to model tree
-    fn resolve_includes(&mut self, cr: &ConfigResolver, scope: &ScopeMut) -> ModelResult<()> {
-        use walkdir::WalkDir;
-
-        let load_file_sym = Symbol::from(LOAD_FILE_FUNC_NAME);
+    /// Returns paths of files in model directory matching `Includes`, in walk order.
+    /// Used to read model files ahead with `PrefetchedFiles`.
+    pub fn included_files(model_dir: &Path) -> ModelResult<Vec<PathBuf>> {
+        let cr = ConfigResolver::scan(model_dir)
+            .into_diag_res()
+            .map_err_as_cause(|| ModelErrorDetail::ConfigRead)?;
 
-        for e in WalkDir::new(self.rev_info.path())
-            .min_depth(1)
-            .sort_by(|a, b| a.path().cmp(b.path()))
+        Ok(included_entries(model_dir, &cr)
             .into_iter()
-            .filter_map(|e| e.ok()) {
-            let path_abs = e.path();
-            let path = path_abs.strip_prefix(self.rev_info.path()).unwrap();
-
-            if path.starts_with(DEFAULT_WORK_DIR_PATH) {
-                continue;
-            }
-
-            let file_type: FileType = e.file_type().into();
+            .filter(|(_, file_type, _)| *file_type == FileType::File)
+            .map(|(path_abs, _, _)| path_abs)
+            .collect())
+    }
 
-            if file_type == FileType::File {
-                let file_name = path.file_name().unwrap();
-                if file_name == DEFAULT_MANIFEST_FILENAME || file_name == DEFAULT_CONFIG_FILENAME {
-                    continue;
-                }
-            }
+    /// Walk through each entry in model directory, resolve matching `Includes` and apply changes to model tree
+    fn resolve_includes(
+        &mut self,
+        cr: &ConfigResolver,
+        scope: &ScopeMut,
+        prefetched: PrefetchedFiles,
+    ) -> ModelResult<()> {
+        let load_file_sym = Symbol::from(LOAD_FILE_FUNC_NAME);
 
-            let config = cr.resolve(&path_abs);
+        let entries = included_entries(self.rev_info.path(), cr);
+        let prefetched = Arc::new(prefetched);
 
-            if let Some(inc) = config.find_include(&path, file_type) {
-                let file_info = FileInfo::new(path_abs, file_type, FileFormat::Binary);
+        // tree is assembled sequentially in walk order, regardless of read completion order
+        for (path_abs, file_type, inc) in entries {
+            let file_info = FileInfo::new(&path_abs, file_type, FileFormat::Binary);
 
-                let n = match file_type {
-                    FileType::File => {
-                        let data = FileBuffer::open(&path_abs)?;
-                        NodeRef::binary(data.into_data())
-                    }
-                    FileType::Dir => {
-                        NodeRef::null()
-                    }
-                    _ => return Err(ModelErrorDetail::IncludesResolve.into())
-                };
+            let n = match file_type {
+                FileType::File => {
+                    let data = FileBuffer::open(&path_abs)?;
+                    NodeRef::binary(data.into_data())
+                }
+                FileType::Dir => NodeRef::null(),
+                _ => return Err(ModelErrorDetail::IncludesResolve.into()),
+            };
 
-                n.data_mut().set_file(Some(file_info.clone()));
+            n.data_mut().set_file(Some(file_info.clone()));
 
-                let parent_path = path_abs.parent().unwrap();
+            let parent_path = path_abs.parent().unwrap();
 
 
-                scope.set_func(
-                    load_file_sym.clone(),
-                    Box::new(LoadFileFunc::new(self.rev_info.path().into(), parent_path.into())),
-                );
+            scope.set_func(
+                load_file_sym.clone(),
+                Box::new(LoadFileFunc::with_prefetched(
+                    self.rev_info.path().into(),
+                    parent_path.into(),
+                    prefetched.clone(),
+                )),
+            );
 
-                let item = inc
-                    .item()
-                    .apply_one_ext(self.root(), &n, scope.as_ref())
-                    .map_err_as_cause(|| ModelErrorDetail::Expr)?;
+            let item = inc
+                .item()
+                .apply_one_ext(self.root(), &n, scope.as_ref())
+                .map_err_as_cause(|| ModelErrorDetail::Expr)?;
 
-                if item.data().file().is_none() {
-                    item.data_mut().set_file(Some(file_info));
-                }
+            if item.data().file().is_none() {
+                item.data_mut().set_file(Some(file_info));
+            }
 
-                scope.set_var("item".into(), NodeSet::One(item));
+            scope.set_var("item".into(), NodeSet::One(item));
 
-                inc.mapping()
-                    .apply_ext(self.root(), self.root(), scope.as_ref())
-                    .map_err_as_cause(|| ModelErrorDetail::Expr)?;
-            }
+            inc.mapping()
+                .apply_ext(self.root(), self.root(), scope.as_ref())
+                .map_err_as_cause(|| ModelErrorDetail::Expr)?;
         }
 
         // do not leak temporary scope items
@@ -262,21 +355,24 @@ impl Model {
         // definitions (hosts, users, processors)
         let mut hosts = Vec::new();
         for h in scope.get_var("$hosts").unwrap().iter() {
-            let host = HostDef::parse(&self, &self.scoped, h)?;
+            let host = HostDef::parse(&self, &self.scoped, h)
+                .map_err_as_cause(|| DefsErrorDetail::def_source("host", self.root(), h))?;
             hosts.push(host);
         }
         self.hosts = hosts;
 
         let mut users = Vec::new();
         for u in scope.get_var("$users").unwrap().iter() {
-            let user = UserDef::parse(&self, &self.scoped, u)?;
+            let user = UserDef::parse(&self, &self.scoped, u)
+                .map_err_as_cause(|| DefsErrorDetail::def_source("user", self.root(), u))?;
             users.push(user);
         }
         self.users = users;
 
         let mut procs = Vec::new();
         for p in scope.get_var("$procs").unwrap().iter() {
-            let proc = ProcDef::parse(&self, &self.scoped, p)?;
+            let proc = ProcDef::parse(&self, &self.scoped, p)
+                .map_err_as_cause(|| DefsErrorDetail::def_source("proc", self.root(), p))?;
             procs.push(proc);
         }
         self.procs = procs;
@@ -326,8 +422,9 @@ impl Model {
         Ok(())
     }
 
-    /// Add `$defines`, `$hosts`, `$users`, `$procs` variables to model scope.
-    /// Variables are computed from `Defines` defined in `Manifest`.
+    /// Add `$defines`, `$vars`, `$defaults`, `$hosts`, `$users`, `$procs` variables to model scope.
+    /// Variables are computed from `Defines`, `[vars]` and `[defaults]` sections defined
+    /// in `Manifest`.
     fn set_defines(&mut self, manifest: &Manifest) {
         let defs = manifest.defines().to_node();
         if manifest.defines().is_user_defined() {
@@ -341,6 +438,11 @@ impl Model {
         let scope_def = self.scoped.scope_def_mut();
 
         scope_def.set_var_def("$defines".into(), ValueDef::Static(defs));
+        scope_def.set_var_def("$vars".into(), ValueDef::Static(manifest.vars_node()));
+        scope_def.set_var_def(
+            "$defaults".into(),
+            ValueDef::Static(manifest.defaults().to_node()),
+        );
         scope_def.set_var_def(
             "$hosts".into(),
             ValueDef::Resolvable(manifest.defines().hosts().clone()),
@@ -375,6 +477,66 @@ impl Model {
         &self.procs
     }
 
+    pub fn groups(&self) -> &LinkedHashMap<String, HostGroup> {
+        &self.groups
+    }
+
+    /// Returns hosts belonging to group `name` defined in manifest `[groups]` section.
+    pub fn hosts_in_group(&self, name: &str) -> ModelResult<Vec<&HostDef>> {
+        let group = match self.groups.get(name) {
+            Some(group) => group,
+            None => {
+                return Err(ModelErrorDetail::HostGroupUnknown {
+                    group: name.to_string(),
+                }
+                .into())
+            }
+        };
+
+        match *group {
+            HostGroup::Hosts(ref hostnames) => {
+                let mut hosts = Vec::with_capacity(hostnames.len());
+                for hostname in hostnames.iter() {
+                    match self.hosts.iter().find(|h| h.hostname() == hostname) {
+                        Some(h) => hosts.push(h),
+                        None => {
+                            return Err(ModelErrorDetail::HostGroupUnknownHost {
+                                group: name.to_string(),
+                                hostname: hostname.clone(),
+                            }
+                            .into())
+                        }
+                    }
+                }
+                Ok(hosts)
+            }
+            HostGroup::Expr(ref expr) => {
+                let scope = self.scope()?;
+                let res = expr
+                    .apply_ext(self.root(), self.root(), scope)
+                    .map_err_as_cause(|| ModelErrorDetail::Expr)?;
+                let mut hosts = Vec::with_capacity(res.len());
+                for n in res.iter() {
+                    match self
+                        .hosts
+                        .iter()
+                        .find(|h| h.node().data_ptr() == n.data_ptr())
+                    {
+                        Some(h) => hosts.push(h),
+                        None => {
+                            return Err(ModelErrorDetail::HostGroupNonHost {
+                                group: name.to_string(),
+                                path: n.path().to_string(),
+                            }
+                            .into())
+                        }
+                    }
+                }
+                Ok(hosts)
+            }
+        }
+    }
+
     pub fn get_host(&self, node: &NodeRef) -> Option<&HostDef> {
         self.lookup.get(node)
     }
@@ -407,6 +569,18 @@ impl Model {
         self.lookup.get_path(self.root(), node_path)
     }
 
+    /// Check model for structural problems, like duplicate host names, steps without tasks,
+    /// hosts expressions not matching host definitions or shadowed scope variables.
+    pub fn lint(&self) -> Vec<LintFinding> {
+        lint::lint(self)
+    }
+
+    /// Stable fingerprint of model tree. Logically equal models have the same fingerprint,
+    /// regardless of file formats, formatting or property ordering.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.root())
+    }
+
     pub fn resolve_path<P1, P2>(&self, path: P1, current_dir: P2) -> PathBuf
     where
         P1: AsRef<Path>,
@@ -452,7 +626,7 @@ impl Model {
         self.as_scoped().clear_scope();
     }
 
-    fn deep_copy(&self) -> Self {
+    fn deep_copy(&self) -> ModelResult<Self> {
         let mut node_path_map = HashMap::new();
         self.root().visit_recursive(|_, _, n| {
             node_path_map.insert(n.data_ptr(), n.path());
@@ -480,12 +654,14 @@ impl Model {
             hosts: self.hosts.clone(),
             users: self.users.clone(),
             procs: self.procs.clone(),
+            groups: self.groups.clone(),
             lookup: ModelLookup::new(),
         };
 
-        m.remap(&node_map);
+        m.remap(&node_map)
+            .map_err_as_cause(|| ModelErrorDetail::DeepCopy)?;
 
-        m
+        Ok(m)
     }
 }
 
@@ -496,11 +672,18 @@ impl AsScoped for Model {
 }
 
 impl Remappable for Model {
-    fn remap(&mut self, node_map: &NodeMap) {
-        self.scoped.remap(node_map);
-        self.hosts.iter_mut().for_each(|h| h.remap(node_map));
-        self.users.iter_mut().for_each(|u| u.remap(node_map));
-        self.procs.iter_mut().for_each(|p| p.remap(node_map));
+    fn remap(&mut self, node_map: &NodeMap) -> DefsResult<()> {
+        self.scoped.remap(node_map)?;
+        for h in self.hosts.iter_mut() {
+            h.remap(node_map)?;
+        }
+        for u in self.users.iter_mut() {
+            u.remap(node_map)?;
+        }
+        for p in self.procs.iter_mut() {
+            p.remap(node_map)?;
+        }
+        Ok(())
     }
 }
 
@@ -601,6 +784,46 @@ impl std::fmt::Debug for ModelLookup {
     }
 }
 
+/// Walk through each entry in model directory and collect entries matching `Includes`,
+/// sorted by path.
+fn included_entries<'a>(
+    model_dir: &Path,
+    cr: &'a ConfigResolver,
+) -> Vec<(PathBuf, FileType, &'a Include)> {
+    use walkdir::WalkDir;
+
+    let mut entries = Vec::new();
+    for e in WalkDir::new(model_dir)
+        .min_depth(1)
+        .sort_by(|a, b| a.path().cmp(b.path()))
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path_abs = e.path();
+        let path = path_abs.strip_prefix(model_dir).unwrap();
+
+        if path.starts_with(DEFAULT_WORK_DIR_PATH) {
+            continue;
+        }
+
+        let file_type: FileType = e.file_type().into();
+
+        if file_type == FileType::File {
+            let file_name = path.file_name().unwrap();
+            if file_name == DEFAULT_MANIFEST_FILENAME || file_name == DEFAULT_CONFIG_FILENAME {
+                continue;
+            }
+        }
+
+        let config = cr.resolve(&path_abs);
+
+        if let Some(inc) = config.find_include(&path, file_type) {
+            entries.push((path_abs.to_path_buf(), file_type, inc));
+        }
+    }
+    entries
+}
+
 #[derive(Debug, Clone)]
 pub struct ModelRef(Arc<ReentrantMutex<Model>>);
 
@@ -616,6 +839,14 @@ impl ModelRef {
         Ok(Self::new(Model::read(rev_info)?))
     }
 
+    /// Read model for provided revision info. Model files are read concurrently
+    /// on blocking thread pool before the model tree is assembled.
+    pub async fn read_async(rev_info: RevInfo) -> ModelResult<ModelRef> {
+        let paths = Model::included_files(rev_info.path())?;
+        let prefetched = PrefetchedFiles::read(paths).await;
+        Ok(Self::new(Model::read_prefetched(rev_info, prefetched)?))
+    }
+
     /// Create a new model for provided revision info.
     pub fn create(rev_info: RevInfo) -> ModelResult<ModelRef> {
         Ok(Self::new(Model::create(rev_info)?))
@@ -625,9 +856,9 @@ impl ModelRef {
         self.0.lock()
     }
 
-    pub fn deep_copy(&self) -> ModelRef {
+    pub fn deep_copy(&self) -> ModelResult<ModelRef> {
         let m = self.lock();
-        Self::new(m.deep_copy())
+        Ok(Self::new(m.deep_copy()?))
     }
 
     pub fn reset(&self) {
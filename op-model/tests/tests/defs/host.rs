@@ -1,6 +1,6 @@
 use super::*;
 use kg_tree::Kind;
-use op_model::{HostDef, ParsedModelDef, DefsErrorDetail};
+use op_model::{DefsErrorDetail, HostDef, ParsedModelDef, Remappable};
 use op_test_helpers::UnwrapDisplay;
 
 #[test]
@@ -89,3 +89,149 @@ fn parse_non_obj_host() {
         assert_eq!(&Kind::String, kind)
     );
 }
+
+#[test]
+fn new_host_vars_override_globals() {
+    // language=json
+    let json = r#"{
+        "hostname": "localhost",
+        "vars": {
+            "domain": "host.example.com"
+        }
+    }"#;
+    let node: NodeRef = node!(json);
+    // language=json
+    let globals: NodeRef = node!(r#"{"domain": "example.com", "port": 22}"#);
+    let host = HostDef::with_vars(node.clone(), node.clone(), Some(&globals)).unwrap_disp();
+
+    assert_eq!(
+        "host.example.com",
+        host.vars().get_child_key("domain").unwrap().as_string()
+    );
+    assert_eq!(
+        Some(22),
+        host.vars().get_child_key("port").unwrap().as_integer()
+    );
+    // globals must not be modified
+    assert_eq!(
+        "example.com",
+        globals.get_child_key("domain").unwrap().as_string()
+    );
+}
+
+#[test]
+fn new_host_vars_non_obj() {
+    // language=json
+    let json = r#"{
+        "hostname": "localhost",
+        "vars": "some string"
+    }"#;
+    let node: NodeRef = node!(json);
+    let res = HostDef::with_vars(node.clone(), node.clone(), None);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::UnexpectedPropType { kind, .. },
+        assert_eq!(&Kind::String, kind)
+    );
+}
+
+#[test]
+fn remap_missing_node() {
+    let node: NodeRef = node!(r#"{"hostname": "localhost"}"#);
+    let mut host = HostDef::new(node.clone(), node.clone()).unwrap_disp();
+
+    let res = host.remap(&kg_tree::NodeMap::with_capacity(0));
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::RemapMissingNode { path, .. },
+        assert_eq!("$", path)
+    );
+}
+
+#[test]
+fn parse_ssh_dest_valid() {
+    // language=json
+    let node = r#"{
+        "hostname": "localhost",
+        "ssh_dest": {
+            "port": 2222,
+            "username": "deploy.user-1"
+        }
+    }"#;
+    let node: NodeRef = node!(node);
+    let model: Model = Model::empty();
+
+    let host = HostDef::parse(&model, model.as_scoped(), &node).unwrap_disp();
+
+    assert_eq!("localhost", host.hostname())
+}
+
+#[test]
+fn parse_ssh_dest_port_non_numeric() {
+    // language=json
+    let node = r#"{
+        "hostname": "localhost",
+        "ssh_dest": {
+            "port": "twenty-two"
+        }
+    }"#;
+    let node: NodeRef = node!(node);
+    let model: Model = Model::empty();
+
+    let res = HostDef::parse(&model, model.as_scoped(), &node);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::HostSshDestPort { value },
+        assert_eq!("twenty-two", value)
+    );
+}
+
+#[test]
+fn parse_ssh_dest_port_out_of_range() {
+    // language=json
+    let node = r#"{
+        "hostname": "localhost",
+        "ssh_dest": {
+            "port": 70000
+        }
+    }"#;
+    let node: NodeRef = node!(node);
+    let model: Model = Model::empty();
+
+    let res = HostDef::parse(&model, model.as_scoped(), &node);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::HostSshDestPort { value },
+        assert_eq!("70000", value)
+    );
+}
+
+#[test]
+fn parse_ssh_dest_username_invalid() {
+    // language=json
+    let node = r#"{
+        "hostname": "localhost",
+        "ssh_dest": {
+            "username": "root; rm -rf /"
+        }
+    }"#;
+    let node: NodeRef = node!(node);
+    let model: Model = Model::empty();
+
+    let res = HostDef::parse(&model, model.as_scoped(), &node);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::HostSshDestUsername { value },
+        assert_eq!("root; rm -rf /", value)
+    );
+}
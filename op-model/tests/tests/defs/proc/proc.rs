@@ -128,6 +128,32 @@ watch_file:
         assert_detail!(res, DefsErrorDetail, DefsErrorDetail::ProcFileWatchParse{..});
 }
 
+#[test]
+fn proc_def_file_watch_parse_err_reports_pattern() {
+    // language=yaml
+    let node = r#"
+proc: update
+id: update_hosts
+watch_file:
+  "conf/**": +
+  "[Z-A]": +
+"#;
+    let node: NodeRef = node!(node, yaml);
+    let model: Model = Model::empty();
+
+    let res = ProcDef::parse(&model, model.as_scoped(), &node);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::ProcFileWatchParse { pattern, proc, .. },
+        {
+            assert_eq!("[Z-A]", pattern);
+            assert_eq!("update_hosts", proc);
+        }
+    );
+}
+
 #[test]
 fn proc_def_run_parse_err() {
     // language=yaml
@@ -3,8 +3,8 @@ use kg_diag::BasicDiag;
 use kg_tree::opath::Opath;
 use kg_tree::FileFormat;
 use op_model::{Case, OutputMode, Switch, TaskDef, TaskEnv, TaskKind, TaskOutput};
-use std::str::FromStr;
 use op_test_helpers::UnwrapDisplay;
+use std::str::FromStr;
 
 #[test]
 fn proc_kind_from_str() {
@@ -289,7 +289,7 @@ expr: "@.some.expr"
         OutputMode::Var(_) => panic!("Expr expected"),
         OutputMode::Expr(opath) => assert_eq!("@.some.expr", opath.to_string()),
     }
-    assert_eq!(FileFormat::Json, out.format());
+    assert_eq!(Some(FileFormat::Json), out.format());
 }
 
 #[test]
@@ -306,7 +306,25 @@ fn task_output_parse_string() {
         }
         OutputMode::Expr(_) => panic!("Var expected"),
     }
-    assert_eq!(FileFormat::Yaml, out.format());
+    assert_eq!(Some(FileFormat::Yaml), out.format());
+}
+
+#[test]
+fn task_output_parse_auto() {
+    // language=yaml
+    let node = r#""auto""#;
+    let node: NodeRef = node!(node, yaml);
+    let out = TaskOutput::parse(&node).unwrap_disp();
+    assert_eq!(None, out.format());
+
+    // language=yaml
+    let node = r#"
+format: auto
+var: result
+"#;
+    let node: NodeRef = node!(node, yaml);
+    let out = TaskOutput::parse(&node).unwrap_disp();
+    assert_eq!(None, out.format());
 }
 
 #[test]
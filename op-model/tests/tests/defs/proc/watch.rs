@@ -23,7 +23,7 @@ fn model_watch_parse_err() {
 
 #[test]
 fn file_watch_parse() {
-    let w = FileWatch::parse("conf/hosts/**", "+").unwrap_disp();
+    let w = FileWatch::parse("update_hosts", "conf/hosts/**", "+").unwrap_disp();
 
     let glob = GlobBuilder::new("conf/hosts/**").build().unwrap();
     assert_eq!(&glob, w.glob());
@@ -32,8 +32,15 @@ fn file_watch_parse() {
 
 #[test]
 fn file_watch_parse_err() {
-    let res = FileWatch::parse("[Z-A]", "+");
-
-    let (_err, _detail) =
-        assert_detail!(res, DefsErrorDetail, DefsErrorDetail::ProcFileWatchParse{..});
+    let res = FileWatch::parse("update_hosts", "[Z-A]", "+");
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::ProcFileWatchParse { pattern, proc, .. },
+        {
+            assert_eq!("[Z-A]", pattern);
+            assert_eq!("update_hosts", proc);
+        }
+    );
 }
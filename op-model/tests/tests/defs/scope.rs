@@ -1,6 +1,6 @@
 use super::*;
 use kg_tree::opath::{Opath, ScopeMut};
-use op_model::{AsScoped, ParsedModelDef, ScopeDef, ValueDef};
+use op_model::{AsScoped, ParsedModelDef, ScopeDef, Scoped, ValueDef};
 use op_test_helpers::{UnwrapDisplay, NodeRefExt};
 
 #[test]
@@ -164,3 +164,118 @@ fn scope_resolve_parse_err() {
     let (err, _detail) = assert_detail!(res, DefsErrorDetail, DefsErrorDetail::ScopeValParse{key, ..}, assert_eq!("dyn_variable", key));
     assert_cause!(err);
 }
+
+#[test]
+fn scope_import() {
+    // language=yaml
+    let node = r#"
+lib:
+  common:
+    greeting: "hello"
+    name_len: "${@.name.length()}"
+  extra:
+    greeting: "hi"
+host:
+  name: zeus
+  scope_import: ["@^.lib.common", "@^.lib.extra"]
+  scope:
+    local: 1
+"#;
+    let root: NodeRef = node!(node, yaml);
+    let node = root.get_child_key("host").unwrap();
+    let model: Model = Model::empty();
+
+    let def = ScopeDef::parse(&model, model.as_scoped(), &node).unwrap_disp();
+
+    assert_eq!(3, def.len());
+    assert!(!def.get_var_def("name_len").unwrap().is_static());
+
+    let scope = ScopeMut::new();
+    def.resolve(&root, &node, &scope).unwrap_disp();
+
+    let greeting = assert_one!(scope.get_var("greeting").unwrap().clone());
+    assert_eq!("hi", greeting.as_string_ext());
+}
+
+#[test]
+fn scope_import_local_shadows() {
+    // language=yaml
+    let node = r#"
+lib:
+  greeting: "hello"
+host:
+  scope_import: "@^.lib"
+  scope:
+    greeting: "${'local'}"
+"#;
+    let root: NodeRef = node!(node, yaml);
+    let node = root.get_child_key("host").unwrap();
+    let model: Model = Model::empty();
+
+    let def = ScopeDef::parse(&model, model.as_scoped(), &node).unwrap_disp();
+
+    assert_eq!(1, def.len());
+    assert!(!def.get_var_def("greeting").unwrap().is_static());
+}
+
+#[test]
+fn scope_import_non_object() {
+    // language=yaml
+    let node = r#"
+lib: "not a library"
+host:
+  scope_import: "@^.lib"
+"#;
+    let root: NodeRef = node!(node, yaml);
+    let node = root.get_child_key("host").unwrap();
+    let model: Model = Model::empty();
+
+    let res = ScopeDef::parse(&model, model.as_scoped(), &node);
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::ScopeImport { expr },
+        assert_eq!("@^.lib", expr)
+    );
+}
+
+#[test]
+fn scoped_resolve_cycle() {
+    // language=yaml
+    let node = r#"
+a:
+  scope:
+    a_val: 1
+b:
+  scope:
+    b_val: 2
+"#;
+    let root: NodeRef = node!(node, yaml);
+    let a_node = root.get_child_key("a").unwrap();
+    let b_node = root.get_child_key("b").unwrap();
+    let model: Model = Model::empty();
+
+    let a_def = ScopeDef::parse(&model, model.as_scoped(), &a_node).unwrap_disp();
+    let b_def = ScopeDef::parse(&model, model.as_scoped(), &b_node).unwrap_disp();
+    let a = Scoped::new(&root, &a_node, a_def);
+    let b = Scoped::new(&root, &b_node, b_def);
+
+    // deliberately make each scope the parent of the other one
+    unsafe {
+        a.add_child(&b);
+        b.add_child(&a);
+    }
+
+    let res = a.scope().map(|_| ());
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DefsErrorDetail,
+        DefsErrorDetail::ScopeCycle { path, keys },
+        {
+            assert_eq!("$.a", path);
+            assert_eq!(&["a_val".to_string()][..], &keys[..]);
+        }
+    );
+}
@@ -188,3 +188,171 @@ fn node_parse_err() {
     });
     assert_cause!(err);
 }
+
+#[test]
+fn yaml_merge_keys() {
+    let (_tmp, dir) = get_tmp_dir();
+    // language=yaml
+    let content = r#"
+base: &base
+  domain: example.com
+  ssh_dest:
+    port: 22
+    username: root
+zeus:
+  <<: *base
+  hostname: zeus
+hera:
+  <<: *base
+  hostname: hera
+  domain: other.com
+"#;
+    init_repo(&dir);
+    write_file!(dir.join("hosts.yaml"), content);
+    let _commit = initial_commit(&dir);
+
+    let func = LoadFileFunc::new(dir.clone(), "".into());
+    let scope = ScopeMut::new();
+    scope.set_func("loadFile".into(), Box::new(func));
+    let node = node!();
+
+    let opath = Opath::parse("loadFile('hosts.yaml')").unwrap_disp();
+
+    let res = opath
+        .apply_one_ext(&node, &node, scope.as_ref())
+        .unwrap_disp();
+
+    let zeus = res.get_key("zeus");
+    assert_eq!("zeus", zeus.get_key("hostname").as_string_ext());
+    assert_eq!("example.com", zeus.get_key("domain").as_string_ext());
+    assert_eq!(22, zeus.get_key("ssh_dest").get_key("port").as_int_ext());
+    assert!(zeus.get_child_key("<<").is_none());
+
+    let hera = res.get_key("hera");
+    assert_eq!("hera", hera.get_key("hostname").as_string_ext());
+    assert_eq!("other.com", hera.get_key("domain").as_string_ext());
+    assert_eq!(
+        "root",
+        hera.get_key("ssh_dest").get_key("username").as_string_ext()
+    );
+}
+
+#[test]
+fn include_directive() {
+    let (_tmp, dir) = get_tmp_dir();
+    std::fs::create_dir_all(dir.join("hosts/common")).unwrap();
+    // language=yaml
+    let content = r#"
+zeus:
+  $include: common/base.yaml
+  hostname: zeus
+hera:
+  $include: [common/base.yaml, common/extra.json]
+  hostname: hera
+"#;
+    write_file!(dir.join("hosts/hosts.yaml"), content);
+    // language=yaml
+    let content = r#"
+domain: example.com
+hostname: base
+"#;
+    write_file!(dir.join("hosts/common/base.yaml"), content);
+    // language=json
+    let content = r#"{"domain": "other.com"}"#;
+    write_file!(dir.join("hosts/common/extra.json"), content);
+
+    let func = LoadFileFunc::new(dir.clone(), "".into());
+    let scope = ScopeMut::new();
+    scope.set_func("loadFile".into(), Box::new(func));
+    let node = node!();
+
+    let opath = Opath::parse("loadFile('hosts/hosts.yaml')").unwrap_disp();
+
+    let res = opath
+        .apply_one_ext(&node, &node, scope.as_ref())
+        .unwrap_disp();
+
+    let zeus = res.get_key("zeus");
+    assert_eq!("zeus", zeus.get_key("hostname").as_string_ext());
+    assert_eq!("example.com", zeus.get_key("domain").as_string_ext());
+    assert!(zeus.get_child_key("$include").is_none());
+
+    let hera = res.get_key("hera");
+    assert_eq!("hera", hera.get_key("hostname").as_string_ext());
+    assert_eq!("other.com", hera.get_key("domain").as_string_ext());
+}
+
+#[test]
+fn include_cycle() {
+    let (_tmp, dir) = get_tmp_dir();
+    // language=yaml
+    let content = r#"
+a:
+  $include: b.yaml
+"#;
+    write_file!(dir.join("a.yaml"), content);
+    // language=yaml
+    let content = r#"
+b:
+  $include: a.yaml
+"#;
+    write_file!(dir.join("b.yaml"), content);
+
+    let func = LoadFileFunc::new(dir.clone(), "".into());
+    let scope = ScopeMut::new();
+    scope.set_func("loadFile".into(), Box::new(func));
+    let node = node!();
+
+    let opath = Opath::parse("loadFile('a.yaml')").unwrap_disp();
+
+    let res = opath.apply_one_ext(&node, &node, scope.as_ref());
+
+    let (err, _detail) = assert_detail!(res, FuncCallErrorDetail, FuncCallCustom { id }, {
+        assert_eq!(&FuncId::from("loadFile"), id);
+    });
+    let _cause = assert_cause!(err, op_model::DefsErrorDetail);
+}
+
+#[test]
+fn include_markers_in_values_kept() {
+    let (_tmp, dir) = get_tmp_dir();
+    // language=yaml
+    let content = r#"
+zeus:
+  hostname: zeus
+  description: "shift with << operator, see $include docs"
+"#;
+    write_file!(dir.join("hosts.yaml"), content);
+    // language=toml
+    let content = r#"
+[zeus]
+hostname = "zeus"
+"<<" = "not a merge key in toml"
+"#;
+    write_file!(dir.join("hosts.toml"), content);
+
+    let func = LoadFileFunc::new(dir.clone(), "".into());
+    let scope = ScopeMut::new();
+    scope.set_func("loadFile".into(), Box::new(func));
+    let node = node!();
+
+    let opath = Opath::parse("loadFile('hosts.yaml')").unwrap_disp();
+    let res = opath
+        .apply_one_ext(&node, &node, scope.as_ref())
+        .unwrap_disp();
+    let zeus = res.get_key("zeus");
+    assert_eq!(
+        "shift with << operator, see $include docs",
+        zeus.get_key("description").as_string_ext()
+    );
+
+    let opath = Opath::parse("loadFile('hosts.toml')").unwrap_disp();
+    let res = opath
+        .apply_one_ext(&node, &node, scope.as_ref())
+        .unwrap_disp();
+    let zeus = res.get_key("zeus");
+    assert_eq!(
+        "not a merge key in toml",
+        zeus.get_key("<<").as_string_ext()
+    );
+}
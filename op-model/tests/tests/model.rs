@@ -2,10 +2,12 @@ use super::*;
 use kg_diag::IoErrorDetail;
 use kg_tree::opath::FuncCallErrorDetail;
 use op_model::DefsErrorDetail;
-use op_model::{ Model, ModelErrorDetail, ModelErrorDetail::*};
-use op_test_helpers::{get_tmp_dir, UnwrapDisplay, init_repo};
+use op_model::{
+    HostLimit, LintSeverity, Model, ModelDef, ModelErrorDetail, ModelErrorDetail::*, ModelRef,
+    ScopedModelDef,
+};
 use op_rev::RevInfo;
-
+use op_test_helpers::{get_tmp_dir, init_repo, NodeRefExt, UnwrapDisplay};
 
 #[test]
 fn load_manifest() {
@@ -70,6 +72,22 @@ info = "unexpected string"
     let _cause = assert_cause!(err, kg_tree::serial::Error);
 }
 
+#[test]
+fn load_manifest_invalid_manifest() {
+    let (_tmp, dir) = get_tmp_dir();
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+descripton = "typo"
+"#;
+    write_file!(dir.join("op.toml"), content);
+    let res = Model::load_manifest(&dir);
+
+    let (err, _detail) = assert_detail!(res, ModelErrorDetail, ManifestInvalid { .. });
+    let _cause = assert_cause!(err, DefsErrorDetail);
+}
+
 #[test]
 fn read_include_item_expr_err() {
     let (_tmp, dir) = get_tmp_dir();
@@ -227,7 +245,16 @@ hostname: some.hostname
     let res = Model::read(rev_info);
 
     let (err, _detail) = assert_detail!(res, ModelErrorDetail, DefsParse{..});
-    let _cause = assert_cause!(err, DefsErrorDetail);
+    let cause = assert_cause!(err, DefsErrorDetail);
+    match cause {
+        DefsErrorDetail::DefSource { kind, file, .. } => {
+            assert_eq!("host", kind);
+            assert!(file.ends_with("conf/hosts/fedora.yaml"));
+        }
+        _ => panic!("DefSource expected, got {}", cause),
+    }
+    let source = assert_cause!(err);
+    let _cause = assert_cause!(source, DefsErrorDetail);
 }
 
 #[test]
@@ -294,3 +321,309 @@ fn read() {
     assert_eq!("example", model.users()[0].username());
     assert_eq!("example2", model.users()[1].username());
 }
+
+#[test]
+fn hosts_in_group() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    copy_resource!("model1", &dir);
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+
+[groups]
+listed = ["fedora.domain.com"]
+resolved = "$.conf.hosts.*"
+missing = ["unknown.domain.com"]
+"#;
+    write_file!(dir.join("op.toml"), content);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = Model::read(rev_info).unwrap_disp();
+
+    let hosts = model.hosts_in_group("listed").unwrap_disp();
+    assert_eq!(1, hosts.len());
+    assert_eq!("fedora.domain.com", hosts[0].hostname());
+
+    let hosts = model.hosts_in_group("resolved").unwrap_disp();
+    assert_eq!(1, hosts.len());
+    assert_eq!("fedora.domain.com", hosts[0].hostname());
+
+    let res = model.hosts_in_group("missing");
+    let (_err, _detail) = assert_detail!(res, ModelErrorDetail, HostGroupUnknownHost { .. });
+
+    let res = model.hosts_in_group("undefined");
+    let (_err, _detail) = assert_detail!(res, ModelErrorDetail, HostGroupUnknown { .. });
+}
+
+#[test]
+fn host_limit() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    copy_resource!("model1", &dir);
+    init_repo(&dir);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = Model::read(rev_info).unwrap_disp();
+
+    let hosts = HostLimit::default().apply(model.hosts()).unwrap_disp();
+    assert_eq!(1, hosts.len());
+
+    let limit = HostLimit::new(vec!["other.domain.com".into(), "fedora*".into()]);
+    let hosts = limit.apply(model.hosts()).unwrap_disp();
+    assert_eq!(1, hosts.len());
+    assert_eq!("fedora.domain.com", hosts[0].hostname());
+
+    let limit = HostLimit::new(vec!["debian*".into()]);
+    let res = limit.apply(model.hosts());
+    let (_err, _detail) = assert_detail!(res, ModelErrorDetail, HostLimitEmpty { .. });
+
+    let limit = HostLimit::new(vec!["[invalid".into()]);
+    let res = limit.apply(model.hosts());
+    let (_err, _detail) = assert_detail!(res, ModelErrorDetail, HostLimitPattern { .. });
+}
+
+#[test]
+fn read_vars() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    copy_resource!("model1", &dir);
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+
+[vars]
+domain = "example.com"
+ntp_server = "ntp.example.com"
+"#;
+    write_file!(dir.join("op.toml"), content);
+    // language=yaml
+    let content = r#"
+vars:
+  ntp_server: ntp.fedora.domain.com
+"#;
+    write_file!(dir.join("conf/hosts/fedora.yaml"), content);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = Model::read(rev_info).unwrap_disp();
+
+    let vars = assert_one!(model
+        .scope()
+        .unwrap_disp()
+        .get_var("$vars")
+        .unwrap()
+        .clone());
+    assert_eq!("example.com", vars.get_key("domain").as_string_ext());
+    assert_eq!(
+        "ntp.example.com",
+        vars.get_key("ntp_server").as_string_ext()
+    );
+
+    let host_vars = model.hosts()[0].vars();
+    assert_eq!("example.com", host_vars.get_key("domain").as_string_ext());
+    assert_eq!(
+        "ntp.fedora.domain.com",
+        host_vars.get_key("ntp_server").as_string_ext()
+    );
+
+    let scope = model.hosts()[0]
+        .scope(model.scope_mut().unwrap_disp())
+        .unwrap_disp();
+    let vars = assert_one!(scope.get_var("$vars").unwrap().clone());
+    assert_eq!("example.com", vars.get_key("domain").as_string_ext());
+    assert_eq!(
+        "ntp.fedora.domain.com",
+        vars.get_key("ntp_server").as_string_ext()
+    );
+}
+
+#[test]
+fn lint() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    std::fs::create_dir_all(dir.join("conf/hosts")).unwrap();
+    std::fs::create_dir_all(dir.join("proc")).unwrap();
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+"#;
+    write_file!(dir.join("op.toml"), content);
+    // language=yaml
+    let content = r#"
+hostname: zeus.example.com
+ssh_dest: {}
+"#;
+    write_file!(dir.join("conf/hosts/zeus.yaml"), content);
+    write_file!(dir.join("conf/hosts/zeus2.yaml"), content);
+    // language=yaml
+    let content = r#"
+proc: exec
+scope:
+  $hosts: ${$.conf.hosts.*}
+run:
+  - tasks: []
+"#;
+    write_file!(dir.join("proc/empty.yaml"), content);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = ModelRef::read(rev_info).unwrap_disp();
+    let findings = model.lock().lint();
+
+    assert_eq!(3, findings.len());
+    assert_eq!(LintSeverity::Error, findings[0].severity());
+    assert_eq!("$.conf.hosts.zeus2", findings[0].path());
+    assert!(findings[1].message().contains("shadows"));
+    assert!(findings[2].message().contains("no tasks"));
+}
+
+#[test]
+fn read_many_hosts_deterministic_order() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    std::fs::create_dir_all(dir.join("conf/hosts")).unwrap();
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+"#;
+    write_file!(dir.join("op.toml"), content);
+
+    let count = 100;
+    for i in 0..count {
+        let content = format!("hostname: host{:03}.example.com\nssh_dest: {{}}\n", i);
+        write_file!(dir.join(format!("conf/hosts/host{:03}.yaml", i)), content);
+    }
+    let commit = initial_commit(&dir);
+
+    for _ in 0..3 {
+        let rev_info = RevInfo::new(commit, dir.clone());
+        let model = Model::read(rev_info).unwrap_disp();
+
+        assert_eq!(count, model.hosts().len());
+        for (i, h) in model.hosts().iter().enumerate() {
+            assert_eq!(format!("host{:03}.example.com", i), h.hostname());
+        }
+    }
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    for _ in 0..3 {
+        let rev_info = RevInfo::new(commit, dir.clone());
+        let model = rt.block_on(ModelRef::read_async(rev_info)).unwrap_disp();
+        let model = model.lock();
+
+        assert_eq!(count, model.hosts().len());
+        for (i, h) in model.hosts().iter().enumerate() {
+            assert_eq!(format!("host{:03}.example.com", i), h.hostname());
+        }
+    }
+}
+
+#[test]
+fn read_host_ssh_defaults() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    std::fs::create_dir_all(dir.join("conf/hosts")).unwrap();
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+
+[defaults]
+ssh_user = "deploy"
+ssh_port = 2222
+"#;
+    write_file!(dir.join("op.toml"), content);
+    // language=yaml
+    let content = r#"
+hostname: host1.example.com
+ssh_dest: {}
+"#;
+    write_file!(dir.join("conf/hosts/host1.yaml"), content);
+    // language=yaml
+    let content = r#"
+hostname: host2.example.com
+ssh_dest:
+  username: admin
+  port: 22
+"#;
+    write_file!(dir.join("conf/hosts/host2.yaml"), content);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = Model::read(rev_info).unwrap_disp();
+
+    let dest = model.hosts()[0].node().get_key("ssh_dest");
+    assert_eq!("deploy", dest.get_key("username").as_string_ext());
+    assert_eq!(2222, dest.get_key("port").as_int_ext());
+
+    let dest = model.hosts()[1].node().get_key("ssh_dest");
+    assert_eq!("admin", dest.get_key("username").as_string_ext());
+    assert_eq!(22, dest.get_key("port").as_int_ext());
+}
+
+#[test]
+fn read_mixed_format_hosts() {
+    let (_tmp, dir) = get_tmp_dir();
+    let dir = dir.join("model");
+    std::fs::create_dir_all(dir.join("conf/hosts")).unwrap();
+    init_repo(&dir);
+    // language=toml
+    let content = r#"
+[info]
+authors = [""]
+description = "Opereon model"
+"#;
+    write_file!(dir.join("op.toml"), content);
+    // language=toml
+    let content = r#"
+hostname = "host1.example.com"
+packages = ["mc", "vim"]
+
+[ssh_dest]
+port = 22
+username = "root"
+"#;
+    write_file!(dir.join("conf/hosts/host1.toml"), content);
+    // language=yaml
+    let content = r#"
+hostname: host2.example.com
+packages: [mc, vim]
+ssh_dest:
+  port: 22
+  username: root
+"#;
+    write_file!(dir.join("conf/hosts/host2.yml"), content);
+    let commit = initial_commit(&dir);
+    let rev_info = RevInfo::new(commit, dir.clone());
+
+    let model = Model::read(rev_info).unwrap_disp();
+
+    assert_eq!(2, model.hosts().len());
+    assert_eq!("host1.example.com", model.hosts()[0].hostname());
+    assert_eq!("host2.example.com", model.hosts()[1].hostname());
+    for host in model.hosts() {
+        let dest = host.node().get_key("ssh_dest");
+        assert_eq!(22, dest.get_key("port").as_int_ext());
+        assert_eq!("root", dest.get_key("username").as_string_ext());
+        let packages = host.node().get_key("packages").as_array_ext();
+        assert_eq!(2, packages.len());
+    }
+}
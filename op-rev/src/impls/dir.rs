@@ -1,6 +1,13 @@
 use super::*;
 use kg_diag::io::fs;
 
+#[derive(Debug, Display, Detail)]
+#[diag(code_offset = 1200)]
+pub enum DirErrorDetail {
+    #[display(fmt = "{operation} is not supported for plain directory models")]
+    Unsupported { operation: String },
+}
+
 #[derive(Debug)]
 pub struct DirManager {
     path: PathBuf,
@@ -82,7 +89,35 @@ impl FileVersionManager for DirManager {
         unimplemented!()
     }
 
+    fn set_author(&mut self, _author: Option<Author>) {}
+
+    fn set_cancel_token(&mut self, _token: CancelToken) {}
+
     async fn get_file_diff(&mut self, _old_rev_id: Oid, _new_rev_id: Oid) -> Result<FileDiff, BasicDiag> {
         unimplemented!()
     }
-}
\ No newline at end of file
+
+    async fn changed_paths(&mut self) -> Result<Vec<PathBuf>, BasicDiag> {
+        // plain directories have no revisions to compare against
+        Ok(Vec::new())
+    }
+
+    async fn list_revisions(&mut self, _limit: usize) -> Result<Vec<RevInfo>, BasicDiag> {
+        Err(DirErrorDetail::Unsupported {
+            operation: String::from("revision history"),
+        }
+        .into())
+    }
+
+    async fn create_tag(
+        &mut self,
+        _name: &str,
+        _rev_id: Oid,
+        _message: Option<&str>,
+    ) -> Result<(), BasicDiag> {
+        Err(DirErrorDetail::Unsupported {
+            operation: String::from("tagging"),
+        }
+        .into())
+    }
+}
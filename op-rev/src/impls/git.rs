@@ -9,6 +9,9 @@ use git2::Repository;
 
 pub type GitResult<T> = Result<T, BasicDiag>;
 
+/// Marker file present in revision dirs created by partial checkout
+const PARTIAL_MARKER: &str = ".op-partial";
+
 //FIXME (jc) promote common file version manager errors to lib.rs
 #[derive(Debug, Display, Detail)]
 #[diag(code_offset = 1100)]
@@ -42,6 +45,18 @@ pub enum GitErrorDetail {
 
     #[display(fmt = "git error occurred: {err}")]
     Custom { err: git2::Error },
+
+    #[display(fmt = "cannot create tag '{name}': {err}")]
+    CreateTag { name: String, err: git2::Error },
+
+    #[display(fmt = "cannot get commit signature: {err}")]
+    Signature { err: git2::Error },
+
+    #[display(fmt = "cannot get working tree status: {err}")]
+    Status { err: git2::Error },
+
+    #[display(fmt = "git operation cancelled")]
+    Cancelled,
 }
 
 /// Get git tree for provided `oid`
@@ -76,9 +91,50 @@ fn find_last_commit(repo: &Repository) -> GitResult<Option<git2::Commit>> {
     Ok(Some(commit))
 }
 
+/// Returns signature for provided `author`, or default repository signature.
+fn get_signature(
+    repo: &Repository,
+    author: Option<&Author>,
+) -> GitResult<git2::Signature<'static>> {
+    let sig = match author {
+        Some(author) => git2::Signature::now(author.name(), author.email()),
+        None => repo.signature(),
+    };
+    sig.map_err(|err| GitErrorDetail::Signature { err }.into())
+}
+
+/// Returns `Cancelled` error if `cancel` token is set, `err` otherwise. Used to report
+/// errors caused by aborting libgit2 operations from callbacks.
+fn cancelled_or(cancel: &CancelToken, err: GitErrorDetail) -> GitErrorDetail {
+    if cancel.is_cancelled() {
+        GitErrorDetail::Cancelled
+    } else {
+        err
+    }
+}
+
+fn check_cancelled(cancel: &CancelToken) -> GitResult<()> {
+    if cancel.is_cancelled() {
+        Err(GitErrorDetail::Cancelled.into())
+    } else {
+        Ok(())
+    }
+}
+
+/// Checkout options aborting checkout when `cancel` token is set.
+fn checkout_opts<'cb>(target_dir: &Path, cancel: &'cb CancelToken) -> CheckoutBuilder<'cb> {
+    let mut opts = CheckoutBuilder::new();
+    opts.target_dir(target_dir);
+    opts.recreate_missing(true);
+    opts.notify_on(git2::CheckoutNotificationType::UPDATED);
+    opts.notify(move |_, _, _, _, _| !cancel.is_cancelled());
+    opts
+}
+
 /// Update provided repository index and return created tree Oid.
 /// Clear index and rebuild it from working dir. Necessary to reflect .gitignore changes.
-fn update_index(repo: &Repository) -> GitResult<git2::Oid> {
+/// Adding files is aborted when `cancel` token is set.
+fn update_index(repo: &Repository, cancel: &CancelToken) -> GitResult<git2::Oid> {
     let mut index = repo
         .index()
         .map_err(|err| GitErrorDetail::GetIndex { err })?;
@@ -89,9 +145,16 @@ fn update_index(repo: &Repository) -> GitResult<git2::Oid> {
 
     let opts = git2::IndexAddOption::default();
 
+    let mut cb = |_path: &Path, _spec: &[u8]| -> i32 {
+        if cancel.is_cancelled() {
+            -1
+        } else {
+            0
+        }
+    };
     index
-        .add_all(&["*"], opts, None)
-        .map_err(|err| GitErrorDetail::Custom { err })?;
+        .add_all(&["*"], opts, Some(&mut cb))
+        .map_err(|err| cancelled_or(cancel, GitErrorDetail::Custom { err }))?;
     // Changes in index won't be saved to disk until index.write*() called.
     let oid = index
         .write_tree()
@@ -107,6 +170,10 @@ pub struct GitManager {
     path: PathBuf,
     /// Contains opened repository
     repo: Arc<Mutex<git2::Repository>>,
+    /// Commit author, if not set identity from git config is used
+    author: Option<Author>,
+    /// Token for cancelling running operations
+    cancel: CancelToken,
 }
 
 impl GitManager {
@@ -120,6 +187,8 @@ impl GitManager {
             Ok(GitManager {
                 path,
                 repo: Arc::new(Mutex::new(repo)),
+                author: None,
+                cancel: CancelToken::new(),
             })
         }).await.unwrap()
     }
@@ -163,6 +232,8 @@ impl GitManager {
             Ok(GitManager {
                 path,
                 repo: Arc::new(Mutex::new(repo)),
+                author: None,
+                cancel: CancelToken::new(),
             })
         }).await.unwrap()
     }
@@ -171,10 +242,16 @@ impl GitManager {
         self.repo.clone()
     }
 
+    fn checkout_path(&self, rev_id: Oid) -> PathBuf {
+        let mut checkout_path = self.path.join(".op/revs");
+        checkout_path.push(&format!("{:12}", rev_id));
+        checkout_path
+    }
+
     pub fn update_index(&self) -> GitResult<git2::Oid> {
         let r = self.repo();
         let repo = r.lock().unwrap();
-        update_index(&*repo)
+        update_index(&*repo, &self.cancel)
     }
 }
 
@@ -200,7 +277,11 @@ impl FileVersionManager for GitManager {
                     let obj = guard
                         .revparse_single(&spec)
                         .map_err(|err| GitErrorDetail::RevisionNotFound { err })?;
-                    Ok(obj.id().into())
+                    // annotated tags must be peeled to the commit they point to
+                    let commit = obj
+                        .peel_to_commit()
+                        .map_err(|err| GitErrorDetail::UnexpectedObjectType { err })?;
+                    Ok(commit.id().into())
                 }).await.unwrap()
             }
         }
@@ -210,23 +291,29 @@ impl FileVersionManager for GitManager {
         if rev_id.is_nil() {
             Ok(RevInfo::new(rev_id, self.path.clone()))
         } else {
-            let mut checkout_path = self.path.join(".op/revs");
-            checkout_path.push(&format!("{:12}", rev_id));
+            let checkout_path = self.checkout_path(rev_id);
 
-            if checkout_path.is_dir() {
+            if checkout_path.is_dir() && !checkout_path.join(PARTIAL_MARKER).exists() {
                 Ok(RevInfo::new(rev_id, checkout_path))
             } else {
                 let repo = self.repo();
+                let cancel = self.cancel.clone();
                 spawn_blocking(move || {
+                    check_cancelled(&cancel)?;
                     fs::create_dir_all(&checkout_path)?;
+                    // mark revision dir as incomplete until checkout finishes, in case it is cancelled
+                    let marker = checkout_path.join(PARTIAL_MARKER);
+                    fs::write(&marker, "")?;
                     let repo = repo.lock().unwrap();
                     let tree = get_tree(&*repo, rev_id)?;
 
-                    let mut opts = CheckoutBuilder::new();
-                    opts.target_dir(&checkout_path);
-                    opts.recreate_missing(true);
+                    let mut opts = checkout_opts(&checkout_path, &cancel);
                     repo.checkout_tree(tree.as_object(), Some(&mut opts))
-                        .map_err(|err| GitErrorDetail::Checkout { rev_id, err })?;
+                        .map_err(|err| {
+                            cancelled_or(&cancel, GitErrorDetail::Checkout { rev_id, err })
+                        })?;
+
+                    std::fs::remove_file(&marker).into_diag_res()?;
 
                     Ok(RevInfo::new(rev_id, checkout_path))
                 }).await.unwrap()
@@ -234,17 +321,54 @@ impl FileVersionManager for GitManager {
         }
     }
 
+    async fn checkout_paths(
+        &mut self,
+        rev_id: Oid,
+        paths: &[PathBuf],
+    ) -> Result<RevInfo, BasicDiag> {
+        let checkout_path = self.checkout_path(rev_id);
+
+        if rev_id.is_nil()
+            || (checkout_path.is_dir() && !checkout_path.join(PARTIAL_MARKER).exists())
+        {
+            return self.checkout(rev_id).await;
+        }
+
+        let repo = self.repo();
+        let paths = paths.to_vec();
+        let cancel = self.cancel.clone();
+        spawn_blocking(move || {
+            check_cancelled(&cancel)?;
+            fs::create_dir_all(&checkout_path)?;
+            // mark revision dir as incomplete, so that full checkout is not served from it
+            fs::write(checkout_path.join(PARTIAL_MARKER), "")?;
+            let repo = repo.lock().unwrap();
+            let tree = get_tree(&*repo, rev_id)?;
+
+            let mut opts = checkout_opts(&checkout_path, &cancel);
+            for p in paths.iter() {
+                opts.path(p);
+            }
+            repo.checkout_tree(tree.as_object(), Some(&mut opts))
+                .map_err(|err| cancelled_or(&cancel, GitErrorDetail::Checkout { rev_id, err }))?;
+
+            Ok(RevInfo::new(rev_id, checkout_path))
+        }).await.unwrap()
+    }
+
     async fn commit(&mut self, message: &str) -> Result<Oid, BasicDiag> {
         let repo = self.repo();
         let message = message.to_string();
+        let author = self.author.clone();
+        let cancel = self.cancel.clone();
 
         spawn_blocking(move || {
+            check_cancelled(&cancel)?;
             let repo = repo.lock().unwrap();
-            let sig = repo
-                .signature()
-                .map_err(|err| GitErrorDetail::Custom { err })?;
+            let sig = get_signature(&*repo, author.as_ref())?;
 
-            let oid = update_index(&*repo)?;
+            let oid = update_index(&*repo, &cancel)?;
+            check_cancelled(&cancel)?;
             let parent = find_last_commit(&*repo)?;
             let tree = get_tree(&*repo, oid.into())?;
 
@@ -271,6 +395,14 @@ impl FileVersionManager for GitManager {
         }).await.unwrap()
     }
 
+    fn set_author(&mut self, author: Option<Author>) {
+        self.author = author;
+    }
+
+    fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel = token;
+    }
+
     async fn get_file_diff(&mut self, old_rev_id: Oid, new_rev_id: Oid) -> Result<FileDiff, BasicDiag> {
         //FIXME (jc) error handling
 
@@ -279,8 +411,10 @@ impl FileVersionManager for GitManager {
         }
 
         let repo = self.repo();
+        let cancel = self.cancel.clone();
 
         spawn_blocking(move || {
+            check_cancelled(&cancel)?;
             let repo = repo.lock().unwrap();
             let mut opts = git2::DiffOptions::new();
             opts.minimal(true);
@@ -312,6 +446,7 @@ impl FileVersionManager for GitManager {
             find_opts.renames_from_rewrites(true);
             find_opts.remove_unmodified(true);
 
+            check_cancelled(&cancel)?;
             diff.find_similar(Some(&mut find_opts))
                 .map_err(|err| GitErrorDetail::Custom { err })?;
 
@@ -320,6 +455,108 @@ impl FileVersionManager for GitManager {
             Ok(FileDiff::new(changes))
         }).await.unwrap()
     }
+
+    async fn changed_paths(&mut self) -> Result<Vec<PathBuf>, BasicDiag> {
+        let repo = self.repo();
+
+        spawn_blocking(move || {
+            let repo = repo.lock().unwrap();
+            let mut opts = git2::StatusOptions::new();
+            opts.include_untracked(true);
+            opts.recurse_untracked_dirs(true);
+            opts.include_ignored(false);
+
+            let statuses = repo
+                .statuses(Some(&mut opts))
+                .map_err(|err| GitErrorDetail::Status { err })?;
+
+            let mut paths: Vec<PathBuf> = statuses
+                .iter()
+                .filter(|e| e.status() != git2::Status::CURRENT)
+                .filter_map(|e| e.path().map(PathBuf::from))
+                .collect();
+            paths.sort();
+            Ok(paths)
+        }).await.unwrap()
+    }
+
+    async fn list_revisions(&mut self, limit: usize) -> Result<Vec<RevInfo>, BasicDiag> {
+        let repo = self.repo();
+        let path = self.path.clone();
+        let cancel = self.cancel.clone();
+
+        spawn_blocking(move || {
+            let repo = repo.lock().unwrap();
+            if find_last_commit(&*repo)?.is_none() {
+                return Ok(Vec::new());
+            }
+
+            let mut walk = repo
+                .revwalk()
+                .map_err(|err| GitErrorDetail::Custom { err })?;
+            walk.push_head()
+                .map_err(|err| GitErrorDetail::Custom { err })?;
+            walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)
+                .map_err(|err| GitErrorDetail::Custom { err })?;
+
+            let mut revs = Vec::new();
+            for oid in walk.take(limit) {
+                check_cancelled(&cancel)?;
+                let oid = oid.map_err(|err| GitErrorDetail::Custom { err })?;
+                let commit = repo
+                    .find_commit(oid)
+                    .map_err(|err| GitErrorDetail::RevisionNotFound { err })?;
+
+                let author = commit.author();
+                let mut rev = RevInfo::new(oid.into(), path.clone());
+                rev.set_author(Some(format!(
+                    "{} <{}>",
+                    author.name().unwrap_or_default(),
+                    author.email().unwrap_or_default()
+                )));
+                rev.set_timestamp(Some(commit.time().seconds()));
+                rev.set_message(commit.message().map(|m| m.trim_end().to_string()));
+                revs.push(rev);
+            }
+            Ok(revs)
+        }).await.unwrap()
+    }
+
+    async fn create_tag(
+        &mut self,
+        name: &str,
+        rev_id: Oid,
+        message: Option<&str>,
+    ) -> Result<(), BasicDiag> {
+        let repo = self.repo();
+        let name = name.to_string();
+        let message = message.map(|m| m.to_string());
+        let author = self.author.clone();
+
+        spawn_blocking(move || {
+            let repo = repo.lock().unwrap();
+            let obj = repo
+                .find_object(rev_id.into(), None)
+                .map_err(|err| GitErrorDetail::RevisionNotFound { err })?;
+
+            if let Some(message) = message {
+                let sig = get_signature(&*repo, author.as_ref())?;
+                repo.tag(&name, &obj, &sig, &message, false)
+                    .map_err(|err| GitErrorDetail::CreateTag {
+                        name: name.clone(),
+                        err,
+                    })?;
+            } else {
+                repo.tag_lightweight(&name, &obj, false).map_err(|err| {
+                    GitErrorDetail::CreateTag {
+                        name: name.clone(),
+                        err,
+                    }
+                })?;
+            }
+            Ok(())
+        }).await.unwrap()
+    }
 }
 
 
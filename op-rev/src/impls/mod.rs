@@ -3,6 +3,7 @@ use super::*;
 mod dir;
 mod git;
 
+pub use self::dir::DirErrorDetail;
 pub use self::dir::DirManager;
 pub use self::git::GitManager;
 pub use self::git::GitErrorDetail;
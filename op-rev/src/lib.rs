@@ -15,6 +15,8 @@ extern crate kg_diag_derive;
 extern crate async_trait;
 
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::thread;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
@@ -29,15 +31,77 @@ pub use self::meta::*;
 pub use self::impls::*;
 
 
+/// Cancellation flag shared between file version manager and operations using it.
+/// Long running repository operations check it and fail with "cancelled" error when set.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn reset(&self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 #[async_trait]
 pub trait FileVersionManager: Send + std::fmt::Debug {
     async fn resolve(&mut self, rev_path: &RevPath) -> Result<Oid, BasicDiag>;
 
     async fn checkout(&mut self, rev_id: Oid) -> Result<RevInfo, BasicDiag>;
 
+    /// Checkout only provided `paths` (relative to repository root) from revision `rev_id`.
+    /// Defaults to full checkout.
+    async fn checkout_paths(
+        &mut self,
+        rev_id: Oid,
+        paths: &[PathBuf],
+    ) -> Result<RevInfo, BasicDiag> {
+        let _ = paths;
+        self.checkout(rev_id).await
+    }
+
     async fn commit(&mut self, message: &str) -> Result<Oid, BasicDiag>;
 
+    /// Set identity used for commits and annotated tags. When not set, identity configured in
+    /// the repository is used.
+    fn set_author(&mut self, author: Option<Author>);
+
+    /// Set token used to cancel running operations.
+    fn set_cancel_token(&mut self, token: CancelToken);
+
     async fn get_file_diff(&mut self, old_rev_id: Oid, new_rev_id: Oid) -> Result<FileDiff, BasicDiag>;
+
+    /// Returns paths (relative to repository root) of files changed in the working tree
+    /// compared to the current revision, including untracked files.
+    async fn changed_paths(&mut self) -> Result<Vec<PathBuf>, BasicDiag>;
+
+    /// Returns `true` if the working tree has uncommitted changes.
+    async fn is_dirty(&mut self) -> Result<bool, BasicDiag> {
+        Ok(!self.changed_paths().await?.is_empty())
+    }
+
+    /// List at most `limit` most recent revisions, newest first.
+    async fn list_revisions(&mut self, limit: usize) -> Result<Vec<RevInfo>, BasicDiag>;
+
+    /// Create tag `name` pointing to revision `rev_id`. Annotated tag is created when `message`
+    /// is provided, lightweight tag otherwise.
+    async fn create_tag(
+        &mut self,
+        name: &str,
+        rev_id: Oid,
+        message: Option<&str>,
+    ) -> Result<(), BasicDiag>;
 }
 
 
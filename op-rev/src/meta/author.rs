@@ -0,0 +1,78 @@
+use super::*;
+
+/// Identity used as commit author and committer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Author {
+    name: String,
+    email: String,
+}
+
+impl Author {
+    pub fn new<N: Into<String>, E: Into<String>>(name: N, email: E) -> Author {
+        Author {
+            name: name.into(),
+            email: email.into(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+}
+
+impl std::fmt::Display for Author {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+impl std::str::FromStr for Author {
+    type Err = String;
+
+    /// Parses `Name <email>` format used in manifest `[info].authors`, a bare email is used
+    /// both as name and email.
+    fn from_str(s: &str) -> Result<Author, Self::Err> {
+        let s = s.trim();
+        if let (Some(start), true) = (s.find('<'), s.ends_with('>')) {
+            let name = s[..start].trim();
+            let email = s[start + 1..s.len() - 1].trim();
+            if name.is_empty() {
+                Ok(Author::new(email, email))
+            } else {
+                Ok(Author::new(name, email))
+            }
+        } else if s.contains('@') {
+            Ok(Author::new(s, s))
+        } else if !s.is_empty() {
+            Ok(Author::new(s, ""))
+        } else {
+            Err("author cannot be empty".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_name_email() {
+        let a: Author = "John Doe <john@example.com>".parse().unwrap();
+        assert_eq!(Author::new("John Doe", "john@example.com"), a);
+    }
+
+    #[test]
+    fn parse_email() {
+        let a: Author = "john@example.com".parse().unwrap();
+        assert_eq!(Author::new("john@example.com", "john@example.com"), a);
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!("  ".parse::<Author>().is_err());
+    }
+}
@@ -1,10 +1,12 @@
 use super::*;
 
+mod author;
 mod oid;
 mod rev_info;
 mod rev_path;
 mod diff;
 
+pub use self::author::*;
 pub use self::oid::*;
 pub use self::rev_info::*;
 pub use self::rev_path::*;
@@ -8,6 +8,15 @@ pub struct RevInfo {
     id: Oid,
     /// Path to model dir
     path: PathBuf,
+    /// Commit author, as `Name <email>`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    /// Commit time, in seconds since unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<i64>,
+    /// Commit message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
 }
 
 impl Debug for RevInfo {
@@ -15,6 +24,9 @@ impl Debug for RevInfo {
         f.debug_struct("RevInfo")
             .field("id", &self.id().to_string())
             .field("path", &self.path)
+            .field("author", &self.author)
+            .field("timestamp", &self.timestamp)
+            .field("message", &self.message)
             .finish()
     }
 }
@@ -24,6 +36,9 @@ impl RevInfo {
         RevInfo {
             id,
             path,
+            author: None,
+            timestamp: None,
+            message: None,
         }
     }
 
@@ -42,6 +57,30 @@ impl RevInfo {
     pub fn set_path(&mut self, path: PathBuf) {
         self.path = path;
     }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn set_author(&mut self, author: Option<String>) {
+        self.author = author;
+    }
+
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    pub fn set_timestamp(&mut self, timestamp: Option<i64>) {
+        self.timestamp = timestamp;
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
 }
 
 impl Default for RevInfo {
@@ -49,6 +88,9 @@ impl Default for RevInfo {
         RevInfo {
             id: Oid::nil(),
             path: PathBuf::new(),
+            author: None,
+            timestamp: None,
+            message: None,
         }
     }
 }
@@ -0,0 +1,33 @@
+use super::*;
+use op_rev::{DirErrorDetail, DirManager, FileVersionManager, Oid};
+use op_test_helpers::{get_tmp_dir, UnwrapDisplay};
+
+#[test]
+fn list_revisions_unsupported() {
+    let (_tmp, dir) = get_tmp_dir();
+    let mut manager = aw!(DirManager::open(&dir)).unwrap_disp();
+
+    let res = aw!(manager.list_revisions(10));
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DirErrorDetail,
+        DirErrorDetail::Unsupported { operation },
+        assert_eq!("revision history", operation)
+    );
+}
+
+#[test]
+fn create_tag_unsupported() {
+    let (_tmp, dir) = get_tmp_dir();
+    let mut manager = aw!(DirManager::open(&dir)).unwrap_disp();
+
+    let res = aw!(manager.create_tag("v1", Oid::nil(), None));
+
+    let (_err, _detail) = assert_detail!(
+        res,
+        DirErrorDetail,
+        DirErrorDetail::Unsupported { operation },
+        assert_eq!("tagging", operation)
+    );
+}
@@ -1,8 +1,8 @@
 use super::*;
 use git2::{Repository, RepositoryInitOptions, Signature};
-use op_rev::{GitErrorDetail, FileVersionManager, RevPath};
 use op_rev::GitManager;
-use op_test_helpers::{get_tmp_dir, init_repo, ToStringExt, initial_commit, UnwrapDisplay};
+use op_rev::{Author, CancelToken, FileVersionManager, GitErrorDetail, RevPath};
+use op_test_helpers::{get_tmp_dir, init_repo, initial_commit, ToStringExt, UnwrapDisplay};
 
 #[test]
 fn new_git_manager_empty_repo() {
@@ -125,3 +125,169 @@ fn resolve_revision_str() {
 
     assert_eq!(commit, res.into());
 }
+
+#[test]
+fn create_tag_lightweight() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    let commit = initial_commit(&dir);
+    let mut git = aw!(GitManager::open(dir.clone())).unwrap_disp();
+
+    aw!(git.create_tag("v1", commit.into(), None)).unwrap_disp();
+
+    let repo = Repository::open(&dir).unwrap();
+    let tag_ref = repo.find_reference("refs/tags/v1").unwrap();
+    assert_eq!(Some(commit), tag_ref.target());
+
+    let res = aw!(git.resolve(&RevPath::Revision("v1".to_string()))).unwrap_disp();
+    assert_eq!(commit, res.into());
+}
+
+#[test]
+fn create_tag_annotated() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    let commit = initial_commit(&dir);
+    let mut git = aw!(GitManager::open(dir.clone())).unwrap_disp();
+
+    aw!(git.create_tag("v1", commit.into(), Some("Release v1"))).unwrap_disp();
+
+    let repo = Repository::open(&dir).unwrap();
+    let tag_ref = repo.find_reference("refs/tags/v1").unwrap();
+    let tag = tag_ref.peel_to_tag().unwrap();
+    assert_eq!("Release v1", tag.message().unwrap());
+    assert_eq!(commit, tag.target_id());
+
+    let res = aw!(git.resolve(&RevPath::Revision("v1".to_string()))).unwrap_disp();
+    assert_eq!(commit, res.into());
+}
+
+#[test]
+fn create_tag_exists_err() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    let commit = initial_commit(&dir);
+    let mut git = aw!(GitManager::open(dir)).unwrap_disp();
+
+    aw!(git.create_tag("v1", commit.into(), None)).unwrap_disp();
+    let res = aw!(git.create_tag("v1", commit.into(), None));
+
+    let (_err, _detail) = assert_detail!(res, GitErrorDetail, GitErrorDetail::CreateTag { .. });
+}
+
+#[test]
+fn commit_with_author() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    write_file!(dir.join("example_file.txt"), "example content");
+    let mut git = aw!(GitManager::open(dir.clone())).unwrap_disp();
+
+    git.set_author(Some(Author::new("Test", "test@example.com")));
+    let oid = aw!(git.commit("test commit")).unwrap_disp();
+
+    let repo = Repository::open(&dir).unwrap();
+    let commit = repo.find_commit(oid.into()).unwrap();
+    assert_eq!(Some("Test"), commit.author().name());
+    assert_eq!(Some("test@example.com"), commit.author().email());
+    assert_eq!(Some("Test"), commit.committer().name());
+    assert_eq!(Some("test@example.com"), commit.committer().email());
+}
+
+#[test]
+fn checkout_paths_partial() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    std::fs::create_dir_all(dir.join("conf")).unwrap();
+    std::fs::create_dir_all(dir.join("data")).unwrap();
+    write_file!(dir.join("conf/hosts.yaml"), "hosts: []");
+    write_file!(dir.join("data/blob.bin"), "large content");
+    let commit = initial_commit(&dir);
+    let mut git = aw!(GitManager::open(dir)).unwrap_disp();
+
+    let rev = aw!(git.checkout_paths(commit.into(), &["conf".into()])).unwrap_disp();
+
+    assert!(rev.path().join("conf/hosts.yaml").exists());
+    assert!(!rev.path().join("data/blob.bin").exists());
+
+    let rev = aw!(git.checkout(commit.into())).unwrap_disp();
+
+    assert!(rev.path().join("conf/hosts.yaml").exists());
+    assert!(rev.path().join("data/blob.bin").exists());
+}
+
+#[test]
+fn list_revisions() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    let mut git = aw!(GitManager::open(dir.clone())).unwrap_disp();
+    git.set_author(Some(Author::new("Test", "test@example.com")));
+
+    write_file!(dir.join("file1.txt"), "content 1");
+    let first = aw!(git.commit("first commit")).unwrap_disp();
+    write_file!(dir.join("file2.txt"), "content 2");
+    let second = aw!(git.commit("second commit")).unwrap_disp();
+
+    let revs = aw!(git.list_revisions(10)).unwrap_disp();
+
+    assert_eq!(2, revs.len());
+    assert_eq!(second, revs[0].id());
+    assert_eq!(first, revs[1].id());
+    assert_eq!(Some("second commit"), revs[0].message());
+    assert_eq!(Some("Test <test@example.com>"), revs[0].author());
+    assert!(revs[0].timestamp().is_some());
+
+    let revs = aw!(git.list_revisions(1)).unwrap_disp();
+    assert_eq!(1, revs.len());
+}
+
+#[test]
+fn list_revisions_empty_repo() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    let mut git = aw!(GitManager::open(dir)).unwrap_disp();
+
+    let revs = aw!(git.list_revisions(10)).unwrap_disp();
+
+    assert!(revs.is_empty());
+}
+
+#[test]
+fn changed_paths_dirty() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    write_file!(dir.join("file1.txt"), "content 1");
+    let mut git = aw!(GitManager::open(dir.clone())).unwrap_disp();
+    git.set_author(Some(Author::new("Test", "test@example.com")));
+    aw!(git.commit("initial commit")).unwrap_disp();
+
+    assert!(!aw!(git.is_dirty()).unwrap_disp());
+
+    write_file!(dir.join("file1.txt"), "changed content");
+    write_file!(dir.join("file2.txt"), "new file");
+
+    assert!(aw!(git.is_dirty()).unwrap_disp());
+    let paths = aw!(git.changed_paths()).unwrap_disp();
+    assert_eq!(
+        vec![std::path::PathBuf::from("file1.txt"), "file2.txt".into()],
+        paths
+    );
+}
+
+#[test]
+fn checkout_cancelled() {
+    let (_tmp, dir) = get_tmp_dir();
+    init_repo(&dir);
+    write_file!(dir.join("file1.txt"), "content 1");
+    let commit = initial_commit(&dir);
+    let mut git = aw!(GitManager::open(dir)).unwrap_disp();
+    let token = CancelToken::new();
+    git.set_cancel_token(token.clone());
+
+    token.cancel();
+    let res = aw!(git.checkout(commit.into()));
+    let (_err, _detail) = assert_detail!(res, GitErrorDetail, GitErrorDetail::Cancelled);
+
+    token.reset();
+    let rev = aw!(git.checkout(commit.into())).unwrap_disp();
+    assert!(rev.path().join("file1.txt").exists());
+}
@@ -1 +1,2 @@
+mod dir;
 mod git;
\ No newline at end of file